@@ -0,0 +1,246 @@
+use super::{auth, ErrorBody, RunRequest};
+use crate::cli::{Commands, SystemCommands, TaskCommands};
+use crate::config::Config;
+use crate::error::{Result, SigilError};
+use crate::modules::system::SystemInfo;
+use crate::output::{self, say};
+use crate::runtime::task_runner::{TaskDefinition, TaskInstance, TaskStatus};
+use reqwest::{Certificate, Client, Identity, Method, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use tracing::debug;
+
+const MODULE: &str = "agent";
+
+/// Client for a `sigil agent`.
+pub struct AgentClient {
+    client: Client,
+    base: Url,
+    /// Shared `agent.token` used to sign requests.
+    key: Option<Vec<u8>>,
+}
+
+impl AgentClient {
+    /// Connect to `address`, a `host:port` or an `http(s)://` URL. A bare
+    /// address uses HTTPS when `agent.ca_cert` or `agent.client_cert` is set.
+    pub async fn connect(config: &Config, address: &str) -> Result<Self> {
+        let settings = &config.agent;
+        let tls = settings.ca_cert.is_some() || settings.client_cert.is_some();
+        let base = if address.contains("://") {
+            address.to_string()
+        } else {
+            format!("{}://{}", if tls { "https" } else { "http" }, address)
+        };
+        let base = Url::parse(&base)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+            .ok_or_else(|| SigilError::invalid_config("--agent", &format!("'{}' is not an agent address", address)))?;
+
+        // No overall timeout: followed logs stay open for as long as the task runs.
+        let mut builder = Client::builder().connect_timeout(Duration::from_secs(config.general.timeout_seconds));
+        if let Some(ca_cert) = &settings.ca_cert {
+            let pem = read_pem(ca_cert).await?;
+            let certificate = Certificate::from_pem(&pem)
+                .map_err(|e| SigilError::invalid_config("agent.ca_cert", &e.to_string()))?;
+            builder = builder.tls_built_in_root_certs(false).add_root_certificate(certificate);
+        }
+        match (&settings.client_cert, &settings.client_key) {
+            (Some(cert), Some(key)) => {
+                let mut pem = read_pem(cert).await?;
+                pem.push(b'\n');
+                pem.extend(read_pem(key).await?);
+                let identity = Identity::from_pem(&pem)
+                    .map_err(|e| SigilError::invalid_config("agent.client_cert", &e.to_string()))?;
+                builder = builder.identity(identity);
+            }
+            (None, None) => {}
+            _ => {
+                return Err(SigilError::invalid_config(
+                    "agent.client_cert",
+                    "client_cert and client_key must be set together",
+                ))
+            }
+        }
+        let client = builder
+            .build()
+            .map_err(|e| SigilError::Network(format!("Cannot create HTTP client: {}", e)))?;
+
+        let key = match &settings.token {
+            Some(token) => Some(token.resolve(config).await?.into_bytes()),
+            None => None,
+        };
+        Ok(AgentClient { client, base, key })
+    }
+
+    fn url(&self, path: &[&str]) -> Url {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("agent address is an http(s) URL")
+            .pop_if_empty()
+            .extend(path);
+        url
+    }
+
+    /// Send a request, signed when a token is configured, and turn error
+    /// responses into errors.
+    async fn send(&self, method: Method, url: Url, body: Option<Vec<u8>>) -> Result<Response> {
+        debug!("Agent {} {}", method, url);
+        let body = body.unwrap_or_default();
+        let mut request = self.client.request(method.clone(), url.clone());
+        if let Some(key) = &self.key {
+            let signed_path = match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            };
+            request = request.header("Authorization", auth::authorization(key, method.as_str(), &signed_path, &body));
+        }
+        if !body.is_empty() {
+            request = request.header("Content-Type", "application/json").body(body);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| SigilError::Network(format!("Agent request failed: {}", e)))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let text = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<ErrorBody>(&text)
+            .map(|body| body.error)
+            .unwrap_or_else(|_| text.trim().to_string());
+        // The agent sends the full error text; keep its kind without repeating the prefix.
+        let reason = |prefix: &str| message.strip_prefix(prefix).unwrap_or(&message).to_string();
+        Err(match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                SigilError::Authentication(format!("Agent denied access: {}", reason("Authentication error: ")))
+            }
+            StatusCode::NOT_FOUND => SigilError::resource_not_found(reason("Resource not found: ")),
+            StatusCode::BAD_REQUEST | StatusCode::TOO_MANY_REQUESTS => SigilError::module(MODULE.to_string(), message),
+            _ => SigilError::Network(format!("Agent returned {}: {}", status, message)),
+        })
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &[&str]) -> Result<T> {
+        let response = self.send(Method::GET, self.url(path), None).await?;
+        response
+            .json()
+            .await
+            .map_err(|e| SigilError::Network(format!("Agent response unreadable: {}", e)))
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(&self, path: &[&str], body: &B) -> Result<T> {
+        let response = self.send(Method::POST, self.url(path), Some(serde_json::to_vec(body)?)).await?;
+        response
+            .json()
+            .await
+            .map_err(|e| SigilError::Network(format!("Agent response unreadable: {}", e)))
+    }
+
+    pub async fn tasks(&self) -> Result<Vec<TaskDefinition>> {
+        self.get(&["v1", "tasks"]).await
+    }
+
+    /// Start a task; the returned instance is still pending.
+    pub async fn run_task(&self, name: &str, request: &RunRequest) -> Result<TaskInstance> {
+        self.post(&["v1", "tasks", name, "run"], request).await
+    }
+
+    /// An instance by ID, or the most recent run of a task by name.
+    pub async fn instance(&self, task: &str) -> Result<TaskInstance> {
+        self.get(&["v1", "instances", task]).await
+    }
+
+    /// Copy an instance's log to the terminal until its task process exits:
+    /// to stdout, or stderr when stdout carries JSON or YAML.
+    pub async fn follow_logs(&self, instance: &TaskInstance) -> Result<()> {
+        let mut url = self.url(&["v1", "instances", &instance.id.to_string(), "logs"]);
+        url.set_query(Some("follow=true"));
+        let mut response = self.send(Method::GET, url, None).await?;
+        let mut terminal: Box<dyn Write> = if output::is_structured() {
+            Box::new(std::io::stderr())
+        } else {
+            Box::new(std::io::stdout())
+        };
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| SigilError::Network(format!("Lost the agent's log stream: {}", e)))?
+        {
+            terminal.write_all(&chunk)?;
+            terminal.flush()?;
+        }
+        Ok(())
+    }
+
+    pub async fn system_info(&self) -> Result<SystemInfo> {
+        self.get(&["v1", "system", "info"]).await
+    }
+}
+
+async fn read_pem(path: &Path) -> Result<Vec<u8>> {
+    tokio::fs::read(path)
+        .await
+        .map_err(|e| SigilError::invalid_config(path.display().to_string(), e.to_string()))
+}
+
+/// Run a command on the agent at `address` instead of locally.
+pub async fn handle_command(cmd: &Commands, address: &str, config: &Config) -> Result<()> {
+    let client = AgentClient::connect(config, address).await?;
+    match cmd {
+        Commands::Task(TaskCommands::List) => {
+            output::print(client.tasks().await?.as_slice())?;
+        }
+        Commands::Task(TaskCommands::Run { name, params, hosts, targets, parallel, strategy, .. }) => {
+            let request = RunRequest {
+                params: params.clone(),
+                hosts: hosts.clone(),
+                targets: targets.clone(),
+                parallel: *parallel,
+                serial: strategy.serial,
+                max_fail_percentage: strategy.max_fail_percentage,
+                pause: strategy.pause,
+            };
+            let accepted = client.run_task(name, &request).await?;
+            crate::audit::note_instance(accepted.id);
+            say!("{}", output::decorate("🛰️ ", format!("Dispatched task '{}' to {} as {}", name, client.base, accepted.id)));
+            client.follow_logs(&accepted).await?;
+
+            let instance = client.instance(&accepted.id.to_string()).await?;
+            output::structured(&instance)?;
+            match instance.status {
+                TaskStatus::Completed => {}
+                TaskStatus::Failed | TaskStatus::Cancelled => {
+                    return Err(SigilError::task_execution(format!(
+                        "Task '{}' failed on the agent: {}",
+                        name,
+                        instance.error.as_deref().unwrap_or("no error recorded")
+                    )))
+                }
+                status => {
+                    return Err(SigilError::task_execution(format!(
+                        "Task '{}' is still {:?} on the agent; check it with 'sigil --agent {} task status {}'",
+                        name, status, address, instance.id
+                    )))
+                }
+            }
+        }
+        Commands::Task(TaskCommands::Status { task }) => {
+            output::print(&client.instance(task).await?)?;
+        }
+        Commands::System(SystemCommands::Info) => {
+            output::print(&client.system_info().await?)?;
+        }
+        _ => {
+            return Err(SigilError::invalid_config(
+                "--agent",
+                "Only 'task list', 'task run', 'task status' and 'system info' can be sent to an agent",
+            ))
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,42 @@
+//! `sigil agent`: an HTTP/JSON API for running tasks on this host, and the
+//! client that `--agent` uses to drive it from a controller.
+//!
+//! Requests are authenticated by an HMAC signature over the request made
+//! with the shared `agent.token`, by a client certificate when the agent
+//! is given `agent.client_ca`, or both. Tasks started through the API run
+//! as ordinary `sigil task run` processes, so their state is the same
+//! `TaskInstance` files either way.
+
+use crate::runtime::strategy::BatchSize;
+use serde::{Deserialize, Serialize};
+
+pub mod auth;
+pub mod client;
+pub mod server;
+
+/// Body of `POST /v1/tasks/{name}/run`, mirroring `sigil task run`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunRequest {
+    /// `key=value` parameters.
+    pub params: Vec<String>,
+    pub hosts: Vec<String>,
+    pub targets: Option<String>,
+    pub parallel: Option<u32>,
+    pub serial: Option<BatchSize>,
+    pub max_fail_percentage: Option<u8>,
+    pub pause: Option<u64>,
+}
+
+/// Body of every error response.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+/// `GET /v1/health`, the one endpoint that needs no authentication.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Health {
+    pub status: String,
+    pub version: String,
+}
@@ -0,0 +1,438 @@
+use super::auth::Verifier;
+use super::{ErrorBody, Health, RunRequest};
+use crate::config::{Config, LoadOptions};
+use crate::error::{Result, SigilError};
+use crate::modules::system;
+use crate::output::{self, say};
+use crate::runtime::background;
+use crate::runtime::task_runner::{self, TaskInstance};
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Body, Bytes, Frame, Incoming};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{self, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Request bodies are small JSON documents.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// How often a followed log is checked for new output.
+const LOG_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+type ResponseBody = BoxBody<Bytes, Infallible>;
+
+/// An error response: status and message.
+struct ApiError(StatusCode, String);
+
+impl From<SigilError> for ApiError {
+    fn from(e: SigilError) -> Self {
+        let status = match &e {
+            SigilError::ResourceNotFound { .. } => StatusCode::NOT_FOUND,
+            SigilError::Authentication(_) => StatusCode::UNAUTHORIZED,
+            SigilError::TaskExecution { .. } | SigilError::InvalidConfig { .. } => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError(status, e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        ApiError(StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e))
+    }
+}
+
+/// A run started by this agent process.
+struct Run {
+    /// What was returned when the run was accepted, served until the task
+    /// process saves its own state.
+    accepted: TaskInstance,
+    finished: bool,
+}
+
+struct Agent {
+    config: Config,
+    /// Passed on to task processes so they load the same configuration.
+    options: LoadOptions,
+    verifier: Option<Verifier>,
+    runs: Mutex<HashMap<Uuid, Run>>,
+}
+
+/// Serve the agent API until interrupted.
+pub async fn serve(config: &Config, options: &LoadOptions, listen: Option<&str>) -> Result<()> {
+    let settings = &config.agent;
+    let listen = listen.unwrap_or(&settings.listen);
+    let address: SocketAddr = listen
+        .parse()
+        .map_err(|_| SigilError::invalid_config("agent.listen".to_string(), format!("'{}' is not an address", listen)))?;
+
+    let verifier = match &settings.token {
+        Some(token) => Some(Verifier::new(&token.resolve(config).await?)),
+        None => None,
+    };
+    let acceptor = match (&settings.tls_cert, &settings.tls_key) {
+        (Some(cert), Some(key)) => Some(tls_acceptor(cert, key, settings.client_ca.as_deref())?),
+        (None, None) => None,
+        _ => {
+            return Err(SigilError::invalid_config(
+                "agent.tls_cert".to_string(),
+                "tls_cert and tls_key must be set together".to_string(),
+            ))
+        }
+    };
+    let mutual_tls = acceptor.is_some() && settings.client_ca.is_some();
+    if verifier.is_none() && !mutual_tls {
+        return Err(SigilError::invalid_config(
+            "agent.token".to_string(),
+            "The agent needs agent.token or agent.client_ca to authenticate requests".to_string(),
+        ));
+    }
+    if acceptor.is_none() {
+        warn!("⚠️  Serving plain HTTP: requests are signed but task output is not encrypted");
+    }
+
+    let agent = Arc::new(Agent {
+        config: config.clone(),
+        options: options.clone(),
+        verifier,
+        runs: Mutex::new(HashMap::new()),
+    });
+    let listener = TcpListener::bind(address).await?;
+    say!(
+        "{}",
+        output::decorate(
+            "🛰️ ",
+            format!(
+                "Agent listening on {}://{}{}",
+                if acceptor.is_some() { "https" } else { "http" },
+                address,
+                if mutual_tls { " (client certificates required)" } else { "" }
+            )
+        )
+    );
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = tokio::signal::ctrl_c() => {
+                say!("{}", output::decorate("👋", "Agent stopped"));
+                return Ok(());
+            }
+        };
+        let agent = Arc::clone(&agent);
+        let acceptor = acceptor.clone();
+        tokio::spawn(async move {
+            match acceptor {
+                Some(acceptor) => match acceptor.accept(stream).await {
+                    Ok(stream) => serve_connection(stream, agent, peer).await,
+                    Err(e) => debug!("TLS handshake with {} failed: {}", peer, e),
+                },
+                None => serve_connection(stream, agent, peer).await,
+            }
+        });
+    }
+}
+
+async fn serve_connection<S>(stream: S, agent: Arc<Agent>, peer: SocketAddr)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |request| {
+        let agent = Arc::clone(&agent);
+        async move { Ok::<_, Infallible>(agent.handle(request, peer).await) }
+    });
+    if let Err(e) = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await {
+        debug!("Connection from {} ended: {}", peer, e);
+    }
+}
+
+/// Server config for `cert`/`key`, verifying client certificates against
+/// `client_ca` when given.
+fn tls_acceptor(cert: &Path, key: &Path, client_ca: Option<&Path>) -> Result<TlsAcceptor> {
+    let tls_error = |path: &Path, e: &dyn std::fmt::Display| {
+        SigilError::invalid_config(path.display().to_string(), e.to_string())
+    };
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<std::result::Result<Vec<_>, _>>())
+        .map_err(|e| tls_error(cert, &e))?;
+    let key_der = PrivateKeyDer::from_pem_file(key).map_err(|e| tls_error(key, &e))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| tls_error(cert, &e))?;
+    let builder = match client_ca {
+        Some(ca) => {
+            let mut roots = RootCertStore::empty();
+            for ca_cert in CertificateDer::pem_file_iter(ca).map_err(|e| tls_error(ca, &e))? {
+                roots.add(ca_cert.map_err(|e| tls_error(ca, &e))?).map_err(|e| tls_error(ca, &e))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(|e| tls_error(ca, &e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let server_config = builder.with_single_cert(certs, key_der).map_err(|e| tls_error(cert, &e))?;
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+impl Agent {
+    async fn handle(self: Arc<Self>, request: Request<Incoming>, peer: SocketAddr) -> Response<ResponseBody> {
+        let method = request.method().clone();
+        let path = request.uri().path().to_string();
+        let query = request.uri().query().unwrap_or_default().to_string();
+        debug!("{} {} {}", peer, method, request.uri());
+
+        let result = async {
+            let authorization = request
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            let signed_path = request.uri().path_and_query().map_or("/", |pq| pq.as_str()).to_string();
+            let body = Limited::new(request.into_body(), MAX_BODY_BYTES)
+                .collect()
+                .await
+                .map_err(|e| ApiError(StatusCode::PAYLOAD_TOO_LARGE, e.to_string()))?
+                .to_bytes();
+
+            if path != "/v1/health" {
+                if let Some(verifier) = &self.verifier {
+                    verifier.verify(authorization.as_deref(), method.as_str(), &signed_path, &body)?;
+                }
+            }
+            self.route(&method, &path, &query, &body).await
+        }
+        .await;
+
+        result.unwrap_or_else(|ApiError(status, message)| {
+            if status.is_server_error() {
+                warn!("⚠️  {} {}: {}", method, path, message);
+            }
+            json_response(status, &ErrorBody { error: message })
+        })
+    }
+
+    async fn route(self: &Arc<Self>, method: &Method, path: &str, query: &str, body: &[u8]) -> std::result::Result<Response<ResponseBody>, ApiError> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (method, segments.as_slice()) {
+            (&Method::GET, ["v1", "health"]) => Ok(json_response(
+                StatusCode::OK,
+                &Health {
+                    status: "ok".to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+            )),
+            (&Method::GET, ["v1", "system", "info"]) => {
+                Ok(json_response(StatusCode::OK, &system::get_system_info().await?))
+            }
+            (&Method::GET, ["v1", "tasks"]) => {
+                Ok(json_response(StatusCode::OK, &task_runner::task_definitions(&self.config).await?))
+            }
+            (&Method::POST, ["v1", "tasks", name, "run"]) => {
+                let request: RunRequest = if body.is_empty() { RunRequest::default() } else { serde_json::from_slice(body)? };
+                self.start(name, request).await
+            }
+            (&Method::GET, ["v1", "instances", task]) => Ok(json_response(StatusCode::OK, &self.instance(task).await?)),
+            (&Method::GET, ["v1", "instances", id, "logs"]) => self.logs(id, query),
+            (_, ["v1", "health" | "tasks"] | ["v1", "system", "info"] | ["v1", "tasks", _, "run"] | ["v1", "instances", ..]) => {
+                Err(ApiError(StatusCode::METHOD_NOT_ALLOWED, format!("{} is not allowed on {}", method, path)))
+            }
+            _ => Err(ApiError(StatusCode::NOT_FOUND, format!("No such endpoint: {}", path))),
+        }
+    }
+
+    /// Validate a run request, then start `sigil task run` for it in the
+    /// background with output going to `<state_dir>/<id>.log`.
+    async fn start(self: &Arc<Self>, name: &str, request: RunRequest) -> std::result::Result<Response<ResponseBody>, ApiError> {
+        // Task names become file names under `tasks.definitions_dir`.
+        if name.starts_with('.') || !name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)) {
+            return Err(ApiError(StatusCode::BAD_REQUEST, format!("Invalid task name '{}'", name)));
+        }
+        let running = self.runs.lock().expect("runs lock poisoned").values().filter(|run| !run.finished).count();
+        if running >= self.config.tasks.max_concurrent_tasks {
+            return Err(ApiError(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("{} tasks are already running (tasks.max_concurrent_tasks)", running),
+            ));
+        }
+        let (_, parameters) = task_runner::prepare_task(name, &request.params, &self.config).await?;
+
+        let mut args = Vec::new();
+        for param in &request.params {
+            args.extend(["--params".to_string(), param.clone()]);
+        }
+        for host in &request.hosts {
+            args.extend(["--host".to_string(), host.clone()]);
+        }
+        if let Some(targets) = &request.targets {
+            args.extend(["--targets".to_string(), targets.clone()]);
+        }
+        if let Some(parallel) = request.parallel {
+            args.extend(["--parallel".to_string(), parallel.to_string()]);
+        }
+        if let Some(serial) = request.serial {
+            args.extend(["--serial".to_string(), serial.to_string()]);
+        }
+        if let Some(percent) = request.max_fail_percentage {
+            args.extend(["--max-fail-percentage".to_string(), percent.to_string()]);
+        }
+        if let Some(pause) = request.pause {
+            args.extend(["--pause".to_string(), pause.to_string()]);
+        }
+        let id = Uuid::new_v4();
+        let mut child = background::spawn(id, name, &args, &self.options, &self.config).await?;
+
+        let accepted = TaskInstance::new(id, name, parameters).redacted();
+        self.runs.lock().expect("runs lock poisoned").insert(
+            id,
+            Run {
+                accepted: accepted.clone(),
+                finished: false,
+            },
+        );
+        info!("🚀 Started task '{}' as {}", name, id);
+
+        let agent = Arc::clone(self);
+        tokio::spawn(async move {
+            let status = child.wait().await;
+            agent.finish(id, status).await;
+        });
+
+        Ok(json_response(StatusCode::ACCEPTED, &accepted))
+    }
+
+    /// Record that a task process exited. If it died before saving a final
+    /// state, mark the instance failed so controllers stop waiting.
+    async fn finish(&self, id: Uuid, status: std::io::Result<std::process::ExitStatus>) {
+        let accepted = match self.runs.lock().expect("runs lock poisoned").get_mut(&id) {
+            Some(run) => {
+                run.finished = true;
+                run.accepted.clone()
+            }
+            None => return,
+        };
+        match &status {
+            Ok(status) => info!("🏁 Task process for {} finished: {}", id, status),
+            Err(e) => info!("🏁 Task process for {} finished: {}", id, e),
+        }
+        background::settle(accepted, &status, false, &self.config).await;
+    }
+
+    /// Whether more output may still be written to an instance's log.
+    fn is_running(&self, id: &Uuid) -> bool {
+        self.runs
+            .lock()
+            .expect("runs lock poisoned")
+            .get(id)
+            .is_some_and(|run| !run.finished)
+    }
+
+    async fn instance(&self, task: &str) -> Result<TaskInstance> {
+        match task_runner::find_task_instance(task, &self.config).await {
+            Ok(instance) => Ok(instance),
+            Err(e) => {
+                // Started but not yet written by the task process.
+                let accepted = Uuid::parse_str(task).ok().and_then(|id| {
+                    self.runs.lock().expect("runs lock poisoned").get(&id).map(|run| run.accepted.clone())
+                });
+                accepted.ok_or(e)
+            }
+        }
+    }
+
+    /// Stream an instance's log from `offset`, and with `follow` keep
+    /// streaming until its process exits.
+    fn logs(self: &Arc<Self>, id: &str, query: &str) -> std::result::Result<Response<ResponseBody>, ApiError> {
+        let id = Uuid::parse_str(id).map_err(|_| ApiError(StatusCode::BAD_REQUEST, format!("Invalid instance ID '{}'", id)))?;
+        let query: HashMap<&str, &str> = query.split('&').filter_map(|pair| pair.split_once('=')).collect();
+        let follow = matches!(query.get("follow"), Some(&"true" | &"1"));
+        let mut offset: u64 = match query.get("offset") {
+            Some(offset) => offset.parse().map_err(|_| ApiError(StatusCode::BAD_REQUEST, "Invalid offset".to_string()))?,
+            None => 0,
+        };
+        let path = background::log_path(&self.config, &id);
+        if !path.exists() {
+            return Err(SigilError::resource_not_found(format!("Log for task instance {}", id)).into());
+        }
+
+        let (sender, receiver) = mpsc::channel(16);
+        let agent = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut buffer = vec![0u8; 64 * 1024];
+            loop {
+                // Checked before reading so output written just before exit is not missed.
+                let running = follow && agent.is_running(&id);
+                let Ok(mut file) = tokio::fs::File::open(&path).await else { return };
+                if file.seek(std::io::SeekFrom::Start(offset)).await.is_err() {
+                    return;
+                }
+                loop {
+                    match file.read(&mut buffer).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => {
+                            offset += read as u64;
+                            if sender.send(Bytes::copy_from_slice(&buffer[..read])).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+                if !running {
+                    return;
+                }
+                tokio::time::sleep(LOG_POLL_INTERVAL).await;
+            }
+        });
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(ChannelBody(receiver).boxed())
+            .expect("static response parts are valid"))
+    }
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<ResponseBody> {
+    let body = serde_json::to_vec(body).expect("API responses serialize");
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)).boxed())
+        .expect("static response parts are valid")
+}
+
+/// A response body fed chunk by chunk from a channel.
+struct ChannelBody(mpsc::Receiver<Bytes>);
+
+impl Body for ChannelBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<std::result::Result<Frame<Bytes>, Infallible>>> {
+        self.0.poll_recv(cx).map(|chunk| chunk.map(|bytes| Ok(Frame::data(bytes))))
+    }
+}
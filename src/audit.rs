@@ -0,0 +1,579 @@
+//! Append-only audit trail of commands that change something: service
+//! restarts, remote commands, task runs, cloud and Proxmox actions, secret
+//! and config edits.
+//!
+//! Entries are JSON Lines in `audit.file`. Each one holds the SHA-256 of
+//! the entry before it, so `sigil audit verify` finds entries that were
+//! edited, removed or inserted. Dropping entries off the end leaves a valid
+//! chain; compare the head hash it reports with one recorded elsewhere.
+
+use crate::cli::{AuditCommands, Cli};
+use crate::config::{AuditConfig, Config};
+use crate::error::{Result, SigilError};
+use crate::output::{self, Render, Text};
+use crate::secrets::redact::{self, MASK};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::warn;
+
+/// Commands recorded, by subcommand path.
+const AUDITED: &[&str] = &[
+    "system.exec",
+    "task.run",
+    "task.create",
+    "secret.set",
+    "secret.delete",
+    "secret.keygen",
+    "secret.rotate",
+    "proxmox.start",
+    "proxmox.stop",
+    "proxmox.shutdown",
+    "proxmox.reboot",
+    "proxmox.snapshot",
+    "proxmox.snapshot.rollback",
+    "proxmox.snapshot.delete",
+    "aws.s3.upload",
+    "aws.s3.delete",
+    "aws.s3.sync",
+    "aws.ec2.start",
+    "aws.ec2.stop",
+    "aws.ec2.reboot",
+    "aws.ec2.snapshot",
+    "azure.vm.start",
+    "azure.vm.stop",
+    "azure.vm.deallocate",
+    "container.start",
+    "container.stop",
+    "container.restart",
+    "config.init",
+    "config.set",
+    "config.unset",
+    "config.migrate",
+    "config.profile.use",
+];
+
+/// Arguments whose values are never recorded, as (action, argument).
+const SENSITIVE: &[(&str, &str)] = &[("secret.set", "value"), ("config.set", "value")];
+
+/// Global flags that only change how output looks.
+const IGNORED_ARGS: &[&str] = &["verbose", "output", "no_emoji"];
+
+/// `prev_hash` of the first entry.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Task instance the current command started, if any.
+static INSTANCE: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Success => write!(f, "success"),
+            Outcome::Failure => write!(f, "failure"),
+        }
+    }
+}
+
+/// One line of the audit log. `hash` stays the last field: it covers the
+/// line as written with `hash` empty.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub time: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub user: String,
+    /// The user who ran sudo, when run through it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sudo_user: Option<String>,
+    pub host: String,
+    /// Subcommand path, such as `proxmox.snapshot.delete`.
+    pub action: String,
+    /// Command line, with secrets masked.
+    pub command: Vec<String>,
+    /// Arguments given on the command line or through the environment.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<String>,
+    pub result: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+/// An action in progress, recorded with its outcome by [`record`].
+pub struct Event {
+    action: String,
+    command: Vec<String>,
+    params: BTreeMap<String, Value>,
+    time: DateTime<Utc>,
+    started: Instant,
+}
+
+impl Event {
+    /// An action taken by the running command.
+    pub fn new(action: &str) -> Self {
+        Event {
+            action: action.to_string(),
+            command: std::env::args().collect(),
+            params: BTreeMap::new(),
+            time: Utc::now(),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn param(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.params.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// The command being run, if it is one that is audited.
+    pub fn from_matches(matches: &ArgMatches) -> Option<Self> {
+        let mut path = Vec::new();
+        let mut command = Cli::command();
+        let mut leaf = matches;
+        while let Some((name, sub)) = leaf.subcommand() {
+            path.push(name);
+            command = command.find_subcommand(name)?.clone();
+            leaf = sub;
+        }
+        let action = path.join(".");
+        if !AUDITED.contains(&action.as_str()) {
+            return None;
+        }
+
+        let mut event = Event::new(&action);
+        let mut hidden = Vec::new();
+        for id in leaf.ids() {
+            let id = id.as_str();
+            let given = matches!(leaf.value_source(id), Some(ValueSource::CommandLine | ValueSource::EnvVariable));
+            // Argument groups from flattened structs carry member names.
+            let is_arg = command.get_arguments().any(|arg| arg.get_id() == id);
+            if !given || !is_arg || IGNORED_ARGS.contains(&id) {
+                continue;
+            }
+            let Ok(Some(raw)) = leaf.try_get_raw(id) else {
+                continue;
+            };
+            let mut values: Vec<String> = raw.map(|value| value.to_string_lossy().into_owned()).collect();
+            if SENSITIVE.contains(&(action.as_str(), id)) {
+                hidden.append(&mut values);
+                values.push(MASK.to_string());
+            }
+            let value = match <[String; 1]>::try_from(values) {
+                Ok([value]) => Value::String(value),
+                Err(values) => Value::from(values),
+            };
+            event.params.insert(id.to_string(), value);
+        }
+        for arg in &mut event.command {
+            if hidden.contains(arg) {
+                *arg = MASK.to_string();
+            }
+        }
+        Some(event)
+    }
+}
+
+/// Attach a task instance id to the command's audit entry.
+pub fn note_instance(id: impl Display) {
+    *INSTANCE.lock().unwrap_or_else(|e| e.into_inner()) = Some(id.to_string());
+}
+
+/// Append `event` with its outcome. A log that cannot be written is
+/// reported but does not fail the action, which has already happened.
+pub fn record<E: Display>(settings: &AuditConfig, event: Event, result: &std::result::Result<(), E>) {
+    if !settings.enabled {
+        return;
+    }
+    let entry = AuditEntry {
+        seq: 0,
+        time: event.time,
+        duration_ms: event.started.elapsed().as_millis() as u64,
+        user: username(),
+        sudo_user: std::env::var("SUDO_USER").ok(),
+        host: hostname(),
+        action: event.action,
+        command: event.command.iter().map(|arg| redact::redact(arg)).collect(),
+        params: event.params.into_iter().map(|(name, value)| (name, redact_value(value))).collect(),
+        instance_id: INSTANCE.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        result: if result.is_ok() { Outcome::Success } else { Outcome::Failure },
+        error: result.as_ref().err().map(|e| redact::redact(&e.to_string())),
+        prev_hash: String::new(),
+        hash: String::new(),
+    };
+    if let Err(e) = append(&settings.file, entry) {
+        warn!("⚠️  Could not write audit log {}: {}", settings.file.display(), e);
+    }
+}
+
+fn redact_value(value: Value) -> Value {
+    match value {
+        Value::String(text) => Value::String(redact::redact(&text)),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact_value).collect()),
+        other => other,
+    }
+}
+
+/// Name of the real user running sigil; `USER` only where it cannot be
+/// looked up.
+fn username() -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(uid) = fs::metadata("/proc/self").map(|metadata| metadata.uid()) {
+            let passwd = fs::read_to_string("/etc/passwd").unwrap_or_default();
+            let name = passwd.lines().find_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                (fields.nth(1)? == uid.to_string()).then(|| name.to_string())
+            });
+            return name.unwrap_or_else(|| format!("uid {}", uid));
+        }
+    }
+    std::env::var("USER")
+        .or_else(|_| std::env::var("LOGNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Chain `entry` onto the last one in `path`. The file stays locked from
+/// reading the last entry until the new one is written, so concurrent
+/// sigil processes cannot fork the chain.
+fn append(path: &Path, mut entry: AuditEntry) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.read(true).append(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.lock()?;
+
+    (entry.seq, entry.prev_hash) = match last_line(&mut file)? {
+        Some(line) => {
+            let last: AuditEntry = serde_json::from_str(&line).map_err(|e| {
+                SigilError::module(
+                    "audit".to_string(),
+                    format!("Last entry is unreadable ({}); run 'sigil audit verify'", e),
+                )
+            })?;
+            (last.seq + 1, last.hash)
+        }
+        None => (1, GENESIS.to_string()),
+    };
+    entry.hash = String::new();
+    entry.hash = digest(&serde_json::to_string(&entry)?);
+    let mut line = serde_json::to_string(&entry)?;
+    line.push('\n');
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// The last non-empty line of `file`, read backwards from the end.
+fn last_line(file: &mut File) -> io::Result<Option<String>> {
+    let mut end = file.seek(SeekFrom::End(0))?;
+    let mut tail = Vec::new();
+    loop {
+        let start = end.saturating_sub(4096);
+        let mut chunk = vec![0; (end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        chunk.append(&mut tail);
+        tail = chunk;
+
+        let body = tail.strip_suffix(b"\n").unwrap_or(&tail);
+        if let Some(newline) = body.iter().rposition(|&b| b == b'\n') {
+            return Ok(Some(String::from_utf8_lossy(&body[newline + 1..]).into_owned()));
+        }
+        if start == 0 {
+            return Ok((!body.is_empty()).then(|| String::from_utf8_lossy(body).into_owned()));
+        }
+        end = start;
+    }
+}
+
+fn digest(text: &str) -> String {
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
+/// Check one line's own hash and return the entry.
+fn check_line(line: &str) -> std::result::Result<AuditEntry, String> {
+    let entry: AuditEntry = serde_json::from_str(line).map_err(|e| format!("not an audit entry: {}", e))?;
+    let marker = ",\"hash\":\"";
+    let unhashed = line
+        .rfind(marker)
+        .filter(|&at| line[at + marker.len()..] == format!("{}\"}}", entry.hash))
+        .map(|at| format!("{}{}\"}}", &line[..at], marker))
+        .ok_or("hash is not the last field")?;
+    if digest(&unhashed) != entry.hash {
+        return Err("contents do not match its hash".to_string());
+    }
+    Ok(entry)
+}
+
+fn open(path: &Path) -> Result<BufReader<File>> {
+    match File::open(path) {
+        Ok(file) => Ok(BufReader::new(file)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(SigilError::resource_not_found(format!("Audit log {}", path.display())))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Result of `sigil audit verify`.
+#[derive(Debug, Serialize)]
+struct Verification {
+    file: String,
+    entries: u64,
+    /// Hash of the last entry, to compare with a copy kept elsewhere.
+    head: String,
+}
+
+impl Render for Verification {
+    fn render(&self, text: &mut Text) {
+        text.item("✅", format!("{}: {} entries, chain intact", self.file, self.entries));
+        text.field("Head", &self.head);
+    }
+}
+
+fn verify(path: &Path) -> Result<Verification> {
+    let broken = |number: usize, problem: String| {
+        SigilError::module("audit".to_string(), format!("{}:{}: {}", path.display(), number, problem))
+    };
+    let mut entries = 0;
+    let mut head = GENESIS.to_string();
+    for (index, line) in open(path)?.lines().enumerate() {
+        let line = line?;
+        let number = index + 1;
+        if line.is_empty() {
+            continue;
+        }
+        let entry = check_line(&line).map_err(|problem| broken(number, problem))?;
+        if entry.seq != entries + 1 {
+            return Err(broken(number, format!("expected entry {}, found {}", entries + 1, entry.seq)));
+        }
+        if entry.prev_hash != head {
+            return Err(broken(number, "does not follow the entry before it".to_string()));
+        }
+        entries = entry.seq;
+        head = entry.hash;
+    }
+    Ok(Verification {
+        file: path.display().to_string(),
+        entries,
+        head,
+    })
+}
+
+impl Render for [AuditEntry] {
+    fn render(&self, text: &mut Text) {
+        let rows: Vec<Vec<String>> = self
+            .iter()
+            .map(|entry| {
+                let result = match (&entry.result, text.is_plain()) {
+                    (_, true) => entry.result.to_string(),
+                    (Outcome::Success, false) => output::decorate("✅", entry.result),
+                    (Outcome::Failure, false) => output::decorate("❌", entry.result),
+                };
+                vec![
+                    entry.seq.to_string(),
+                    entry.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string(),
+                    format!("{}@{}", entry.user, entry.host),
+                    entry.action.clone(),
+                    result,
+                    entry.command.iter().skip(1).cloned().collect::<Vec<_>>().join(" "),
+                ]
+            })
+            .collect();
+        text.table(&["SEQ", "TIME", "USER", "ACTION", "RESULT", "COMMAND"], &rows);
+    }
+}
+
+/// `RFC 3339`, or a local date meaning its midnight.
+fn parse_since(since: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(since) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(since, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0)?.and_local_timezone(Local).earliest())
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| SigilError::invalid_config("--since", "expected an RFC 3339 time or YYYY-MM-DD"))
+}
+
+pub async fn handle_command(cmd: &AuditCommands, config: &Config) -> Result<()> {
+    match cmd {
+        AuditCommands::Show { action, user, instance, since, failed, limit, file } => {
+            let path = file.as_ref().unwrap_or(&config.audit.file);
+            let since = since.as_deref().map(parse_since).transpose()?;
+            let mut entries = Vec::new();
+            for line in open(path)?.lines() {
+                let line = line?;
+                let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) else {
+                    continue;
+                };
+                let keep = action.as_ref().is_none_or(|action| {
+                    entry.action == *action || entry.action.starts_with(&format!("{}.", action))
+                }) && user.as_ref().is_none_or(|user| entry.user == *user)
+                    && instance.as_ref().is_none_or(|id| entry.instance_id.as_ref() == Some(id))
+                    && since.is_none_or(|since| entry.time >= since)
+                    && (!failed || entry.result == Outcome::Failure);
+                if keep {
+                    entries.push(entry);
+                }
+            }
+            if *limit > 0 && entries.len() > *limit {
+                entries.drain(..entries.len() - limit);
+            }
+            if entries.is_empty() && !output::is_structured() {
+                println!("No matching audit entries");
+            } else {
+                output::print(entries.as_slice())?;
+            }
+        }
+        AuditCommands::Verify { file } => {
+            let path = file.as_ref().unwrap_or(&config.audit.file);
+            output::print(&verify(path)?)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(action: &str) -> AuditEntry {
+        AuditEntry {
+            seq: 0,
+            time: Utc::now(),
+            duration_ms: 5,
+            user: "ops".to_string(),
+            sudo_user: None,
+            host: "admin1".to_string(),
+            action: action.to_string(),
+            command: vec!["sigil".to_string(), action.replace('.', " ")],
+            params: BTreeMap::new(),
+            instance_id: None,
+            result: Outcome::Success,
+            error: None,
+            prev_hash: String::new(),
+            hash: String::new(),
+        }
+    }
+
+    /// A log of three chained entries, and its lines.
+    fn log() -> (PathBuf, Vec<String>) {
+        let path = std::env::temp_dir().join(format!("sigil-audit-{}.jsonl", uuid::Uuid::new_v4()));
+        for action in ["task.run", "proxmox.stop", "config.set"] {
+            append(&path, entry(action)).unwrap();
+        }
+        let lines = fs::read_to_string(&path).unwrap().lines().map(str::to_string).collect();
+        (path, lines)
+    }
+
+    /// What `verify` says about `lines` written in place of the log.
+    fn verify_lines(path: &Path, lines: &[String]) -> std::result::Result<u64, String> {
+        fs::write(path, lines.iter().map(|line| format!("{}\n", line)).collect::<String>()).unwrap();
+        verify(path).map(|verification| verification.entries).map_err(|e| e.to_string())
+    }
+
+    fn assert_broken(result: std::result::Result<u64, String>, problem: &str) {
+        match result {
+            Err(message) => assert!(message.contains(problem), "expected '{}', got '{}'", problem, message),
+            Ok(entries) => panic!("chain of {} entries accepted", entries),
+        }
+    }
+
+    #[test]
+    fn accepts_an_intact_chain() {
+        let (path, lines) = log();
+        let verification = verify(&path).unwrap();
+        assert_eq!(verification.entries, 3);
+        let last: AuditEntry = serde_json::from_str(&lines[2]).unwrap();
+        assert_eq!(verification.head, last.hash);
+        assert_eq!(last.seq, 3);
+
+        // Dropping entries off the end leaves a valid, shorter chain.
+        assert_eq!(verify_lines(&path, &lines[..2]), Ok(2));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_an_edited_line() {
+        let (path, mut lines) = log();
+        lines[1] = lines[1].replace("\"proxmox.stop\"", "\"proxmox.start\"");
+        assert_broken(verify_lines(&path, &lines), ":2: contents do not match its hash");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_an_edited_line_with_its_hash_recomputed() {
+        let (path, mut lines) = log();
+        let mut edited: AuditEntry = serde_json::from_str(&lines[1]).unwrap();
+        edited.result = Outcome::Failure;
+        edited.hash = String::new();
+        edited.hash = digest(&serde_json::to_string(&edited).unwrap());
+        lines[1] = serde_json::to_string(&edited).unwrap();
+        assert_broken(verify_lines(&path, &lines), ":3: does not follow the entry before it");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_removed_middle_line() {
+        let (path, mut lines) = log();
+        lines.remove(1);
+        assert_broken(verify_lines(&path, &lines), ":2: expected entry 2, found 3");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_reordered_lines() {
+        let (path, mut lines) = log();
+        lines.swap(1, 2);
+        assert_broken(verify_lines(&path, &lines), ":2: expected entry 2, found 3");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn continues_the_chain_after_existing_entries() {
+        let (path, lines) = log();
+        append(&path, entry("secret.set")).unwrap();
+        let appended: AuditEntry = serde_json::from_str(fs::read_to_string(&path).unwrap().lines().nth(3).unwrap()).unwrap();
+        let previous: AuditEntry = serde_json::from_str(&lines[2]).unwrap();
+        assert_eq!((appended.seq, appended.prev_hash), (4, previous.hash));
+        assert_eq!(verify(&path).unwrap().entries, 4);
+        fs::remove_file(&path).unwrap();
+    }
+}
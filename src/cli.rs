@@ -1,4 +1,8 @@
-use clap::{Parser, Subcommand};
+use crate::completions;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::ArgValueCandidates;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(
@@ -6,19 +10,41 @@ use clap::{Parser, Subcommand};
     about = "Your DevOps familiar for automation, scripting, and cloud orchestration",
     long_about = "Sigil is a Rust-based CLI tool designed for Linux-focused scripting, \
                   homelab automation, and hybrid cloud orchestration across Proxmox, AWS, Azure, and beyond.",
+    after_long_help = crate::error::EXIT_CODES,
     version
 )]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
 
-    /// Enable verbose output
-    #[arg(short, long, global = true)]
-    pub verbose: bool,
+    /// Log more: -v one level below `logging.level`, -vv two
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
 
-    /// Configuration file path
-    #[arg(short, long, global = true)]
-    pub config: Option<String>,
+    /// Configuration file path, merged on top of the system, user and project files
+    #[arg(short, long, global = true, env = "SIGIL_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Named profile to apply on top of the configuration
+    #[arg(long, global = true, env = "SIGIL_PROFILE", add = ArgValueCandidates::new(completions::profile_names))]
+    pub profile: Option<String>,
+
+    /// Fail on unknown configuration keys instead of warning
+    #[arg(long, global = true, env = "SIGIL_STRICT_CONFIG")]
+    pub strict_config: bool,
+
+    /// Output format for command results
+    #[arg(short, long, global = true, value_enum, default_value_t, env = "SIGIL_OUTPUT")]
+    pub output: OutputFormat,
+
+    /// Leave emoji out of output (also when NO_COLOR is set)
+    #[arg(long, global = true, env = "SIGIL_NO_EMOJI")]
+    pub no_emoji: bool,
+
+    /// Send task and system info commands to the `sigil agent` at this
+    /// address (`host:port` or a URL)
+    #[arg(long, global = true, env = "SIGIL_AGENT", value_name = "ADDRESS")]
+    pub agent: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -35,6 +61,64 @@ pub enum Commands {
     #[command(subcommand)]
     Config(ConfigCommands),
 
+    /// Read and manage secrets in the configured backend
+    #[command(subcommand)]
+    Secret(SecretCommands),
+
+    /// Proxmox VE nodes, VMs and containers
+    #[command(subcommand)]
+    Proxmox(ProxmoxCommands),
+
+    /// Amazon Web Services
+    #[command(subcommand)]
+    Aws(AwsCommands),
+
+    /// Microsoft Azure resource groups and virtual machines
+    #[command(subcommand)]
+    Azure(AzureCommands),
+
+    /// Docker or Podman containers
+    #[command(subcommand)]
+    Container(ContainerCommands),
+
+    /// Hosts, groups and variables for remote execution
+    #[command(subcommand)]
+    Inventory(InventoryCommands),
+
+    /// Audit trail of commands that changed something
+    #[command(subcommand)]
+    Audit(AuditCommands),
+
+    /// Dashboard of system metrics, services and task runs
+    Tui {
+        /// systemd unit to watch, besides modules.system.watched_services (repeatable)
+        #[arg(long = "service", value_name = "UNIT")]
+        services: Vec<String>,
+    },
+
+    /// Serve the task and system API that `--agent` controllers use
+    Agent {
+        /// Address to listen on (default: agent.listen)
+        #[arg(long)]
+        listen: Option<String>,
+    },
+
+    /// Print a shell completion script, e.g. `source <(sigil completions bash)`
+    Completions {
+        /// Shell to complete in
+        shell: clap_complete::Shell,
+    },
+
+    /// Print the man page for sigil or one of its commands
+    Man {
+        /// Command path, e.g. `task run`
+        commands: Vec<String>,
+
+        /// Write pages for every command into this directory instead
+        #[arg(long, value_name = "DIR", conflicts_with = "commands")]
+        dir: Option<PathBuf>,
+    },
+
     /// Show version information
     Version,
 }
@@ -45,6 +129,10 @@ pub enum SystemCommands {
     Monitor {
         /// Service name to monitor
         service: Option<String>,
+
+        /// Monitor a Docker/Podman container with this name instead of a systemd unit
+        #[arg(long, requires = "service")]
+        container: bool,
         
         /// Restart service if CPU usage exceeds threshold
         #[arg(long)]
@@ -61,7 +149,20 @@ pub enum SystemCommands {
         command: String,
         
         /// Arguments for the command
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
+
+        /// Run on this host over SSH instead of locally (repeatable)
+        #[arg(long = "host", value_name = "HOST")]
+        hosts: Vec<String>,
+
+        /// Run on inventory hosts matching this expression, e.g. 'group:web,!tag:canary'
+        #[arg(long, value_name = "EXPR", conflicts_with = "hosts")]
+        targets: Option<String>,
+
+        /// Hosts to run on at once (default: modules.ssh.parallelism)
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        parallel: Option<u32>,
     },
 
     /// System information
@@ -76,16 +177,37 @@ pub enum TaskCommands {
     /// Run a specific task
     Run {
         /// Task name to run
+        #[arg(add = ArgValueCandidates::new(completions::task_names))]
         name: String,
         
         /// Task parameters in key=value format
         #[arg(short, long)]
         params: Vec<String>,
+
+        /// Run on this host over SSH, overriding the task's `hosts` (repeatable)
+        #[arg(long = "host", value_name = "HOST")]
+        hosts: Vec<String>,
+
+        /// Run on inventory hosts matching this expression, overriding the task's
+        #[arg(long, value_name = "EXPR", conflicts_with = "hosts")]
+        targets: Option<String>,
+
+        /// Hosts to run on at once, overriding the task and config
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        parallel: Option<u32>,
+
+        #[command(flatten)]
+        strategy: StrategyArgs,
+
+        /// ID for the new instance; set by `sigil agent` for the runs it starts
+        #[arg(long, hide = true)]
+        instance_id: Option<uuid::Uuid>,
     },
 
     /// Show task status
     Status {
         /// Task ID or name
+        #[arg(add = ArgValueCandidates::new(completions::instances_and_tasks))]
         task: String,
     },
 
@@ -100,26 +222,674 @@ pub enum TaskCommands {
     },
 }
 
+#[derive(Subcommand)]
+pub enum SecretCommands {
+    /// Print a secret (all fields, or one with --key / `#key`)
+    Get {
+        /// Secret path or `secret://path#key` reference
+        path: String,
+
+        /// Field to print
+        #[arg(short, long)]
+        key: Option<String>,
+    },
+
+    /// Store a secret field; the value is prompted for or read from stdin if omitted
+    Set {
+        /// Secret path or `secret://path#key` reference
+        path: String,
+
+        /// Value to store
+        value: Option<String>,
+
+        /// Field to set (default: "value")
+        #[arg(short, long)]
+        key: Option<String>,
+    },
+
+    /// List secret paths
+    List {
+        /// Only list paths below this prefix
+        prefix: Option<String>,
+    },
+
+    /// Delete a secret and all of its fields
+    Delete {
+        /// Secret path or `secret://path` reference
+        path: String,
+    },
+
+    /// Generate an age identity for the file backend and print its public key
+    Keygen {
+        /// Where to write the identity (default: secrets.file_identity)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Overwrite an existing identity file
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Re-encrypt the secrets file to the current recipients with a fresh key
+    Rotate {
+        /// Change the passphrase of a passphrase-protected file
+        #[arg(long)]
+        new_passphrase: bool,
+    },
+
+    /// Keep the secrets file unlocked in a background agent
+    Unlock {
+        /// Seconds to stay unlocked (default: secrets.unlock_ttl_seconds)
+        #[arg(long)]
+        ttl: Option<u64>,
+    },
+
+    /// Stop the unlock agent
+    Lock,
+
+    /// Run the unlock agent (started by `sigil secret unlock`)
+    #[command(hide = true)]
+    Agent {
+        #[arg(long)]
+        socket: PathBuf,
+
+        #[arg(long)]
+        ttl: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProxmoxCommands {
+    /// List cluster nodes
+    Nodes,
+
+    /// List VMs and containers
+    List {
+        /// Only guests on this node
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Only guests with this tag
+        #[arg(long)]
+        tag: Option<String>,
+    },
+
+    /// Start VMs or containers
+    Start(GuestAction),
+
+    /// Stop VMs or containers immediately
+    Stop(GuestAction),
+
+    /// Shut VMs or containers down cleanly
+    Shutdown(GuestAction),
+
+    /// Reboot VMs or containers
+    Reboot(GuestAction),
+
+    /// Snapshot VMs or containers; `list`, `rollback` and `delete` manage existing snapshots
+    Snapshot(SnapshotArgs),
+
+    /// Wait for a Proxmox task to finish
+    Task {
+        /// Task id (`UPID:node:...`)
+        upid: String,
+    },
+}
+
+/// Overrides for a task's rolling `strategy`.
+#[derive(Args)]
+pub struct StrategyArgs {
+    /// Hosts per batch: a count, a percentage such as 25%, or all
+    #[arg(long, value_name = "N|N%|all")]
+    pub serial: Option<crate::runtime::strategy::BatchSize>,
+
+    /// Share of hosts that may fail before remaining batches are skipped
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub max_fail_percentage: Option<u8>,
+
+    /// Seconds to wait between batches
+    #[arg(long, value_name = "SECONDS")]
+    pub pause: Option<u64>,
+}
+
+/// VMs or containers to act on, by id or by tag.
+#[derive(Args)]
+pub struct GuestSelector {
+    /// VM or container ids
+    #[arg(required_unless_present = "tag")]
+    pub vmids: Vec<u32>,
+
+    /// Every guest with this tag
+    #[arg(long, conflicts_with = "vmids")]
+    pub tag: Option<String>,
+}
+
+/// Guests plus whether to wait for the resulting Proxmox tasks.
+#[derive(Args)]
+pub struct GuestAction {
+    #[command(flatten)]
+    pub target: GuestSelector,
+
+    /// Return once the tasks are submitted instead of waiting for them
+    #[arg(long)]
+    pub no_wait: bool,
+}
+
+#[derive(Args)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct SnapshotArgs {
+    #[command(subcommand)]
+    pub command: Option<SnapshotCommands>,
+
+    #[command(flatten)]
+    pub action: GuestAction,
+
+    /// Snapshot name (default: sigil-<timestamp>)
+    #[arg(short, long)]
+    pub name: Option<String>,
+
+    /// Snapshot description
+    #[arg(short, long)]
+    pub description: Option<String>,
+
+    /// Include VM memory (QEMU only)
+    #[arg(long)]
+    pub vmstate: bool,
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotCommands {
+    /// List snapshots
+    List(GuestSelector),
+
+    /// Roll guests back to a snapshot
+    Rollback {
+        #[command(flatten)]
+        action: GuestAction,
+
+        /// Snapshot name
+        #[arg(short, long)]
+        name: String,
+    },
+
+    /// Delete a snapshot
+    Delete {
+        #[command(flatten)]
+        action: GuestAction,
+
+        /// Snapshot name
+        #[arg(short, long)]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AwsCommands {
+    /// S3 object storage (or an S3-compatible endpoint)
+    #[command(subcommand)]
+    S3(S3Commands),
+
+    /// EC2 instances and their EBS snapshots
+    #[command(subcommand)]
+    Ec2(Ec2Commands),
+}
+
+#[derive(Subcommand)]
+pub enum S3Commands {
+    /// Upload a file or directory, skipping files whose content is unchanged
+    Upload {
+        /// Local file or directory
+        source: PathBuf,
+
+        /// `s3://bucket/key`, or `s3://bucket/prefix/` for a directory
+        destination: String,
+
+        /// Upload even if the object already has the same content
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Download an object or everything below a prefix
+    Download {
+        /// `s3://bucket/key` or `s3://bucket/prefix/`
+        source: String,
+
+        /// Local file or directory
+        destination: PathBuf,
+
+        /// Download even if the local file already has the same content
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// List objects
+    List {
+        /// `s3://bucket/prefix`
+        uri: String,
+
+        /// List every object below the prefix instead of one level
+        #[arg(short, long)]
+        recursive: bool,
+    },
+
+    /// Delete an object, or everything below a prefix
+    Delete {
+        /// `s3://bucket/key`
+        uri: String,
+
+        /// Delete every object below the prefix
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Allow `-r` on a bare `s3://bucket`, deleting every object in it
+        #[arg(long, requires = "recursive")]
+        all: bool,
+    },
+
+    /// Make a destination match a source; one side is local, the other `s3://`
+    Sync {
+        /// Local directory or `s3://bucket/prefix/`
+        source: String,
+
+        /// Local directory or `s3://bucket/prefix/`
+        destination: String,
+
+        /// Remove files at the destination that are not in the source
+        #[arg(long)]
+        delete: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AuditCommands {
+    /// Show audit entries, oldest first
+    Show {
+        /// Only actions starting with this, e.g. `proxmox` or `task.run`
+        #[arg(long)]
+        action: Option<String>,
+
+        /// Only actions by this user
+        #[arg(long)]
+        user: Option<String>,
+
+        /// Only actions on this task instance
+        #[arg(long, add = ArgValueCandidates::new(completions::instance_ids))]
+        instance: Option<String>,
+
+        /// Only actions at or after this time (RFC 3339 or YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only actions that failed
+        #[arg(long)]
+        failed: bool,
+
+        /// Show the newest N matching entries (0 for all)
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+
+        /// Audit log to read (default: audit.file)
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+
+    /// Check that no entry was altered, removed or inserted
+    Verify {
+        /// Audit log to check (default: audit.file)
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum InventoryCommands {
+    /// List hosts with their addresses, groups and tags
+    List {
+        /// Only hosts matching this expression, e.g. 'group:web,!tag:canary'
+        #[arg(long, value_name = "EXPR")]
+        targets: Option<String>,
+    },
+
+    /// Show a host's address, groups and merged variables
+    Show {
+        /// Host name
+        host: String,
+    },
+
+    /// Check that hosts accept an SSH login
+    Ping {
+        /// Hosts to check, e.g. 'group:web,!tag:canary'
+        #[arg(long, value_name = "EXPR", default_value = "all")]
+        targets: String,
+
+        /// Hosts to check at once (default: modules.ssh.parallelism)
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        parallel: Option<u32>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable tables and text
+    #[default]
+    Table,
+    /// Text without headings, emoji or column headers; columns are tab-separated
+    Plain,
+    /// JSON for scripts; monitors print one object per line
+    Json,
+    /// YAML; monitors print one document per sample
+    Yaml,
+}
+
+#[derive(Args)]
+pub struct RegionSelector {
+    /// Region to query; repeat for several (default: the configured region)
+    #[arg(long = "region")]
+    pub regions: Vec<String>,
+
+    /// Query every region enabled for the account
+    #[arg(long, conflicts_with = "regions")]
+    pub all_regions: bool,
+}
+
+#[derive(Args)]
+pub struct InstanceSelector {
+    /// Instance ids
+    pub instance_ids: Vec<String>,
+
+    /// Only instances with this tag, `key=value` or just `key`; repeat to require several
+    #[arg(long = "tag")]
+    pub tags: Vec<String>,
+
+    #[command(flatten)]
+    pub regions: RegionSelector,
+}
+
+#[derive(Subcommand)]
+pub enum Ec2Commands {
+    /// List instances
+    List {
+        #[command(flatten)]
+        selector: InstanceSelector,
+
+        /// Only instances in this state (pending, running, stopped, ...)
+        #[arg(long)]
+        state: Option<String>,
+    },
+
+    /// Show instance details
+    Describe {
+        #[command(flatten)]
+        selector: InstanceSelector,
+    },
+
+    /// Start instances
+    Start {
+        #[command(flatten)]
+        selector: InstanceSelector,
+    },
+
+    /// Stop instances
+    Stop {
+        #[command(flatten)]
+        selector: InstanceSelector,
+
+        /// Force the instances to stop without a clean shutdown
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Reboot instances
+    Reboot {
+        #[command(flatten)]
+        selector: InstanceSelector,
+    },
+
+    /// Snapshot the EBS volumes of instances
+    Snapshot {
+        #[command(flatten)]
+        selector: InstanceSelector,
+
+        /// Keep only the newest N sigil snapshots of each volume (at least 1)
+        #[arg(long)]
+        retain: Option<NonZeroUsize>,
+
+        /// Snapshot description
+        #[arg(short, long)]
+        description: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AzureCommands {
+    /// Resource groups
+    #[command(subcommand)]
+    Rg(ResourceGroupCommands),
+
+    /// Virtual machines
+    #[command(subcommand)]
+    Vm(VmCommands),
+}
+
+#[derive(Subcommand)]
+pub enum ResourceGroupCommands {
+    /// List resource groups in the subscription
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum VmCommands {
+    /// List virtual machines with their power state
+    List {
+        /// Only VMs in this resource group
+        #[arg(short = 'g', long)]
+        resource_group: Option<String>,
+
+        /// Only VMs in this region
+        #[arg(short, long)]
+        location: Option<String>,
+    },
+
+    /// Start virtual machines
+    Start(VmAction),
+
+    /// Power off virtual machines; they keep their compute allocation
+    Stop(VmAction),
+
+    /// Stop virtual machines and release their compute resources
+    Deallocate(VmAction),
+
+    /// Check that VMs are provisioned, running and have a ready agent
+    Healthcheck {
+        /// Only VMs in this resource group
+        #[arg(short = 'g', long)]
+        resource_group: Option<String>,
+
+        /// Region to check (default: modules.azure.location)
+        #[arg(short, long)]
+        location: Option<String>,
+
+        /// Check VMs in every region
+        #[arg(long, conflicts_with = "location")]
+        all_regions: bool,
+    },
+}
+
+/// Virtual machines to act on, by name or by tag.
+#[derive(Args)]
+pub struct VmSelector {
+    /// VM names
+    #[arg(required_unless_present = "tag")]
+    pub names: Vec<String>,
+
+    /// Resource group of the VMs; needed when a name exists in several groups
+    #[arg(short = 'g', long)]
+    pub resource_group: Option<String>,
+
+    /// Every VM with this tag, `key=value` or just `key`
+    #[arg(long, conflicts_with = "names")]
+    pub tag: Option<String>,
+}
+
+#[derive(Args)]
+pub struct VmAction {
+    #[command(flatten)]
+    pub target: VmSelector,
+
+    /// Return once Azure accepted the request instead of waiting for it to finish
+    #[arg(long)]
+    pub no_wait: bool,
+}
+
+#[derive(Subcommand)]
+pub enum ContainerCommands {
+    /// List running containers
+    List {
+        /// Include stopped containers
+        #[arg(short, long)]
+        all: bool,
+    },
+
+    /// Show a container's configuration and state as JSON
+    Inspect {
+        /// Container name or id
+        container: String,
+    },
+
+    /// Start containers
+    Start {
+        /// Container names or ids
+        #[arg(required = true)]
+        containers: Vec<String>,
+    },
+
+    /// Stop containers
+    Stop {
+        /// Container names or ids
+        #[arg(required = true)]
+        containers: Vec<String>,
+
+        /// Seconds to wait for a clean stop before killing
+        #[arg(short, long)]
+        time: Option<u32>,
+    },
+
+    /// Restart containers
+    Restart {
+        /// Container names or ids
+        #[arg(required = true)]
+        containers: Vec<String>,
+
+        /// Seconds to wait for a clean stop before killing
+        #[arg(short, long)]
+        time: Option<u32>,
+    },
+
+    /// Show a container's logs
+    Logs {
+        /// Container name or id
+        container: String,
+
+        /// Keep streaming new output
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Only the last N lines
+        #[arg(short = 'n', long)]
+        tail: Option<u32>,
+
+        /// Prefix lines with their timestamp
+        #[arg(short, long)]
+        timestamps: bool,
+    },
+
+    /// Show CPU and memory usage
+    Stats {
+        /// Container names or ids (default: every running container)
+        containers: Vec<String>,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum ConfigCommands {
     /// Show current configuration
-    Show,
+    Show {
+        /// Annotate each value with the layer it came from
+        #[arg(long)]
+        origin: bool,
+    },
 
     /// Initialize configuration file
     Init,
 
     /// Set configuration value
     Set {
-        /// Configuration key
+        /// Dotted configuration key, e.g. `modules.proxmox.endpoint` or `list[0]`
+        #[arg(add = ArgValueCandidates::new(completions::config_keys))]
         key: String,
         
-        /// Configuration value
+        /// Configuration value, parsed according to the key's type
         value: String,
     },
 
+    /// Remove a value from the config file so lower layers apply again
+    Unset {
+        /// Dotted configuration key
+        #[arg(add = ArgValueCandidates::new(completions::config_keys))]
+        key: String,
+    },
+
     /// Get configuration value
     Get {
-        /// Configuration key
+        /// Dotted configuration key; sections print as TOML
+        #[arg(add = ArgValueCandidates::new(completions::config_keys))]
         key: String,
     },
+
+    /// Check config files for syntax, type and semantic errors
+    Validate,
+
+    /// Upgrade the config file to the current `config_version`
+    Migrate,
+
+    /// Manage named profiles (homelab, staging, prod, ...)
+    #[command(subcommand)]
+    Profile(ProfileCommands),
+
+    /// Print a JSON Schema for editor autocompletion
+    Schema {
+        /// Which document to describe
+        #[arg(value_enum, default_value = "config")]
+        target: SchemaTarget,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProfileCommands {
+    /// List defined profiles, marking the active one
+    List,
+
+    /// Make a profile the default for future commands
+    Use {
+        /// Profile name
+        #[arg(add = ArgValueCandidates::new(completions::profile_names))]
+        name: String,
+    },
+
+    /// Show a profile's overrides and the resulting sections
+    Show {
+        /// Profile name (defaults to the active profile)
+        #[arg(add = ArgValueCandidates::new(completions::profile_names))]
+        name: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum SchemaTarget {
+    /// `config.toml`
+    Config,
+    /// Task definition files
+    Task,
+    /// `inventory.toml`
+    Inventory,
 }
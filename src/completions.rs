@@ -0,0 +1,140 @@
+//! Shell completions. `sigil completions <shell>` prints a script that
+//! calls back into `COMPLETE=<shell> sigil -- <words>` on every TAB, so
+//! candidates come from the `Cli` definition of the installed binary plus
+//! the task, instance, config key and profile names below.
+//!
+//! The candidate functions see only the word being completed, not the rest
+//! of the command line, so they load the configuration the way a command
+//! without `--config`/`--profile` would (`SIGIL_CONFIG` and `SIGIL_PROFILE`
+//! still apply). They never fail: anything unreadable yields no candidates.
+
+use crate::config::{Config, LoadOptions};
+use crate::error::{Result, SigilError};
+use crate::runtime::task_runner::TaskInstance;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::Shells;
+use clap_complete::Shell;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Environment variable that switches `sigil` into completion mode.
+pub const ENV_VAR: &str = "COMPLETE";
+
+/// Print the registration script for `shell`.
+pub fn print_script(shell: Shell) -> Result<()> {
+    let name = shell.to_string();
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&name)
+        .ok_or_else(|| SigilError::module("completions", &format!("Unsupported shell '{}'", name)))?;
+
+    // Call back into this binary the way it was invoked: by name when found
+    // through PATH, otherwise by absolute path.
+    let mut program = std::env::args_os()
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("sigil"));
+    if program.components().count() > 1 {
+        program = std::path::absolute(&program)?;
+    }
+
+    let mut script = Vec::new();
+    completer.write_registration(ENV_VAR, "sigil", "sigil", &program.to_string_lossy(), &mut script)?;
+    std::io::stdout().write_all(&script)?;
+    Ok(())
+}
+
+fn load_config() -> Option<Config> {
+    let options = LoadOptions {
+        path: std::env::var_os("SIGIL_CONFIG").map(PathBuf::from),
+        profile: std::env::var("SIGIL_PROFILE").ok(),
+        strict: false,
+    };
+    Config::load_layered(&options).ok().map(|loaded| loaded.config)
+}
+
+/// Task definitions, by file name like `sigil task run` expects.
+pub fn task_names() -> Vec<CompletionCandidate> {
+    let Some(config) = load_config() else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(&config.tasks.definitions_dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("toml"))
+        .filter_map(|path| path.file_stem().and_then(|s| s.to_str()).map(CompletionCandidate::new))
+        .collect()
+}
+
+/// Saved task instances, newest first, described by task and status.
+pub fn instance_ids() -> Vec<CompletionCandidate> {
+    let Some(config) = load_config() else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(&config.tasks.state_dir) else {
+        return Vec::new();
+    };
+    let mut instances: Vec<TaskInstance> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("json"))
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect();
+    instances.sort_by_key(|instance| std::cmp::Reverse(instance.created_at));
+    instances
+        .into_iter()
+        .enumerate()
+        .map(|(order, instance)| {
+            let help = format!(
+                "{} {:?} {}",
+                instance.definition_name,
+                instance.status,
+                instance.created_at.format("%Y-%m-%d %H:%M")
+            );
+            CompletionCandidate::new(instance.id.to_string())
+                .help(Some(help.into()))
+                .display_order(Some(order))
+        })
+        .collect()
+}
+
+/// `sigil task status` takes an instance ID or a task name.
+pub fn instances_and_tasks() -> Vec<CompletionCandidate> {
+    let mut candidates = task_names();
+    candidates.extend(instance_ids());
+    candidates
+}
+
+/// Dotted keys of the resolved configuration.
+pub fn config_keys() -> Vec<CompletionCandidate> {
+    load_config()
+        .and_then(|config| config.keys().ok())
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Names under `[profiles]`, with their descriptions.
+pub fn profile_names() -> Vec<CompletionCandidate> {
+    // A bad SIGIL_PROFILE would fail the load, so leave it out here.
+    let options = LoadOptions {
+        path: std::env::var_os("SIGIL_CONFIG").map(PathBuf::from),
+        ..LoadOptions::default()
+    };
+    let Ok(loaded) = Config::load_layered(&options) else {
+        return Vec::new();
+    };
+    loaded
+        .config
+        .profiles
+        .iter()
+        .map(|(name, profile)| {
+            CompletionCandidate::new(name).help(profile.description.clone().map(Into::into))
+        })
+        .collect()
+}
@@ -0,0 +1,79 @@
+use crate::error::{Result, SigilError};
+use toml_edit::{DocumentMut, Item, Value};
+
+/// Schema version written by this build. Bump it together with a new entry
+/// in `MIGRATIONS` whenever a key is renamed, moved or changes meaning.
+pub const CURRENT_CONFIG_VERSION: u32 = 1;
+
+/// Files without `config_version` predate versioning. Their layout is the
+/// same as version 1, so they load without a warning.
+const UNVERSIONED: u32 = 0;
+
+type Migration = fn(&mut DocumentMut);
+
+/// `MIGRATIONS[n]` upgrades a document from version `n` to `n + 1`.
+const MIGRATIONS: &[Migration] = &[
+    // 0 -> 1: introduce `config_version`; the layout is otherwise unchanged.
+    |_doc| {},
+];
+
+/// What [`migrate`] did to a document.
+#[derive(Debug, Clone, Copy)]
+pub struct Migrated {
+    /// Version the document was at.
+    pub from: u32,
+    /// Whether a migration changed anything besides `config_version`.
+    pub changed: bool,
+}
+
+/// The schema version a document was written for.
+pub fn version_of(doc: &DocumentMut) -> Result<u32> {
+    match doc.get("config_version") {
+        None => Ok(UNVERSIONED),
+        Some(item) => item
+            .as_integer()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| SigilError::invalid_config("config_version", "Must be a non-negative integer")),
+    }
+}
+
+/// Upgrade a document in place to `CURRENT_CONFIG_VERSION`, keeping its
+/// formatting. Returns `None` if it already was at that version.
+pub fn migrate(doc: &mut DocumentMut) -> Result<Option<Migrated>> {
+    let from = version_of(doc)?;
+    if from > CURRENT_CONFIG_VERSION {
+        return Err(SigilError::invalid_config(
+            "config_version".to_string(),
+            format!(
+                "Version {} was written by a newer sigil (this build supports up to {})",
+                from, CURRENT_CONFIG_VERSION
+            ),
+        ));
+    }
+    if from == CURRENT_CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    let before = doc.to_string();
+    for migration in &MIGRATIONS[from as usize..CURRENT_CONFIG_VERSION as usize] {
+        migration(doc);
+    }
+    let changed = doc.to_string() != before;
+    set_version(doc);
+    Ok(Some(Migrated { from, changed }))
+}
+
+fn set_version(doc: &mut DocumentMut) {
+    let version = Value::from(i64::from(CURRENT_CONFIG_VERSION));
+    match doc.get_mut("config_version") {
+        Some(Item::Value(existing)) => {
+            let decor = existing.decor().clone();
+            *existing = version;
+            *existing.decor_mut() = decor;
+        }
+        _ => {
+            // Root keys are always written before the first `[section]`.
+            doc.insert("config_version", Item::Value(version));
+        }
+    }
+}
@@ -0,0 +1,1186 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use crate::cli::{ConfigCommands, ProfileCommands, SchemaTarget};
+use crate::error::SigilError;
+use secret::SecretValue;
+use toml_edit::DocumentMut;
+use tracing::warn;
+
+pub mod migrate;
+pub mod path;
+pub mod profile;
+pub mod secret;
+pub mod validate;
+
+// Every section falls back to its `Default` field-by-field, so a config
+// file only needs the keys it wants to change.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct Config {
+    /// Schema version of the file; older files are migrated on load.
+    pub config_version: u32,
+    /// Profile used when neither `--profile` nor `SIGIL_PROFILE` is given.
+    pub profile: Option<String>,
+    /// Files merged right after the one that lists them, relative to it.
+    /// SOPS-encrypted YAML/JSON files are decrypted in memory.
+    pub include: Vec<PathBuf>,
+    pub general: GeneralConfig,
+    pub logging: LoggingConfig,
+    pub modules: ModulesConfig,
+    pub secrets: SecretsConfig,
+    pub tasks: TasksConfig,
+    pub inventory: InventoryConfig,
+    pub agent: AgentConfig,
+    pub audit: AuditConfig,
+    pub profiles: profile::Profiles,
+    /// Profile whose overrides were applied to this config, if any.
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub active_profile: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct GeneralConfig {
+    pub data_dir: PathBuf,
+    pub config_dir: PathBuf,
+    pub log_dir: PathBuf,
+    pub default_shell: String,
+    pub timeout_seconds: u64,
+}
+
+/// Console logs go to stderr, file logs to `general.log_dir/sigil.log`.
+/// `RUST_LOG`, when set, replaces `level` and `modules`.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// trace, debug, info, warn or error; each `-v` goes one step further.
+    pub level: String,
+    /// Console format: json, pretty, compact or full.
+    pub format: String,
+    pub file_enabled: bool,
+    pub console_enabled: bool,
+    /// Log file format, from the same choices as `format`.
+    pub file_format: String,
+    /// Levels for single modules by tracing target, such as
+    /// `"sigil::runtime::ssh" = "debug"` or `reqwest = "warn"`.
+    pub modules: BTreeMap<String, String>,
+    /// Start a new log file when: hourly, daily or never.
+    pub rotation: String,
+    /// Also start a new log file past this size; 0 for no limit.
+    pub max_file_size_mb: u64,
+    /// Rotated log files kept besides the current one.
+    pub max_files: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, Default)]
+#[serde(default)]
+pub struct ModulesConfig {
+    pub system: SystemModuleConfig,
+    pub aws: Option<AwsConfig>,
+    pub azure: Option<AzureConfig>,
+    pub proxmox: Option<ProxmoxConfig>,
+    pub container: Option<ContainerConfig>,
+    pub ssh: SshConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct SystemModuleConfig {
+    pub enabled: bool,
+    pub monitor_interval_seconds: u64,
+    pub default_cpu_threshold: u8,
+    pub default_memory_threshold: u8,
+    /// systemd units shown in `sigil tui`.
+    pub watched_services: Vec<String>,
+}
+
+/// Remote execution with the system ssh client, which reads
+/// `~/.ssh/config` and authenticates through ssh-agent.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct SshConfig {
+    /// ssh client to run.
+    pub program: String,
+    /// Hosts contacted at the same time.
+    pub parallelism: usize,
+    pub connect_timeout_seconds: u64,
+    /// Remote user for hosts given without `user@`; overrides `~/.ssh/config`.
+    pub user: Option<String>,
+    /// Extra `-o` options, e.g. `StrictHostKeyChecking=accept-new`.
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, Default)]
+#[serde(default)]
+pub struct AwsConfig {
+    /// Default region. Falls back to `AWS_REGION`, then the profile's
+    /// region in `~/.aws/config`, then `us-east-1`.
+    pub region: Option<String>,
+    pub profile: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<SecretValue>,
+    /// S3-compatible endpoint such as MinIO or Garage; buckets are then
+    /// addressed path-style (`<endpoint>/<bucket>/<key>`).
+    pub endpoint: Option<String>,
+    /// EC2 API endpoint override, e.g. a local stand-in for testing.
+    pub ec2_endpoint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, Default)]
+#[serde(default)]
+pub struct AzureConfig {
+    pub subscription_id: Option<String>,
+    pub tenant_id: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<SecretValue>,
+    /// Region for region-scoped commands such as `vm healthcheck`.
+    pub location: Option<String>,
+    /// Microsoft Entra ID authority (default: `https://login.microsoftonline.com`).
+    pub authority: Option<String>,
+    /// Azure Resource Manager endpoint (default: `https://management.azure.com`).
+    pub management_endpoint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct ProxmoxConfig {
+    pub endpoint: String,
+    pub username: String,
+    pub password: Option<SecretValue>,
+    pub token_id: Option<String>,
+    pub token_secret: Option<SecretValue>,
+    pub verify_ssl: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, Default)]
+#[serde(default)]
+pub struct ContainerConfig {
+    /// Docker or Podman Engine API socket. Defaults to a `unix://`
+    /// `DOCKER_HOST`, then the usual Docker and Podman socket paths.
+    pub socket: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct SecretsConfig {
+    pub backend: String, // "env", "vault", "file"
+    pub vault_endpoint: Option<String>,
+    pub vault_token: Option<SecretValue>,
+    /// Mount point of the KV secrets engine.
+    pub vault_mount: String,
+    /// KV engine version: 1 or 2.
+    pub vault_kv_version: u8,
+    pub vault_namespace: Option<String>,
+    /// Encrypted secrets file for the "file" backend (default: `<data_dir>/secrets.age`).
+    pub file_path: Option<PathBuf>,
+    /// age X25519 public keys (`age1...`) the secrets file is encrypted to.
+    /// Empty means the file is protected by a passphrase instead.
+    pub file_recipients: Vec<String>,
+    /// age identity used to decrypt a recipient-encrypted file
+    /// (default: `<config_dir>/age-identity.txt`).
+    pub file_identity: Option<PathBuf>,
+    /// How long `sigil secret unlock` keeps the file backend unlocked.
+    pub unlock_ttl_seconds: u64,
+    /// Extra regular expressions masked in task output, state and logs. With
+    /// a capture group, only the group is masked.
+    pub redact_patterns: Vec<String>,
+    /// Also mask well-known credential formats (AWS keys, bearer tokens, ...).
+    pub redact_builtin_patterns: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct TasksConfig {
+    pub definitions_dir: PathBuf,
+    pub state_dir: PathBuf,
+    pub max_concurrent_tasks: usize,
+    pub default_retry_count: u32,
+    pub default_timeout_seconds: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct InventoryConfig {
+    /// Hosts, groups and variables used by `--targets` selections.
+    pub file: PathBuf,
+}
+
+/// `sigil agent`, and the controller side used with `--agent`.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct AgentConfig {
+    /// Address `sigil agent` listens on.
+    pub listen: String,
+    /// Shared key every request is signed with (HMAC-SHA256). Required
+    /// unless `client_ca` is set.
+    pub token: Option<SecretValue>,
+    /// Server certificate chain (PEM); the agent serves HTTPS when set.
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// CA (PEM) client certificates must be issued by; enables mutual TLS.
+    pub client_ca: Option<PathBuf>,
+    /// Controller: CA (PEM) for agent certificates, in addition to the
+    /// system roots.
+    pub ca_cert: Option<PathBuf>,
+    /// Controller: certificate and key (PEM) presented to agents.
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+}
+
+/// The audit trail of commands that change something.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct AuditConfig {
+    pub enabled: bool,
+    /// Append-only JSON Lines file; each entry carries the hash of the one
+    /// before it.
+    pub file: PathBuf,
+}
+
+fn home_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or_else(|| PathBuf::from("/tmp"))
+}
+
+fn default_config_dir() -> PathBuf {
+    home_dir().join(".config/sigil")
+}
+
+fn default_data_dir() -> PathBuf {
+    home_dir().join(".local/share/sigil")
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            config_version: migrate::CURRENT_CONFIG_VERSION,
+            profile: None,
+            include: Vec::new(),
+            general: GeneralConfig::default(),
+            logging: LoggingConfig::default(),
+            modules: ModulesConfig::default(),
+            secrets: SecretsConfig::default(),
+            tasks: TasksConfig::default(),
+            inventory: InventoryConfig::default(),
+            agent: AgentConfig::default(),
+            audit: AuditConfig::default(),
+            profiles: profile::Profiles::new(),
+            active_profile: None,
+        }
+    }
+}
+
+impl Default for GeneralConfig {
+    fn default() -> Self {
+        let data_dir = default_data_dir();
+        GeneralConfig {
+            log_dir: data_dir.join("logs"),
+            data_dir,
+            config_dir: default_config_dir(),
+            default_shell: "/bin/bash".to_string(),
+            timeout_seconds: 300,
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: "info".to_string(),
+            format: "full".to_string(),
+            file_enabled: true,
+            console_enabled: true,
+            file_format: "json".to_string(),
+            modules: BTreeMap::new(),
+            rotation: "daily".to_string(),
+            max_file_size_mb: 50,
+            max_files: 7,
+        }
+    }
+}
+
+impl Default for SystemModuleConfig {
+    fn default() -> Self {
+        SystemModuleConfig {
+            enabled: true,
+            monitor_interval_seconds: 30,
+            default_cpu_threshold: 80,
+            default_memory_threshold: 85,
+            watched_services: Vec::new(),
+        }
+    }
+}
+
+impl Default for SshConfig {
+    fn default() -> Self {
+        SshConfig {
+            program: "ssh".to_string(),
+            parallelism: 10,
+            connect_timeout_seconds: 10,
+            user: None,
+            options: Vec::new(),
+        }
+    }
+}
+
+impl Default for ProxmoxConfig {
+    fn default() -> Self {
+        ProxmoxConfig {
+            endpoint: "https://localhost:8006".to_string(),
+            username: "root@pam".to_string(),
+            password: None,
+            token_id: None,
+            token_secret: None,
+            verify_ssl: true,
+        }
+    }
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        SecretsConfig {
+            backend: "env".to_string(),
+            vault_endpoint: None,
+            vault_token: None,
+            vault_mount: "secret".to_string(),
+            vault_kv_version: 2,
+            vault_namespace: None,
+            file_path: None,
+            file_recipients: Vec::new(),
+            file_identity: None,
+            unlock_ttl_seconds: 900,
+            redact_patterns: Vec::new(),
+            redact_builtin_patterns: true,
+        }
+    }
+}
+
+impl Default for TasksConfig {
+    fn default() -> Self {
+        TasksConfig {
+            definitions_dir: default_config_dir().join("tasks"),
+            state_dir: default_data_dir().join("state"),
+            max_concurrent_tasks: 5,
+            default_retry_count: 3,
+            default_timeout_seconds: 600,
+        }
+    }
+}
+
+impl Default for InventoryConfig {
+    fn default() -> Self {
+        InventoryConfig {
+            file: default_config_dir().join("inventory.toml"),
+        }
+    }
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        AgentConfig {
+            listen: "127.0.0.1:7370".to_string(),
+            token: None,
+            tls_cert: None,
+            tls_key: None,
+            client_ca: None,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+        }
+    }
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig {
+            enabled: true,
+            file: default_data_dir().join("audit.jsonl"),
+        }
+    }
+}
+
+/// System-wide configuration shared by every user on the host.
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/sigil/config.toml";
+
+/// Project-local configuration, discovered by walking up from the working directory.
+pub const PROJECT_CONFIG_FILE: &str = "sigil.toml";
+
+/// Prefix for environment overrides, e.g. `SIGIL_LOGGING__LEVEL=debug`.
+pub const ENV_PREFIX: &str = "SIGIL";
+
+/// Origin reported for values taken from `SIGIL_*` environment variables.
+const ENV_ORIGIN: &str = "the environment";
+
+/// One source in the configuration stack, lowest precedence first.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigLayer {
+    Default,
+    System(PathBuf),
+    User(PathBuf),
+    Project(PathBuf),
+    Explicit(PathBuf),
+    Include(PathBuf),
+    Profile(String),
+    Environment,
+}
+
+impl ConfigLayer {
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ConfigLayer::System(path)
+            | ConfigLayer::User(path)
+            | ConfigLayer::Project(path)
+            | ConfigLayer::Explicit(path)
+            | ConfigLayer::Include(path) => Some(path),
+            ConfigLayer::Default | ConfigLayer::Profile(_) | ConfigLayer::Environment => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigLayer::Default => write!(f, "default"),
+            ConfigLayer::System(path) => write!(f, "system ({})", path.display()),
+            ConfigLayer::User(path) => write!(f, "user ({})", path.display()),
+            ConfigLayer::Project(path) => write!(f, "project ({})", path.display()),
+            ConfigLayer::Explicit(path) => write!(f, "--config ({})", path.display()),
+            ConfigLayer::Include(path) => write!(f, "include ({})", path.display()),
+            ConfigLayer::Profile(name) => write!(f, "profile '{}'", name),
+            ConfigLayer::Environment => write!(f, "env ({}_*)", ENV_PREFIX),
+        }
+    }
+}
+
+/// Environment variables with the `SIGIL_` prefix that are CLI settings
+/// rather than config overrides.
+const RESERVED_ENV_VARS: &[&str] = &[
+    "SIGIL_CONFIG",
+    "SIGIL_PROFILE",
+    "SIGIL_STRICT_CONFIG",
+    "SIGIL_OUTPUT",
+    "SIGIL_NO_EMOJI",
+    "SIGIL_AGENT",
+];
+
+/// Whether a `SIGIL_*` variable overrides a config value. Besides the
+/// reserved CLI settings, `SIGIL_SECRET_*` values for the env backend and
+/// `SIGIL_SECRETS_*` backend credentials are not config keys; overrides of
+/// the `[secrets]` section use `SIGIL_SECRETS__*`.
+fn is_config_env_var(key: &str) -> bool {
+    let backend_credential = key.starts_with(crate::secrets::env::ENV_SECRET_PREFIX)
+        || (key.starts_with("SIGIL_SECRETS_") && !key.starts_with("SIGIL_SECRETS__"));
+    key.starts_with(ENV_PREFIX) && !RESERVED_ENV_VARS.contains(&key) && !backend_credential
+}
+
+/// How to assemble the configuration for this invocation.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Extra file from `--config`, merged above the project file.
+    pub path: Option<PathBuf>,
+    /// Profile from `--profile`/`SIGIL_PROFILE`, overriding the `profile` key.
+    pub profile: Option<String>,
+    /// Treat unknown keys as errors instead of warnings.
+    pub strict: bool,
+}
+
+/// A merged configuration together with the layer each leaf value came from.
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    pub config: Config,
+    pub layers: Vec<ConfigLayer>,
+    pub origins: BTreeMap<String, ConfigLayer>,
+    /// Keys present in some layer that no config field consumed.
+    pub unknown_keys: Vec<String>,
+}
+
+/// A config file parsed and migrated in memory, fed to the `config` crate
+/// with its path recorded as the origin of every value.
+#[derive(Debug, Clone)]
+struct FileSource {
+    path: PathBuf,
+    table: toml::Table,
+}
+
+impl FileSource {
+    fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mut doc = content
+            .parse::<DocumentMut>()
+            .map_err(|e| SigilError::invalid_config(path.display().to_string(), e.to_string()))?;
+        if let Some(migrated) = migrate::migrate(&mut doc)?.filter(|migrated| migrated.changed) {
+            warn!(
+                "⚠️  {} uses config version {}; migrated in memory (run 'sigil config migrate' to update it)",
+                path.display(),
+                migrated.from
+            );
+        }
+        let table = toml::from_str(&doc.to_string())?;
+        if let Some(warning) = exposure_warning(path, &table) {
+            warn!("⚠️  {}: {}", path.display(), warning);
+        }
+        Ok(FileSource {
+            path: path.to_path_buf(),
+            table,
+        })
+    }
+
+    /// Read a file named in `include`: TOML, YAML or JSON, decrypted first
+    /// with `identity` if SOPS encrypted it, and migrated in memory like the file that lists
+    /// it. Its own `include` key is not followed.
+    fn include(path: &Path, identity: &Path) -> Result<Self> {
+        let document = crate::secrets::sops::read_file(path, Some(identity))?;
+        let toml::Value::Table(table) = toml::Value::try_from(document)? else {
+            return Err(SigilError::invalid_config(path.display().to_string(), "Must contain a table".to_string()).into());
+        };
+        let mut doc = toml::to_string(&table)?
+            .parse::<DocumentMut>()
+            .map_err(|e| SigilError::invalid_config(path.display().to_string(), e.to_string()))?;
+        if let Some(migrated) = migrate::migrate(&mut doc)?.filter(|migrated| migrated.changed) {
+            warn!(
+                "⚠️  {} uses config version {}; migrated in memory (update it to version {})",
+                path.display(),
+                migrated.from,
+                migrate::CURRENT_CONFIG_VERSION
+            );
+        }
+        let table = toml::from_str(&doc.to_string())?;
+        Ok(FileSource {
+            path: path.to_path_buf(),
+            table,
+        })
+    }
+
+    /// Paths from this file's `include` key, resolved against its directory.
+    fn includes(&self) -> Vec<PathBuf> {
+        let base = self.path.parent().unwrap_or(Path::new("."));
+        self.table
+            .get("include")
+            .and_then(toml::Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(toml::Value::as_str)
+            .map(|include| base.join(include))
+            .collect()
+    }
+}
+
+impl ::config::Source for FileSource {
+    fn clone_into_box(&self) -> Box<dyn ::config::Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> std::result::Result<::config::Map<String, ::config::Value>, ::config::ConfigError> {
+        let origin = self.path.display().to_string();
+        Ok(self
+            .table
+            .iter()
+            .map(|(key, value)| (key.clone(), to_config_value(value, &origin)))
+            .collect())
+    }
+}
+
+fn to_config_value(value: &toml::Value, origin: &String) -> ::config::Value {
+    let kind = match value {
+        toml::Value::String(v) => ::config::ValueKind::String(v.clone()),
+        toml::Value::Integer(v) => ::config::ValueKind::I64(*v),
+        toml::Value::Float(v) => ::config::ValueKind::Float(*v),
+        toml::Value::Boolean(v) => ::config::ValueKind::Boolean(*v),
+        toml::Value::Datetime(v) => ::config::ValueKind::String(v.to_string()),
+        toml::Value::Array(items) => {
+            ::config::ValueKind::Array(items.iter().map(|item| to_config_value(item, origin)).collect())
+        }
+        toml::Value::Table(table) => ::config::ValueKind::Table(
+            table
+                .iter()
+                .map(|(key, value)| (key.clone(), to_config_value(value, origin)))
+                .collect(),
+        ),
+    };
+    ::config::Value::new(Some(origin), kind)
+}
+
+impl Config {
+    /// Load the merged configuration: built-in defaults, then the system,
+    /// user and project files, then `--config`, then `SIGIL_*` env vars.
+    pub async fn load(options: &LoadOptions) -> Result<Self> {
+        let loaded = Self::load_layered(options)?;
+        for key in &loaded.unknown_keys {
+            let layer = loaded.origins.get(key).cloned().unwrap_or(ConfigLayer::Default);
+            warn!("⚠️  Unknown config key '{}' from {}", key, layer);
+        }
+
+        // Mask credentials held in the config itself wherever they show up.
+        crate::secrets::redact::configure(&loaded.config.secrets);
+        let mut config = loaded.config;
+        for value in config.credentials_mut().into_iter().flatten() {
+            if value.is_plaintext() {
+                let _ = value.resolve_local();
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn load_layered(options: &LoadOptions) -> Result<LoadedConfig> {
+        let mut builder = ::config::Config::builder()
+            .add_source(::config::Config::try_from(&Config::default())?);
+        let env: ::config::Map<String, String> = std::env::vars()
+            .filter(|(key, _)| is_config_env_var(key))
+            .collect();
+        let env = ::config::Environment::with_prefix(ENV_PREFIX)
+            .prefix_separator("_")
+            .separator("__")
+            .try_parsing(true)
+            .source(Some(env));
+        let mut layers = Vec::new();
+        for layer in Self::layers(options.path.as_deref())? {
+            if let Some(path) = layer.path() {
+                let source = FileSource::read(path)?;
+                let includes = source.includes();
+                builder = builder.add_source(source);
+                layers.push(layer);
+                if includes.is_empty() {
+                    continue;
+                }
+                // SOPS-encrypted includes are decrypted with the identity
+                // configured so far, as task inputs are.
+                let identity = builder
+                    .clone()
+                    .add_source(env.clone())
+                    .build()
+                    .ok()
+                    .and_then(|merged| merged.try_deserialize::<Config>().ok())
+                    .map(|config| crate::secrets::file::identity_path(&config))
+                    .unwrap_or_else(|| default_config_dir().join(crate::secrets::file::DEFAULT_IDENTITY_NAME));
+                for include in includes {
+                    builder = builder.add_source(FileSource::include(&include, &identity)?);
+                    layers.push(ConfigLayer::Include(include));
+                }
+            }
+        }
+        builder = builder.add_source(env);
+
+        let mut tree = builder.build()?.cache;
+        let active_profile = profile::select(options.profile.as_deref(), &tree);
+        if let Some(name) = &active_profile {
+            profile::apply(&mut tree, name)?;
+        }
+
+        let mut origins = BTreeMap::new();
+        collect_origins("", &tree, &layers, &mut origins);
+
+        let mut unknown_keys = Vec::new();
+        let mut config: Config = serde_ignored::deserialize(tree, |path| unknown_keys.push(path.to_string()))?;
+        config.active_profile = active_profile.clone();
+        if options.strict && !unknown_keys.is_empty() {
+            return Err(SigilError::invalid_config(
+                unknown_keys.join(", "),
+                "Unknown configuration key(s) (strict mode)".to_string(),
+            )
+            .into());
+        }
+
+        let mut all_layers = vec![ConfigLayer::Default];
+        all_layers.extend(layers);
+        all_layers.extend(active_profile.map(ConfigLayer::Profile));
+        all_layers.push(ConfigLayer::Environment);
+
+        Ok(LoadedConfig {
+            config,
+            layers: all_layers,
+            origins,
+            unknown_keys,
+        })
+    }
+
+    /// File layers that exist on disk, in merge order. An explicit `--config`
+    /// path is always included so a typo surfaces as an error.
+    pub fn layers(explicit: Option<&Path>) -> Result<Vec<ConfigLayer>> {
+        let mut layers = Vec::new();
+
+        let system = PathBuf::from(SYSTEM_CONFIG_PATH);
+        if system.is_file() {
+            layers.push(ConfigLayer::System(system));
+        }
+
+        let user = Self::get_config_path();
+        if user.is_file() {
+            layers.push(ConfigLayer::User(user));
+        }
+
+        if let Some(project) = Self::find_project_config(&std::env::current_dir()?) {
+            layers.push(ConfigLayer::Project(project));
+        }
+
+        if let Some(path) = explicit {
+            if !path.is_file() {
+                return Err(SigilError::resource_not_found(format!("Config file: {}", path.display())).into());
+            }
+            layers.push(ConfigLayer::Explicit(std::path::absolute(path)?));
+        }
+
+        Ok(layers)
+    }
+
+    /// Walk up from `start` looking for a project-local `sigil.toml`.
+    pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+        start
+            .ancestors()
+            .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// The file `config init`/`config set` write to: `--config` if given,
+    /// otherwise the user config.
+    pub fn target_path(explicit: Option<&Path>) -> PathBuf {
+        explicit
+            .map(Path::to_path_buf)
+            .unwrap_or_else(Self::get_config_path)
+    }
+
+    pub async fn save(&self, config_path: &Path) -> Result<()> {
+        let content = toml::to_string_pretty(self)?;
+        write_config(config_path, &content).await
+    }
+
+    pub fn get_config_path() -> PathBuf {
+        default_config_dir().join("config.toml")
+    }
+
+    /// Show which profile is in effect before a command's own output. Goes
+    /// to stderr so stdout stays parseable.
+    pub fn print_header(&self) {
+        if let Some(profile) = &self.active_profile {
+            eprintln!("🔖 Profile: {}", profile);
+        }
+    }
+
+    /// Look up a dotted path (`modules.proxmox.endpoint`, `a.list[0]`) in
+    /// the resolved configuration.
+    pub fn get_value(&self, key: &str) -> Result<Option<toml::Value>> {
+        let segments = path::parse_path(key)?;
+        let tree = toml::Value::try_from(self)?;
+        Ok(path::get(&tree, &segments).cloned())
+    }
+
+    /// Every dotted key of the resolved configuration, sections included,
+    /// for shell completion of `config get/set/unset`.
+    pub fn keys(&self) -> Result<Vec<String>> {
+        fn walk(prefix: &str, table: &toml::Table, out: &mut Vec<String>) {
+            for (key, child) in table {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                out.push(path.clone());
+                if let toml::Value::Table(child) = child {
+                    walk(&path, child, out);
+                }
+            }
+        }
+
+        let mut keys = Vec::new();
+        if let toml::Value::Table(table) = toml::Value::try_from(self)? {
+            walk("", &table, &mut keys);
+        }
+        Ok(keys)
+    }
+
+    /// Edit a config file in place, keeping its comments and layout, and
+    /// check that the result still forms a valid configuration.
+    pub async fn edit_file<F>(file: &Path, edit: F) -> Result<()>
+    where
+        F: FnOnce(&mut DocumentMut) -> crate::error::Result<()>,
+    {
+        let content = match tokio::fs::read_to_string(file).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut doc = content
+            .parse::<DocumentMut>()
+            .map_err(|e| SigilError::invalid_config(file.display().to_string(), e.to_string()))?;
+
+        if !content.trim().is_empty() {
+            migrate::migrate(&mut doc)?;
+        }
+        let before = Self::validate_document(&doc).unwrap_or_default();
+        edit(&mut doc)?;
+        let after = Self::validate_document(&doc)
+            .map_err(|e| SigilError::invalid_config(file.display().to_string(), format!("Change rejected: {}", e)))?;
+        if let Some(unknown) = after.iter().find(|key| !before.contains(key)) {
+            return Err(SigilError::invalid_config(unknown.clone(), "Unknown configuration key".to_string()).into());
+        }
+
+        write_config(file, &doc.to_string()).await
+    }
+
+    /// Check that a (possibly partial) config document deserializes into a
+    /// `Config`, returning the keys it contains that no field consumed.
+    fn validate_document(doc: &DocumentMut) -> Result<Vec<String>> {
+        let value: toml::Value = toml::from_str(&doc.to_string())?;
+        let mut unknown = Vec::new();
+        let _: Config = serde_ignored::deserialize(value, |path| unknown.push(path.to_string()))?;
+        Ok(unknown)
+    }
+
+    /// Every credential field, including those inside profiles.
+    fn credentials_mut(&mut self) -> Vec<&mut Option<SecretValue>> {
+        let mut fields = credentials(
+            self.modules.aws.as_mut(),
+            self.modules.azure.as_mut(),
+            self.modules.proxmox.as_mut(),
+            Some(&mut self.secrets),
+        );
+        fields.push(&mut self.agent.token);
+        for profile in self.profiles.values_mut() {
+            fields.extend(credentials(
+                profile.aws.as_mut(),
+                profile.azure.as_mut(),
+                profile.proxmox.as_mut(),
+                profile.secrets.as_mut(),
+            ));
+        }
+        fields
+    }
+
+    /// A copy with plaintext credentials replaced by a marker, for display.
+    pub fn redacted(&self) -> Config {
+        let mut config = self.clone();
+        for value in config.credentials_mut().into_iter().flatten() {
+            value.redact();
+        }
+        config
+    }
+
+    /// Whether any credential is written inline rather than referenced.
+    pub fn has_plaintext_credentials(&self) -> bool {
+        self.clone()
+            .credentials_mut()
+            .into_iter()
+            .flatten()
+            .any(|value| value.is_plaintext())
+    }
+}
+
+fn credentials<'a>(
+    aws: Option<&'a mut AwsConfig>,
+    azure: Option<&'a mut AzureConfig>,
+    proxmox: Option<&'a mut ProxmoxConfig>,
+    secrets: Option<&'a mut SecretsConfig>,
+) -> Vec<&'a mut Option<SecretValue>> {
+    let mut fields = Vec::new();
+    if let Some(aws) = aws {
+        fields.push(&mut aws.secret_access_key);
+    }
+    if let Some(azure) = azure {
+        fields.push(&mut azure.client_secret);
+    }
+    if let Some(proxmox) = proxmox {
+        fields.push(&mut proxmox.password);
+        fields.push(&mut proxmox.token_secret);
+    }
+    if let Some(secrets) = secrets {
+        fields.push(&mut secrets.vault_token);
+    }
+    fields
+}
+
+/// Config files can hold credentials, so they are written owner-only.
+async fn write_config(path: &Path, content: &str) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    file.write_all(content.as_bytes()).await?;
+    file.flush().await?;
+    // `mode` only applies to new files; tighten existing ones too.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).await?;
+    }
+    Ok(())
+}
+
+/// A warning if `path` is readable by other users and `table` holds
+/// plaintext credentials. Never warns where there are no Unix permissions.
+#[cfg(unix)]
+pub fn exposure_warning(path: &Path, table: &toml::Table) -> Option<String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = std::fs::metadata(path).ok()?.permissions().mode();
+    if mode & 0o004 == 0 {
+        return None;
+    }
+    let config: Config = toml::Value::Table(table.clone()).try_into().ok()?;
+    config.has_plaintext_credentials().then(|| {
+        format!(
+            "World-readable file contains plaintext credentials; run 'chmod 600 {}' or use secret:// references",
+            path.display()
+        )
+    })
+}
+
+#[cfg(not(unix))]
+pub fn exposure_warning(_path: &Path, _table: &toml::Table) -> Option<String> {
+    None
+}
+
+/// Flatten the merged `config` value tree into dotted keys, mapping each
+/// leaf's origin string back to the layer that produced it.
+fn collect_origins(
+    prefix: &str,
+    value: &::config::Value,
+    layers: &[ConfigLayer],
+    out: &mut BTreeMap<String, ConfigLayer>,
+) {
+    match &value.kind {
+        ::config::ValueKind::Table(table) => {
+            for (key, child) in table {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                collect_origins(&path, child, layers, out);
+            }
+        }
+        _ => {
+            let layer = match value.origin() {
+                None => ConfigLayer::Default,
+                Some(ENV_ORIGIN) => ConfigLayer::Environment,
+                Some(origin) if origin.starts_with(profile::PROFILE_ORIGIN_PREFIX) => {
+                    ConfigLayer::Profile(origin[profile::PROFILE_ORIGIN_PREFIX.len()..].to_string())
+                }
+                Some(origin) => layers
+                    .iter()
+                    .find(|layer| layer.path().is_some_and(|path| path == Path::new(origin)))
+                    .cloned()
+                    .unwrap_or(ConfigLayer::Default),
+            };
+            out.insert(prefix.to_string(), layer);
+        }
+    }
+}
+
+pub async fn handle_command(cmd: &ConfigCommands, options: &LoadOptions) -> Result<()> {
+    let explicit = options.path.as_deref();
+    match cmd {
+        ConfigCommands::Show { origin } => {
+            let loaded = Config::load_layered(options)?;
+            loaded.config.print_header();
+            if *origin {
+                println!("# Layers (lowest precedence first):");
+                for layer in &loaded.layers {
+                    println!("#   {}", layer);
+                }
+                let flat = flatten_toml(&toml::Value::try_from(loaded.config.redacted())?);
+                for (key, value) in flat {
+                    let layer = loaded.origins.get(&key).cloned().unwrap_or(ConfigLayer::Default);
+                    println!("{} = {}  # {}", key, value, layer);
+                }
+            } else if !crate::output::structured(&loaded.config.redacted())? {
+                let content = toml::to_string_pretty(&loaded.config.redacted())?;
+                println!("{}", content);
+            }
+        }
+        ConfigCommands::Init => {
+            let path = Config::target_path(explicit);
+            let config = Config::default();
+            config.save(&path).await?;
+            println!("✅ Configuration initialized at: {:?}", path);
+        }
+        ConfigCommands::Set { key, value } => {
+            // Only the target file is rewritten so values from other layers
+            // are not baked into it.
+            let file = Config::target_path(explicit);
+            let segments = path::parse_path(key)?;
+            // An unset `Option<String>` must not turn `0123` into a number.
+            let schema = serde_json::to_value(schemars::schema_for!(Config))?;
+            let expected = match path::schema_type(&schema, &segments) {
+                Some(expected) => Some(expected),
+                None => Config::load(options).await?.get_value(key)?,
+            };
+            let parsed = path::parse_typed(value, expected.as_ref(), key)?;
+            Config::edit_file(&file, |doc| path::set(doc, &segments, parsed.clone())).await?;
+            println!("✅ Set {} = {} in {}", key, parsed, file.display());
+        }
+        ConfigCommands::Unset { key } => {
+            let file = Config::target_path(explicit);
+            let segments = path::parse_path(key)?;
+            let mut removed = false;
+            Config::edit_file(&file, |doc| {
+                removed = path::unset(doc, &segments)?;
+                Ok(())
+            })
+            .await?;
+            if removed {
+                println!("✅ Unset {} in {}", key, file.display());
+            } else {
+                println!("ℹ️  {} is not set in {}", key, file.display());
+            }
+        }
+        ConfigCommands::Profile(cmd) => {
+            handle_profile_command(cmd, options).await?;
+        }
+        ConfigCommands::Migrate => {
+            let file = Config::target_path(explicit);
+            if !file.is_file() {
+                return Err(SigilError::resource_not_found(format!("Config file: {}", file.display())).into());
+            }
+            let content = tokio::fs::read_to_string(&file).await?;
+            let mut doc = content
+                .parse::<DocumentMut>()
+                .map_err(|e| SigilError::invalid_config(file.display().to_string(), e.to_string()))?;
+            match migrate::migrate(&mut doc)? {
+                Some(migrated) => {
+                    write_config(&file, &doc.to_string()).await?;
+                    println!(
+                        "✅ Migrated {} from version {} to {}",
+                        file.display(),
+                        migrated.from,
+                        migrate::CURRENT_CONFIG_VERSION
+                    );
+                }
+                None => println!("ℹ️  {} is already at version {}", file.display(), migrate::CURRENT_CONFIG_VERSION),
+            }
+        }
+        ConfigCommands::Validate => {
+            let diagnostics = validate::validate(options)?;
+            let errors = diagnostics
+                .iter()
+                .filter(|d| d.severity == validate::Severity::Error)
+                .count();
+            for diagnostic in &diagnostics {
+                eprintln!("{}", diagnostic);
+            }
+            if errors > 0 {
+                return Err(SigilError::invalid_config(
+                    "config".to_string(),
+                    format!("{} error(s), {} warning(s)", errors, diagnostics.len() - errors),
+                )
+                .into());
+            }
+            println!("✅ Configuration is valid ({} warning(s))", diagnostics.len());
+        }
+        ConfigCommands::Schema { target } => {
+            let schema = match target {
+                SchemaTarget::Config => schemars::schema_for!(Config),
+                SchemaTarget::Task => schemars::schema_for!(crate::runtime::task_runner::TaskDefinition),
+                SchemaTarget::Inventory => schemars::schema_for!(crate::inventory::InventoryFile),
+            };
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+        }
+        ConfigCommands::Get { key } => {
+            let config = Config::load(options).await?;
+            config.print_header();
+            match config.redacted().get_value(key)? {
+                Some(toml::Value::String(value)) => println!("{}", value),
+                Some(toml::Value::Table(table)) => print!("{}", toml::to_string_pretty(&table)?),
+                Some(value) => println!("{}", value),
+                None => {
+                    return Err(SigilError::resource_not_found(format!("Configuration key '{}'", key)).into());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn handle_profile_command(cmd: &ProfileCommands, options: &LoadOptions) -> Result<()> {
+    match cmd {
+        ProfileCommands::List => {
+            let config = Config::load(options).await?;
+            if config.profiles.is_empty() {
+                println!("No profiles defined. Add a [profiles.<name>] section to your config.");
+            }
+            for (name, profile) in &config.profiles {
+                let marker = if config.active_profile.as_deref() == Some(name) { "*" } else { " " };
+                print!("{} {} ({})", marker, name, profile::summary(profile));
+                if let Some(description) = &profile.description {
+                    print!(" - {}", description);
+                }
+                println!();
+            }
+        }
+        ProfileCommands::Use { name } => {
+            let config = Config::load(options).await?;
+            if !config.profiles.contains_key(name) {
+                return Err(SigilError::resource_not_found(format!("Profile '{}'", name)).into());
+            }
+            let file = Config::target_path(options.path.as_deref());
+            let value = toml_edit::Value::from(name.as_str());
+            Config::edit_file(&file, |doc| path::set(doc, &[path::Segment::Key("profile".to_string())], value)).await?;
+            println!("✅ Default profile is now '{}' ({})", name, file.display());
+        }
+        ProfileCommands::Show { name } => {
+            let options = LoadOptions {
+                profile: name.clone().or_else(|| options.profile.clone()),
+                ..options.clone()
+            };
+            // Credentials in the profile and the effective sections alike.
+            let config = Config::load(&options).await?.redacted();
+            let Some(active) = &config.active_profile else {
+                println!("No profile is active.");
+                return Ok(());
+            };
+            config.print_header();
+            if let Some(profile) = config.profiles.get(active) {
+                println!("# Overrides");
+                println!("{}", toml::to_string_pretty(profile)?);
+            }
+            println!("# Effective sections");
+            let mut effective = toml::Table::new();
+            effective.insert("modules".to_string(), toml::Value::try_from(&config.modules)?);
+            effective.insert("secrets".to_string(), toml::Value::try_from(&config.secrets)?);
+            effective.insert("tasks".to_string(), toml::Value::try_from(&config.tasks)?);
+            effective.insert("inventory".to_string(), toml::Value::try_from(&config.inventory)?);
+            println!("{}", toml::to_string_pretty(&effective)?);
+        }
+    }
+    Ok(())
+}
+
+fn flatten_toml(value: &toml::Value) -> Vec<(String, String)> {
+    fn walk(prefix: &str, value: &toml::Value, out: &mut Vec<(String, String)>) {
+        match value {
+            toml::Value::Table(table) => {
+                for (key, child) in table {
+                    let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                    walk(&path, child, out);
+                }
+            }
+            other => out.push((prefix.to_string(), other.to_string())),
+        }
+    }
+
+    let mut out = Vec::new();
+    walk("", value, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_env_vars_are_not_config_keys() {
+        for (key, value) in [("SIGIL_OUTPUT", "json"), ("SIGIL_NO_EMOJI", "true"), ("SIGIL_AGENT", "127.0.0.1:7370")] {
+            std::env::set_var(key, value);
+            assert!(!is_config_env_var(key), "{} is read as a config override", key);
+        }
+        let options = LoadOptions {
+            strict: true,
+            ..LoadOptions::default()
+        };
+        let loaded = Config::load_layered(&options).expect("config loads with CLI env vars set");
+        for key in ["output", "no_emoji", "agent"] {
+            assert!(!loaded.unknown_keys.iter().any(|unknown| unknown == key));
+        }
+        assert!(is_config_env_var("SIGIL_LOGGING__LEVEL"));
+    }
+}
@@ -0,0 +1,351 @@
+use crate::error::{Result, SigilError};
+use std::fmt;
+use toml_edit::{Array, ArrayOfTables, DocumentMut, Item, Table, TableLike, Value};
+
+/// One step in a dotted config path such as `modules.proxmox.endpoint` or
+/// `tasks.env[0]`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Segment::Key(key) => write!(f, "{}", key),
+            Segment::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+enum Op {
+    Set(Value),
+    Unset,
+}
+
+/// Parse `a.b[0].c` (or the equivalent `a.b.0.c`) into segments.
+pub fn parse_path(path: &str) -> Result<Vec<Segment>> {
+    let invalid = |reason: &str| SigilError::invalid_config(path, reason);
+
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let (key, mut rest) = match part.find('[') {
+            Some(pos) => part.split_at(pos),
+            None => (part, ""),
+        };
+
+        if !key.is_empty() {
+            match key.parse::<usize>() {
+                Ok(index) => segments.push(Segment::Index(index)),
+                Err(_) => segments.push(Segment::Key(key.to_string())),
+            }
+        } else if rest.is_empty() {
+            return Err(invalid("Empty path segment"));
+        }
+
+        while !rest.is_empty() {
+            let close = rest.find(']').ok_or_else(|| invalid("Unclosed '['"))?;
+            let index = rest[1..close]
+                .parse::<usize>()
+                .map_err(|_| invalid("Array index must be a non-negative integer"))?;
+            segments.push(Segment::Index(index));
+            rest = &rest[close + 1..];
+            if !rest.is_empty() && !rest.starts_with('[') {
+                return Err(invalid("Unexpected characters after ']'"));
+            }
+        }
+    }
+
+    if segments.is_empty() {
+        return Err(invalid("Empty path"));
+    }
+    Ok(segments)
+}
+
+/// Look up a path in a resolved config tree.
+pub fn get<'a>(value: &'a toml::Value, path: &[Segment]) -> Option<&'a toml::Value> {
+    path.iter().try_fold(value, |current, segment| match (segment, current) {
+        (Segment::Key(key), toml::Value::Table(table)) => table.get(key),
+        (Segment::Index(index), toml::Value::Array(array)) => array.get(*index),
+        _ => None,
+    })
+}
+
+/// A value of the type the JSON schema gives `path`, as the `expected` of
+/// [`parse_typed`]; its content is meaningless. `None` when the schema has
+/// no single type there.
+pub fn schema_type(schema: &serde_json::Value, path: &[Segment]) -> Option<toml::Value> {
+    let mut node = resolve(schema, schema)?;
+    for segment in path {
+        let child = match segment {
+            Segment::Key(key) => node["properties"]
+                .get(key)
+                .or_else(|| node.get("additionalProperties").filter(|child| child.is_object())),
+            Segment::Index(_) => node.get("items"),
+        };
+        node = resolve(schema, child?)?;
+    }
+    placeholder(schema, node)
+}
+
+/// Follow `$ref`s and look through the `null` alternative of `Option`s.
+fn resolve<'a>(root: &'a serde_json::Value, mut node: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+    loop {
+        if let Some(reference) = node["$ref"].as_str() {
+            node = root["$defs"].get(reference.strip_prefix("#/$defs/")?)?;
+        } else if let Some(variants) = node["anyOf"].as_array().or(node["oneOf"].as_array()) {
+            let mut types = variants.iter().filter(|variant| variant["type"] != "null");
+            node = types.next()?;
+            if types.next().is_some() {
+                return None;
+            }
+        } else {
+            return Some(node);
+        }
+    }
+}
+
+fn placeholder(root: &serde_json::Value, node: &serde_json::Value) -> Option<toml::Value> {
+    let kind = match &node["type"] {
+        serde_json::Value::String(kind) => kind.as_str(),
+        serde_json::Value::Array(kinds) => {
+            let mut kinds = kinds.iter().filter_map(|kind| kind.as_str()).filter(|kind| *kind != "null");
+            let kind = kinds.next()?;
+            if kinds.next().is_some() {
+                return None;
+            }
+            kind
+        }
+        _ => return None,
+    };
+    Some(match kind {
+        "string" => toml::Value::String(String::new()),
+        "integer" => toml::Value::Integer(0),
+        "number" => toml::Value::Float(0.0),
+        "boolean" => toml::Value::Boolean(false),
+        "array" => {
+            let item = node.get("items").and_then(|items| placeholder(root, resolve(root, items)?));
+            toml::Value::Array(item.into_iter().collect())
+        }
+        "object" => toml::Value::Table(toml::Table::new()),
+        _ => return None,
+    })
+}
+
+/// Parse a command-line value into a TOML value of the `expected` type:
+/// from [`schema_type`], else the value currently at the path. Without
+/// either, TOML literal syntax decides.
+pub fn parse_typed(raw: &str, expected: Option<&toml::Value>, key: &str) -> Result<Value> {
+    let mismatch = |kind: &str| SigilError::invalid_config(key.to_string(), format!("Expected {}, got '{}'", kind, raw));
+
+    match expected {
+        Some(toml::Value::String(_)) => Ok(Value::from(raw)),
+        Some(toml::Value::Integer(_)) => raw.parse::<i64>().map(Value::from).map_err(|_| mismatch("an integer")),
+        Some(toml::Value::Float(_)) => raw.parse::<f64>().map(Value::from).map_err(|_| mismatch("a number")),
+        Some(toml::Value::Boolean(_)) => parse_bool(raw).map(Value::from).ok_or_else(|| mismatch("a boolean")),
+        Some(toml::Value::Datetime(_)) => raw
+            .parse::<toml_edit::Datetime>()
+            .map(Value::from)
+            .map_err(|_| mismatch("a datetime")),
+        Some(toml::Value::Array(items)) => {
+            if raw.trim_start().starts_with('[') {
+                return parse_literal(raw).filter(Value::is_array).ok_or_else(|| mismatch("an array"));
+            }
+            // Comma-separated shorthand: `a,b,c`
+            let mut array = Array::new();
+            for item in raw.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+                array.push(parse_typed(item, items.first(), key)?);
+            }
+            Ok(Value::Array(array))
+        }
+        Some(toml::Value::Table(_)) => parse_literal(raw)
+            .filter(Value::is_inline_table)
+            .ok_or_else(|| mismatch("an inline table like { key = \"value\" }")),
+        None => Ok(parse_literal(raw).unwrap_or_else(|| Value::from(raw))),
+    }
+}
+
+fn parse_bool(raw: &str) -> Option<bool> {
+    match raw.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_literal(raw: &str) -> Option<Value> {
+    raw.parse::<Value>().ok().map(|mut value| {
+        value.decor_mut().clear();
+        value
+    })
+}
+
+/// Set a value in a TOML document, creating intermediate tables as needed.
+pub fn set(doc: &mut DocumentMut, path: &[Segment], value: Value) -> Result<()> {
+    apply_table(doc.as_table_mut(), path, &Op::Set(value)).map(|_| ())
+}
+
+/// Remove a value from a TOML document. Returns whether anything was removed.
+pub fn unset(doc: &mut DocumentMut, path: &[Segment]) -> Result<bool> {
+    apply_table(doc.as_table_mut(), path, &Op::Unset)
+}
+
+fn not_container(segment: &Segment, kind: &str) -> SigilError {
+    SigilError::invalid_config(segment.to_string(), format!("Cannot descend into {}", kind))
+}
+
+fn apply_table(table: &mut dyn TableLike, path: &[Segment], op: &Op) -> Result<bool> {
+    let (segment, rest) = path.split_first().expect("path is never empty");
+    let key = match segment {
+        Segment::Key(key) => key.as_str(),
+        Segment::Index(_) => return Err(not_container(segment, "a table with an index")),
+    };
+
+    if rest.is_empty() {
+        return Ok(match op {
+            Op::Set(value) => {
+                // Replace in place so comments around the key and value survive.
+                if let Some(Item::Value(existing)) = table.get_mut(key) {
+                    let decor = existing.decor().clone();
+                    *existing = value.clone();
+                    *existing.decor_mut() = decor;
+                } else {
+                    table.insert(key, Item::Value(value.clone()));
+                }
+                true
+            }
+            Op::Unset => table.remove(key).is_some(),
+        });
+    }
+
+    if table.get(key).is_none() {
+        if let Op::Unset = op {
+            return Ok(false);
+        }
+        let mut child = Table::new();
+        child.set_implicit(true);
+        table.insert(key, Item::Table(child));
+    }
+
+    match table.get_mut(key).expect("inserted above") {
+        Item::Table(child) => {
+            let changed = apply_table(child, rest, op)?;
+            // Don't leave an empty `[section]` header behind after an unset.
+            if matches!(op, Op::Unset) && child.is_empty() {
+                table.remove(key);
+            }
+            Ok(changed)
+        }
+        Item::Value(Value::InlineTable(child)) => apply_table(child, rest, op),
+        Item::Value(Value::Array(array)) => apply_array(array, rest, op),
+        Item::ArrayOfTables(array) => apply_array_of_tables(array, rest, op),
+        _ => Err(not_container(segment, "a scalar value")),
+    }
+}
+
+fn apply_array(array: &mut Array, path: &[Segment], op: &Op) -> Result<bool> {
+    let (segment, rest) = path.split_first().expect("path is never empty");
+    let index = match segment {
+        Segment::Index(index) => *index,
+        Segment::Key(_) => return Err(not_container(segment, "an array with a key")),
+    };
+
+    if rest.is_empty() {
+        return match op {
+            Op::Set(value) if index < array.len() => {
+                array.replace(index, value.clone());
+                Ok(true)
+            }
+            Op::Set(value) if index == array.len() => {
+                array.push(value.clone());
+                Ok(true)
+            }
+            Op::Set(_) => Err(SigilError::invalid_config(
+                segment.to_string(),
+                format!("Index out of bounds (array has {} items)", array.len()),
+            )),
+            Op::Unset if index < array.len() => {
+                array.remove(index);
+                Ok(true)
+            }
+            Op::Unset => Ok(false),
+        };
+    }
+
+    match array.get_mut(index) {
+        Some(Value::InlineTable(child)) => apply_table(child, rest, op),
+        Some(Value::Array(child)) => apply_array(child, rest, op),
+        Some(_) => Err(not_container(segment, "a scalar value")),
+        None if matches!(op, Op::Unset) => Ok(false),
+        None => Err(SigilError::resource_not_found(format!("Config array index {}", segment))),
+    }
+}
+
+fn apply_array_of_tables(array: &mut ArrayOfTables, path: &[Segment], op: &Op) -> Result<bool> {
+    let (segment, rest) = path.split_first().expect("path is never empty");
+    let index = match segment {
+        Segment::Index(index) => *index,
+        Segment::Key(_) => return Err(not_container(segment, "an array of tables with a key")),
+    };
+
+    if rest.is_empty() {
+        return match op {
+            Op::Unset if index < array.len() => {
+                array.remove(index);
+                Ok(true)
+            }
+            Op::Unset => Ok(false),
+            Op::Set(_) => Err(SigilError::invalid_config(
+                segment.to_string(),
+                "Set individual keys of a table entry instead of replacing it".to_string(),
+            )),
+        };
+    }
+
+    match array.get_mut(index) {
+        Some(child) => apply_table(child, rest, op),
+        None if matches!(op, Op::Unset) => Ok(false),
+        None => Err(SigilError::resource_not_found(format!("Config array index {}", segment))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn set(key: &str, raw: &str) -> Result<Value> {
+        let schema = serde_json::to_value(schemars::schema_for!(Config)).expect("schema serializes");
+        let expected = schema_type(&schema, &parse_path(key)?);
+        parse_typed(raw, expected.as_ref(), key)
+    }
+
+    #[test]
+    fn unset_strings_stay_strings() {
+        for (key, raw) in [
+            ("secrets.file_identity", "0123"),
+            ("modules.aws.region", "true"),
+            ("modules.aws.secret_access_key", "007"),
+            ("general.default_shell", "1.5"),
+        ] {
+            assert_eq!(set(key, raw).unwrap().as_str(), Some(raw), "{}", key);
+        }
+    }
+
+    #[test]
+    fn parses_by_schema_type() {
+        assert_eq!(set("general.timeout_seconds", "0123").unwrap().as_integer(), Some(123));
+        assert!(set("general.timeout_seconds", "soon").is_err());
+        let recipients = set("secrets.file_recipients", "a,1").unwrap();
+        let items: Vec<_> = recipients.as_array().unwrap().iter().map(|item| item.as_str()).collect();
+        assert_eq!(items, [Some("a"), Some("1")]);
+    }
+
+    #[test]
+    fn unknown_paths_have_no_schema_type() {
+        let schema = serde_json::to_value(schemars::schema_for!(Config)).unwrap();
+        assert_eq!(schema_type(&schema, &parse_path("general.no_such_key").unwrap()), None);
+        assert_eq!(parse_typed("42", None, "x").unwrap().as_integer(), Some(42));
+    }
+}
@@ -0,0 +1,184 @@
+use super::{AwsConfig, AzureConfig, ProxmoxConfig, SecretsConfig, ENV_ORIGIN};
+use crate::error::{Result, SigilError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Origin recorded on values a profile overrides: `profile:<name>`.
+pub const PROFILE_ORIGIN_PREFIX: &str = "profile:";
+
+/// A named environment (homelab, staging, prod, ...) whose sections are laid
+/// over the merged configuration when it is active.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, Default)]
+#[serde(default)]
+pub struct ProfileConfig {
+    pub description: Option<String>,
+    pub aws: Option<AwsConfig>,
+    pub azure: Option<AzureConfig>,
+    pub proxmox: Option<ProxmoxConfig>,
+    pub secrets: Option<SecretsConfig>,
+    pub tasks: Option<ProfileTasksConfig>,
+    pub inventory: Option<ProfileInventoryConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, Default)]
+#[serde(default)]
+pub struct ProfileTasksConfig {
+    pub definitions_dir: Option<PathBuf>,
+    pub state_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema, Default)]
+#[serde(default)]
+pub struct ProfileInventoryConfig {
+    pub file: Option<PathBuf>,
+}
+
+/// Where each profile section lands in the main config tree.
+const PROFILE_SECTIONS: &[(&str, &[&str])] = &[
+    ("aws", &["modules", "aws"]),
+    ("azure", &["modules", "azure"]),
+    ("proxmox", &["modules", "proxmox"]),
+    ("secrets", &["secrets"]),
+    ("tasks", &["tasks"]),
+    ("inventory", &["inventory"]),
+];
+
+/// Pick the profile for this run: `--profile`/`SIGIL_PROFILE` first, then
+/// the `profile` key persisted by `sigil config profile use`.
+pub fn select(requested: Option<&str>, tree: &::config::Value) -> Option<String> {
+    requested.map(str::to_string).or_else(|| match &tree.kind {
+        ::config::ValueKind::Table(root) => root
+            .get("profile")
+            .and_then(|value| value.clone().into_string().ok())
+            .filter(|name| !name.is_empty()),
+        _ => None,
+    })
+}
+
+/// Lay `profiles.<name>` over the raw config tree, field by field. Values
+/// set through `SIGIL_*` environment variables still win over the profile.
+pub fn apply(tree: &mut ::config::Value, name: &str) -> Result<()> {
+    let root = match &mut tree.kind {
+        ::config::ValueKind::Table(root) => root,
+        _ => return Ok(()),
+    };
+
+    let profile = root
+        .get("profiles")
+        .and_then(|profiles| match &profiles.kind {
+            ::config::ValueKind::Table(profiles) => profiles.get(name).cloned(),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            let available = names(root);
+            SigilError::resource_not_found(format!(
+                "Profile '{}' (available: {})",
+                name,
+                if available.is_empty() { "none".to_string() } else { available.join(", ") }
+            ))
+        })?;
+
+    let sections = match profile.kind {
+        ::config::ValueKind::Table(sections) => sections,
+        _ => return Err(SigilError::invalid_config(format!("profiles.{}", name), "Must be a table".to_string())),
+    };
+
+    let origin = format!("{}{}", PROFILE_ORIGIN_PREFIX, name);
+    for (section, target) in PROFILE_SECTIONS {
+        if let Some(value) = sections.get(*section) {
+            let slot = target.iter().fold(&mut *root, |table, key| {
+                let entry = table
+                    .entry(key.to_string())
+                    .or_insert_with(|| ::config::Value::new(None, ::config::Map::<String, ::config::Value>::new()));
+                if !matches!(entry.kind, ::config::ValueKind::Table(_)) {
+                    *entry = ::config::Value::new(None, ::config::Map::<String, ::config::Value>::new());
+                }
+                match &mut entry.kind {
+                    ::config::ValueKind::Table(child) => child,
+                    _ => unreachable!("replaced with a table above"),
+                }
+            });
+            overlay(slot, value, &origin);
+        }
+    }
+
+    Ok(())
+}
+
+fn overlay(target: &mut ::config::Map<String, ::config::Value>, value: &::config::Value, origin: &String) {
+    let ::config::ValueKind::Table(source) = &value.kind else {
+        return;
+    };
+    for (key, child) in source {
+        match (&child.kind, target.get_mut(key)) {
+            (::config::ValueKind::Table(_), Some(existing)) if matches!(existing.kind, ::config::ValueKind::Table(_)) => {
+                if let ::config::ValueKind::Table(existing) = &mut existing.kind {
+                    overlay(existing, child, origin);
+                }
+            }
+            (_, Some(existing)) if existing.origin() == Some(ENV_ORIGIN) => {}
+            _ => {
+                target.insert(key.clone(), relabel(child, origin));
+            }
+        }
+    }
+}
+
+fn relabel(value: &::config::Value, origin: &String) -> ::config::Value {
+    let kind = match &value.kind {
+        ::config::ValueKind::Table(table) => ::config::ValueKind::Table(
+            table
+                .iter()
+                .map(|(key, child)| (key.clone(), relabel(child, origin)))
+                .collect(),
+        ),
+        ::config::ValueKind::Array(items) => {
+            ::config::ValueKind::Array(items.iter().map(|item| relabel(item, origin)).collect())
+        }
+        other => other.clone(),
+    };
+    ::config::Value::new(Some(origin), kind)
+}
+
+fn names(root: &::config::Map<String, ::config::Value>) -> Vec<String> {
+    match root.get("profiles").map(|profiles| &profiles.kind) {
+        Some(::config::ValueKind::Table(profiles)) => {
+            let mut names: Vec<String> = profiles.keys().cloned().collect();
+            names.sort();
+            names
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Sections a profile overrides, for `sigil config profile list`.
+pub fn summary(profile: &ProfileConfig) -> String {
+    let mut sections = Vec::new();
+    if profile.aws.is_some() {
+        sections.push("aws");
+    }
+    if profile.azure.is_some() {
+        sections.push("azure");
+    }
+    if profile.proxmox.is_some() {
+        sections.push("proxmox");
+    }
+    if profile.secrets.is_some() {
+        sections.push("secrets");
+    }
+    if profile.tasks.is_some() {
+        sections.push("tasks");
+    }
+    if profile.inventory.is_some() {
+        sections.push("inventory");
+    }
+    if sections.is_empty() {
+        "no overrides".to_string()
+    } else {
+        sections.join(", ")
+    }
+}
+
+pub type Profiles = BTreeMap<String, ProfileConfig>;
@@ -0,0 +1,102 @@
+use super::Config;
+use crate::error::{Result, SigilError};
+use crate::secrets::{self, redact, SecretRef};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Prefix for credentials read from another environment variable.
+pub const ENV_SCHEME: &str = "env:";
+
+/// Prefix for credentials read from a file (trailing newline stripped).
+pub const FILE_SCHEME: &str = "file:";
+
+/// Shown in place of plaintext credentials.
+pub const REDACTED: &str = "********";
+
+/// A credential field in the config. It holds either a reference that is
+/// resolved when the credential is used (`secret://path#key`, `env:VAR`,
+/// `file:/path`) or a plaintext value, which is never shown in `Debug`
+/// output or `sigil config show`.
+#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct SecretValue(String);
+
+impl SecretValue {
+    /// The `secret://` reference, if this value is one.
+    pub fn secret_ref(&self) -> Option<SecretRef> {
+        SecretRef::parse(&self.0)
+    }
+
+    pub fn is_plaintext(&self) -> bool {
+        !(self.0.starts_with(secrets::SECRET_SCHEME)
+            || self.0.starts_with(ENV_SCHEME)
+            || self.0.starts_with(FILE_SCHEME))
+    }
+
+    /// Safe to display: references as written, plaintext redacted.
+    pub fn display(&self) -> &str {
+        if self.is_plaintext() {
+            REDACTED
+        } else {
+            &self.0
+        }
+    }
+
+    /// Replace a plaintext value with the redaction marker.
+    pub fn redact(&mut self) {
+        if self.is_plaintext() {
+            self.0 = REDACTED.to_string();
+        }
+    }
+
+    /// Resolve without a secrets backend: plaintext, `env:` and `file:`
+    /// values. Returns `None` for `secret://` references. Resolved values
+    /// are masked in output from then on.
+    pub fn resolve_local(&self) -> Result<Option<String>> {
+        let value = self.read_local()?;
+        if let Some(value) = &value {
+            redact::register(value);
+        }
+        Ok(value)
+    }
+
+    fn read_local(&self) -> Result<Option<String>> {
+        if let Some(var) = self.0.strip_prefix(ENV_SCHEME) {
+            return std::env::var(var)
+                .map(Some)
+                .map_err(|_| SigilError::Secret(format!("Environment variable {} is not set", var)));
+        }
+        if let Some(path) = self.0.strip_prefix(FILE_SCHEME) {
+            let path = match path.strip_prefix("~/") {
+                Some(rest) => super::home_dir().join(rest),
+                None => Path::new(path).to_path_buf(),
+            };
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| SigilError::Secret(format!("Cannot read {}: {}", path.display(), e)))?;
+            return Ok(Some(content.trim_end_matches(['\r', '\n']).to_string()));
+        }
+        if self.secret_ref().is_some() {
+            return Ok(None);
+        }
+        Ok(Some(self.0.clone()))
+    }
+
+    /// Resolve to the actual credential, looking `secret://` references up
+    /// in the configured backend.
+    pub async fn resolve(&self, config: &Config) -> Result<String> {
+        if let Some(value) = self.resolve_local()? {
+            return Ok(value);
+        }
+        let reference = self.secret_ref().expect("only secret:// values need a backend");
+        let store = secrets::open_store(config)?;
+        secrets::resolve(store.as_ref(), &reference).await
+    }
+}
+
+impl fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SecretValue").field(&self.display()).finish()
+    }
+}
@@ -0,0 +1,468 @@
+use super::{exposure_warning, migrate, Config, ConfigLayer, LoadOptions, LoadedConfig};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml_edit::{ImDocument, Item, TableLike};
+
+pub const LOG_LEVELS: &[&str] = &["trace", "debug", "info", "warn", "error"];
+pub const LOG_FORMATS: &[&str] = &["json", "pretty", "compact", "full"];
+pub const LOG_ROTATIONS: &[&str] = &["hourly", "daily", "never"];
+pub const SECRET_BACKENDS: &[&str] = &["env", "vault", "file"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A single finding from `sigil config validate`, located in the file that
+/// supplied the offending value when one is known.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub key: Option<String>,
+    pub message: String,
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line, self.column) {
+            (Some(file), Some(line), Some(column)) => write!(f, "{}:{}:{}: ", file.display(), line, column)?,
+            (Some(file), _, _) => write!(f, "{}: ", file.display())?,
+            _ => {}
+        }
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: ", severity)?;
+        if let Some(key) = &self.key {
+            write!(f, "{}: ", key)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Validate every config file layer on its own (syntax and types), then, if
+/// those are clean, run semantic checks against the merged result.
+pub fn validate(options: &LoadOptions) -> anyhow::Result<Vec<Diagnostic>> {
+    let mut diagnostics: Vec<Diagnostic> = Config::layers(options.path.as_deref())?
+        .iter()
+        .filter_map(ConfigLayer::path)
+        .flat_map(|path| check_file(path, options.strict))
+        .collect();
+
+    if !diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        // Unknown keys were already reported per file above.
+        let lenient = LoadOptions {
+            strict: false,
+            ..options.clone()
+        };
+        let loaded = Config::load_layered(&lenient)?;
+        let mut checker = Checker {
+            loaded: &loaded,
+            diagnostics: Vec::new(),
+        };
+        checker.run();
+        diagnostics.extend(checker.diagnostics);
+    }
+
+    diagnostics.sort_by_key(|d| std::cmp::Reverse(d.severity));
+    Ok(diagnostics)
+}
+
+/// Syntax, type and unknown-key problems in one file, with positions taken
+/// from the TOML parser or from the span of the offending key.
+pub fn check_file(path: &Path, strict: bool) -> Vec<Diagnostic> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            return vec![Diagnostic {
+                severity: Severity::Error,
+                key: None,
+                message: format!("Cannot read file: {}", e),
+                file: Some(path.to_path_buf()),
+                line: None,
+                column: None,
+            }];
+        }
+    };
+
+    let mut doc = match content.parse::<toml_edit::DocumentMut>() {
+        Ok(doc) => doc,
+        Err(e) => {
+            let (line, column) = e
+                .span()
+                .map(|span| line_column(&content, span.start))
+                .unzip();
+            return vec![Diagnostic {
+                severity: Severity::Error,
+                key: None,
+                message: e.message().trim().to_string(),
+                file: Some(path.to_path_buf()),
+                line,
+                column,
+            }];
+        }
+    };
+
+    let diagnostic = |severity, key: String, message: String| {
+        let (line, column) = locate(&content, &key).unzip();
+        Diagnostic {
+            severity,
+            key: Some(key),
+            message,
+            file: Some(path.to_path_buf()),
+            line,
+            column,
+        }
+    };
+
+    let mut diagnostics = Vec::new();
+    match migrate::migrate(&mut doc) {
+        Ok(Some(migrated)) if migrated.changed => diagnostics.push(diagnostic(
+            Severity::Warning,
+            "config_version".to_string(),
+            format!(
+                "File is at version {}, current is {}; run 'sigil config migrate'",
+                migrated.from,
+                migrate::CURRENT_CONFIG_VERSION
+            ),
+        )),
+        Ok(_) => {}
+        Err(e) => return vec![diagnostic(Severity::Error, "config_version".to_string(), e.to_string())],
+    }
+
+    let value: toml::Value = match toml::from_str(&doc.to_string()) {
+        Ok(value) => value,
+        Err(e) => return vec![diagnostic(Severity::Error, String::new(), e.message().to_string())],
+    };
+
+    if let Some(warning) = value.as_table().and_then(|table| exposure_warning(path, table)) {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            key: None,
+            message: warning,
+            file: Some(path.to_path_buf()),
+            line: None,
+            column: None,
+        });
+    }
+
+    let mut unknown = Vec::new();
+    let result = serde_path_to_error::deserialize::<_, Config>(serde_ignored::Deserializer::new(
+        value,
+        &mut |path: serde_ignored::Path| unknown.push(path.to_string()),
+    ));
+    if let Err(e) = result {
+        diagnostics.push(diagnostic(
+            Severity::Error,
+            e.path().to_string(),
+            e.inner().message().trim().to_string(),
+        ));
+    }
+
+    let severity = if strict { Severity::Error } else { Severity::Warning };
+    for key in unknown {
+        diagnostics.push(diagnostic(severity, key, "Unknown configuration key".to_string()));
+    }
+
+    diagnostics
+}
+
+/// Find the 1-based line and column of a dotted key in a TOML document.
+/// Falls back to the deepest enclosing key that exists.
+pub fn locate(content: &str, key: &str) -> Option<(usize, usize)> {
+    let doc = ImDocument::parse(content.to_string()).ok()?;
+    let mut table: &dyn TableLike = doc.as_table();
+    let mut found = None;
+
+    for part in key.split('.') {
+        let (key, item) = table.get_key_value(part)?;
+        if let Some(span) = key.span() {
+            found = Some(line_column(content, span.start));
+        }
+        table = match item {
+            Item::Table(child) => child,
+            Item::Value(toml_edit::Value::InlineTable(child)) => child,
+            _ => break,
+        };
+    }
+
+    found
+}
+
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rfind('\n').map_or(before.len(), |pos| before.len() - pos - 1) + 1;
+    (line, column)
+}
+
+struct Checker<'a> {
+    loaded: &'a LoadedConfig,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn run(&mut self) {
+        let config = &self.loaded.config;
+
+        // general
+        let shell = Path::new(&config.general.default_shell);
+        if !shell.is_file() {
+            self.error("general.default_shell", format!("Shell '{}' does not exist", shell.display()));
+        }
+        if config.general.timeout_seconds == 0 {
+            self.error("general.timeout_seconds", "Must be greater than 0");
+        }
+        for (key, dir) in [
+            ("general.data_dir", &config.general.data_dir),
+            ("general.config_dir", &config.general.config_dir),
+            ("general.log_dir", &config.general.log_dir),
+            ("tasks.definitions_dir", &config.tasks.definitions_dir),
+            ("tasks.state_dir", &config.tasks.state_dir),
+        ] {
+            if dir.is_file() {
+                self.error(key, format!("'{}' is a file, expected a directory", dir.display()));
+            } else if dir.is_relative() {
+                self.warning(key, "Relative path is resolved against the working directory");
+            }
+        }
+
+        // audit
+        if config.audit.file.is_dir() {
+            self.error("audit.file", format!("'{}' is a directory", config.audit.file.display()));
+        }
+
+        // logging
+        self.one_of("logging.level", &config.logging.level, LOG_LEVELS);
+        self.one_of("logging.format", &config.logging.format, LOG_FORMATS);
+        self.one_of("logging.file_format", &config.logging.file_format, LOG_FORMATS);
+        self.one_of("logging.rotation", &config.logging.rotation, LOG_ROTATIONS);
+        for (target, level) in &config.logging.modules {
+            self.one_of(&format!("logging.modules.{}", target), level, LOG_LEVELS);
+        }
+        if !config.logging.file_enabled && !config.logging.console_enabled {
+            self.warning("logging.console_enabled", "Both console and file logging are disabled");
+        }
+
+        // modules
+        let system = &config.modules.system;
+        self.percentage("modules.system.default_cpu_threshold", system.default_cpu_threshold);
+        self.percentage("modules.system.default_memory_threshold", system.default_memory_threshold);
+        if system.monitor_interval_seconds == 0 {
+            self.error("modules.system.monitor_interval_seconds", "Must be greater than 0");
+        }
+
+        let ssh = &config.modules.ssh;
+        if ssh.parallelism == 0 {
+            self.error("modules.ssh.parallelism", "Must be greater than 0");
+        }
+        if ssh.connect_timeout_seconds == 0 {
+            self.error("modules.ssh.connect_timeout_seconds", "Must be greater than 0");
+        }
+
+        if let Some(aws) = &config.modules.aws {
+            if aws.region.as_ref().is_some_and(|region| region.trim().is_empty()) {
+                self.error("modules.aws.region", "Region must not be empty");
+            }
+            if aws.access_key_id.is_some() != aws.secret_access_key.is_some() {
+                self.error(
+                    "modules.aws.access_key_id",
+                    "access_key_id and secret_access_key must be set together",
+                );
+            }
+            if let Some(endpoint) = &aws.endpoint {
+                self.url("modules.aws.endpoint", endpoint);
+            }
+            if let Some(endpoint) = &aws.ec2_endpoint {
+                self.url("modules.aws.ec2_endpoint", endpoint);
+            }
+        }
+
+        if let Some(azure) = &config.modules.azure {
+            if azure.client_id.is_some() && (azure.tenant_id.is_none() || azure.client_secret.is_none()) {
+                self.error("modules.azure.client_id", "client_id requires tenant_id and client_secret");
+            }
+            if let Some(authority) = &azure.authority {
+                self.url("modules.azure.authority", authority);
+            }
+            if let Some(endpoint) = &azure.management_endpoint {
+                self.url("modules.azure.management_endpoint", endpoint);
+            }
+        }
+
+        if let Some(proxmox) = &config.modules.proxmox {
+            self.url("modules.proxmox.endpoint", &proxmox.endpoint);
+            if proxmox.token_id.is_some() != proxmox.token_secret.is_some() {
+                self.error("modules.proxmox.token_id", "token_id and token_secret must be set together");
+            }
+            if proxmox.password.is_none() && proxmox.token_id.is_none() {
+                self.warning("modules.proxmox", "Neither password nor API token is configured");
+            }
+            if !proxmox.verify_ssl {
+                self.warning("modules.proxmox.verify_ssl", "TLS certificate verification is disabled");
+            }
+        }
+
+        if let Some(socket) = config.modules.container.as_ref().and_then(|c| c.socket.as_ref()) {
+            if !socket.exists() {
+                self.warning("modules.container.socket", format!("{} does not exist", socket.display()));
+            }
+        }
+
+        // secrets
+        let secrets = &config.secrets;
+        self.one_of("secrets.backend", &secrets.backend, SECRET_BACKENDS);
+        if secrets.backend == "vault" {
+            match &secrets.vault_endpoint {
+                Some(endpoint) => self.url("secrets.vault_endpoint", endpoint),
+                None if std::env::var("VAULT_ADDR").is_ok() => {}
+                None => self.error("secrets.vault_endpoint", "Required when secrets.backend = \"vault\""),
+            }
+            if !matches!(secrets.vault_kv_version, 1 | 2) {
+                self.error("secrets.vault_kv_version", "Must be 1 or 2");
+            }
+        }
+        for recipient in &secrets.file_recipients {
+            if recipient.parse::<age::x25519::Recipient>().is_err() {
+                self.error(
+                    "secrets.file_recipients",
+                    format!("'{}' is not an age X25519 public key (age1...)", recipient),
+                );
+            }
+        }
+        if secrets.unlock_ttl_seconds == 0 {
+            self.error("secrets.unlock_ttl_seconds", "Must be greater than 0");
+        }
+        for pattern in &secrets.redact_patterns {
+            if let Err(e) = regex::Regex::new(pattern) {
+                self.error("secrets.redact_patterns", format!("Invalid pattern '{}': {}", pattern, e));
+            }
+        }
+
+        // tasks
+        if config.tasks.max_concurrent_tasks == 0 {
+            self.error("tasks.max_concurrent_tasks", "Must be greater than 0");
+        }
+        if config.tasks.default_timeout_seconds == 0 {
+            self.error("tasks.default_timeout_seconds", "Must be greater than 0");
+        }
+
+        // agent
+        let agent = &config.agent;
+        if agent.listen.parse::<std::net::SocketAddr>().is_err() {
+            self.error("agent.listen", format!("'{}' is not an address such as 127.0.0.1:7370", agent.listen));
+        }
+        for (key, first, second) in [
+            ("agent.tls_cert", &agent.tls_cert, &agent.tls_key),
+            ("agent.client_cert", &agent.client_cert, &agent.client_key),
+        ] {
+            if first.is_some() != second.is_some() {
+                self.error(key, "Certificate and key must be set together");
+            }
+        }
+        if agent.client_ca.is_some() && agent.tls_cert.is_none() {
+            self.error("agent.client_ca", "Client certificates require agent.tls_cert and agent.tls_key");
+        }
+        for (key, path) in [
+            ("agent.tls_cert", &agent.tls_cert),
+            ("agent.tls_key", &agent.tls_key),
+            ("agent.client_ca", &agent.client_ca),
+            ("agent.ca_cert", &agent.ca_cert),
+            ("agent.client_cert", &agent.client_cert),
+            ("agent.client_key", &agent.client_key),
+        ] {
+            if let Some(path) = path.as_ref().filter(|path| !path.is_file()) {
+                self.error(key, format!("{} does not exist", path.display()));
+            }
+        }
+
+        // inventory (sources are not queried here)
+        if let Err(e) = crate::inventory::Inventory::read_file(&config.inventory.file) {
+            self.error("inventory.file", e.to_string());
+        }
+    }
+
+    fn one_of(&mut self, key: &str, value: &str, allowed: &[&str]) {
+        if !allowed.contains(&value) {
+            let mut message = format!("'{}' is not one of: {}", value, allowed.join(", "));
+            if let Some(suggestion) = closest(value, allowed) {
+                message.push_str(&format!(" (did you mean '{}'?)", suggestion));
+            }
+            self.error(key, message);
+        }
+    }
+
+    fn percentage(&mut self, key: &str, value: u8) {
+        if value == 0 || value > 100 {
+            self.error(key, format!("{} is not a percentage between 1 and 100", value));
+        }
+    }
+
+    fn url(&mut self, key: &str, value: &str) {
+        if !(value.starts_with("https://") || value.starts_with("http://")) {
+            self.error(key, format!("'{}' is not an http(s) URL", value));
+        }
+    }
+
+    fn error(&mut self, key: &str, message: impl Into<String>) {
+        self.push(Severity::Error, key, message.into());
+    }
+
+    fn warning(&mut self, key: &str, message: impl Into<String>) {
+        self.push(Severity::Warning, key, message.into());
+    }
+
+    fn push(&mut self, severity: Severity, key: &str, message: String) {
+        // Point at the file (and position) that supplied the value, if any.
+        let file = self
+            .loaded
+            .origins
+            .iter()
+            .find(|(origin_key, _)| *origin_key == key || origin_key.starts_with(&format!("{}.", key)))
+            .and_then(|(_, layer)| layer.path())
+            .map(Path::to_path_buf);
+        let (line, column) = file
+            .as_deref()
+            .and_then(|file| std::fs::read_to_string(file).ok())
+            .and_then(|content| locate(&content, key))
+            .unzip();
+
+        self.diagnostics.push(Diagnostic {
+            severity,
+            key: Some(key.to_string()),
+            message,
+            file,
+            line,
+            column,
+        });
+    }
+}
+
+/// Suggest the allowed value with the smallest edit distance, if it is close.
+fn closest<'a>(value: &str, allowed: &[&'a str]) -> Option<&'a str> {
+    allowed
+        .iter()
+        .map(|candidate| (edit_distance(value, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -1,3 +1,4 @@
+use std::process::ExitStatus;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -17,11 +18,12 @@ pub enum SigilError {
     #[error("TOML serialization error: {0}")]
     TomlSer(#[from] toml::ser::Error),
 
+    /// `exit_code` is the failed command's, when there was one.
     #[error("Task execution failed: {message}")]
-    TaskExecution { message: String },
+    TaskExecution { message: String, exit_code: Option<i32> },
 
     #[error("System command failed: {command} - {error}")]
-    SystemCommand { command: String, error: String },
+    SystemCommand { command: String, error: String, exit_code: Option<i32> },
 
     #[error("Module error: {module} - {message}")]
     Module { module: String, message: String },
@@ -32,22 +34,45 @@ pub enum SigilError {
     #[error("Network error: {0}")]
     Network(String),
 
+    #[error("Secret error: {0}")]
+    Secret(String),
+
     #[error("Resource not found: {resource}")]
     ResourceNotFound { resource: String },
 
-    #[error("Permission denied: {operation}")]
-    PermissionDenied { operation: String },
-
     #[error("Invalid configuration: {field} - {reason}")]
     InvalidConfig { field: String, reason: String },
 }
 
 pub type Result<T> = std::result::Result<T, SigilError>;
 
+/// The codes [`SigilError::exit_code`] returns, for `sigil --help`.
+pub const EXIT_CODES: &str = "\
+Exit codes:
+  0   Success
+  1   Any other error
+  2   Invalid command line
+  65  Malformed JSON or TOML data
+  66  Resource not found
+  69  Network error
+  70  Task failed
+  71  System command failed
+  72  Module error
+  73  Secret error
+  74  I/O error
+  76  Authentication failed
+  78  Configuration error
+
+When a task or `system exec` fails because its command exited non-zero,
+sigil exits with that command's code instead (128+N if it was killed by
+signal N). On several hosts, that is when every failed host exited with
+the same code.";
+
 impl SigilError {
     pub fn task_execution<S: Into<String>>(message: S) -> Self {
         SigilError::TaskExecution {
             message: message.into(),
+            exit_code: None,
         }
     }
 
@@ -55,6 +80,7 @@ impl SigilError {
         SigilError::SystemCommand {
             command: command.into(),
             error: error.into(),
+            exit_code: None,
         }
     }
 
@@ -71,16 +97,46 @@ impl SigilError {
         }
     }
 
-    pub fn permission_denied<S: Into<String>>(operation: S) -> Self {
-        SigilError::PermissionDenied {
-            operation: operation.into(),
-        }
-    }
-
     pub fn invalid_config<S: Into<String>>(field: S, reason: S) -> Self {
         SigilError::InvalidConfig {
             field: field.into(),
             reason: reason.into(),
         }
     }
+
+    /// Record the exit code of the command behind a task or system command
+    /// failure; other errors are returned unchanged.
+    pub fn with_exit_code(mut self, code: Option<i32>) -> Self {
+        if let SigilError::TaskExecution { exit_code, .. } | SigilError::SystemCommand { exit_code, .. } = &mut self {
+            *exit_code = code;
+        }
+        self
+    }
+
+    /// [`with_exit_code`](Self::with_exit_code) from a process status,
+    /// using 128+N for a process killed by signal N like shells do.
+    pub fn with_exit_status(self, status: ExitStatus) -> Self {
+        let code = status.code();
+        #[cfg(unix)]
+        let code = code.or_else(|| std::os::unix::process::ExitStatusExt::signal(&status).map(|signal| 128 + signal));
+        self.with_exit_code(code)
+    }
+
+    /// Process exit code for this error; see [`EXIT_CODES`].
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            SigilError::TaskExecution { exit_code: Some(code @ 1..=255), .. }
+            | SigilError::SystemCommand { exit_code: Some(code @ 1..=255), .. } => *code as u8,
+            SigilError::Serde(_) | SigilError::TomlDe(_) | SigilError::TomlSer(_) => 65,
+            SigilError::ResourceNotFound { .. } => 66,
+            SigilError::Network(_) => 69,
+            SigilError::TaskExecution { .. } => 70,
+            SigilError::SystemCommand { .. } => 71,
+            SigilError::Module { .. } => 72,
+            SigilError::Secret(_) => 73,
+            SigilError::Io(_) => 74,
+            SigilError::Authentication(_) => 76,
+            SigilError::Config(_) | SigilError::InvalidConfig { .. } => 78,
+        }
+    }
 }
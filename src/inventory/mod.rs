@@ -0,0 +1,340 @@
+use crate::cli::InventoryCommands;
+use crate::config::Config;
+use crate::error::{Result, SigilError};
+use crate::output::{self, Render, Text};
+use crate::runtime::ssh::{RemoteCommand, RemoteJob, SshRunner};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+pub mod select;
+pub mod source;
+
+pub use source::SourceConfig;
+
+/// Variables by name. Values that are not strings are written as JSON when
+/// used in a template.
+pub type Vars = BTreeMap<String, Value>;
+
+/// `inventory.toml`. Command sources print the same shape, minus
+/// `sources`, as JSON.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct InventoryFile {
+    /// Variables for every host.
+    pub vars: Vars,
+    pub hosts: BTreeMap<String, HostEntry>,
+    pub groups: BTreeMap<String, GroupEntry>,
+    /// Dynamic sources, merged over the hosts and groups above in order.
+    pub sources: Vec<SourceConfig>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct HostEntry {
+    /// Hostname or IP to connect to (default: the host's name, which may
+    /// be a `~/.ssh/config` alias).
+    pub address: Option<String>,
+    /// Remote user; overrides `modules.ssh.user`.
+    pub user: Option<String>,
+    pub tags: Vec<String>,
+    /// Groups this host belongs to, besides those that list it.
+    pub groups: Vec<String>,
+    pub vars: Vars,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct GroupEntry {
+    pub hosts: Vec<String>,
+    pub vars: Vars,
+}
+
+/// A host with its group memberships and variables resolved.
+#[derive(Debug, Clone, Serialize)]
+pub struct Host {
+    pub name: String,
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    pub groups: Vec<String>,
+    pub tags: Vec<String>,
+    /// Inventory-wide variables, then each group's in name order, then the
+    /// host's own; later ones win.
+    pub vars: Vars,
+}
+
+impl Host {
+    /// A host that is not in the inventory, such as one given with `--host`.
+    pub fn named(name: &str) -> Self {
+        Host {
+            name: name.to_string(),
+            address: name.to_string(),
+            user: None,
+            groups: Vec::new(),
+            tags: Vec::new(),
+            vars: Vars::new(),
+        }
+    }
+
+    pub fn job(&self, command: RemoteCommand) -> RemoteJob {
+        RemoteJob {
+            host: self.name.clone(),
+            destination: self.address.clone(),
+            user: self.user.clone(),
+            command,
+        }
+    }
+
+    /// Values for `${name}` placeholders: `host` and `address`, overridden
+    /// by any variables of the same name.
+    pub fn template_vars(&self) -> HashMap<String, String> {
+        let mut values = HashMap::from([
+            ("host".to_string(), self.name.clone()),
+            ("address".to_string(), self.address.clone()),
+        ]);
+        for (name, value) in &self.vars {
+            values.insert(name.clone(), render(value));
+        }
+        values
+    }
+}
+
+impl Render for Host {
+    fn render(&self, text: &mut Text) {
+        text.item("🖥️ ", &self.name);
+        text.field("Address", &self.address);
+        if let Some(user) = &self.user {
+            text.field("User", user);
+        }
+        text.field("Groups", self.groups.join(", "));
+        text.field("Tags", self.tags.join(", "));
+        if !self.vars.is_empty() {
+            text.line("Variables:");
+            for (name, value) in &self.vars {
+                text.line(format!("  {} = {}", name, render(value)));
+            }
+        }
+    }
+}
+
+impl Render for [Host] {
+    fn render(&self, text: &mut Text) {
+        let rows: Vec<Vec<String>> = self
+            .iter()
+            .map(|host| {
+                let address = match &host.user {
+                    Some(user) => format!("{}@{}", user, host.address),
+                    None => host.address.clone(),
+                };
+                vec![host.name.clone(), address, host.groups.join(","), host.tags.join(",")]
+            })
+            .collect();
+        text.table(&["NAME", "ADDRESS", "GROUPS", "TAGS"], &rows);
+    }
+}
+
+/// A variable as it appears in a template.
+fn render(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Every host and group from the inventory file and its sources.
+#[derive(Debug, Default)]
+pub struct Inventory {
+    vars: Vars,
+    hosts: BTreeMap<String, HostEntry>,
+    groups: BTreeMap<String, GroupEntry>,
+}
+
+impl Inventory {
+    /// Read `inventory.file` and query its dynamic sources. A missing file
+    /// is an empty inventory.
+    pub async fn load(config: &Config) -> Result<Self> {
+        let path = &config.inventory.file;
+        let file = match Self::read_file(path)? {
+            Some(file) => file,
+            None => {
+                debug!("No inventory at {}", path.display());
+                return Ok(Inventory::default());
+            }
+        };
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+
+        let mut inventory = Inventory::default();
+        let sources = file.sources.clone();
+        inventory.merge(file);
+        for source in &sources {
+            let source = source.source();
+            info!("📡 Loading inventory from {}", source.describe());
+            let found = source.load(config, base_dir).await.map_err(|e| {
+                SigilError::invalid_config(format!("inventory source {}", source.describe()), e.to_string())
+            })?;
+            if !found.sources.is_empty() {
+                return Err(SigilError::invalid_config(
+                    format!("inventory source {}", source.describe()),
+                    "Sources cannot list further sources".to_string(),
+                ));
+            }
+            inventory.merge(found);
+        }
+        inventory.link_groups();
+        Ok(inventory)
+    }
+
+    /// Parse an inventory file without querying its sources.
+    pub fn read_file(path: &Path) -> Result<Option<InventoryFile>> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        toml::from_str(&content)
+            .map(Some)
+            .map_err(|e| SigilError::invalid_config(path.display().to_string(), e.to_string()))
+    }
+
+    /// Lay `other` over this inventory: addresses and users are replaced,
+    /// tags and memberships added, variables overridden key by key.
+    fn merge(&mut self, other: InventoryFile) {
+        self.vars.extend(other.vars);
+        for (name, entry) in other.hosts {
+            let host = self.hosts.entry(name).or_default();
+            if entry.address.is_some() {
+                host.address = entry.address;
+            }
+            if entry.user.is_some() {
+                host.user = entry.user;
+            }
+            extend_unique(&mut host.tags, entry.tags);
+            extend_unique(&mut host.groups, entry.groups);
+            host.vars.extend(entry.vars);
+        }
+        for (name, entry) in other.groups {
+            let group = self.groups.entry(name).or_default();
+            extend_unique(&mut group.hosts, entry.hosts);
+            group.vars.extend(entry.vars);
+        }
+    }
+
+    /// Record memberships both ways, adding hosts that only appear in a
+    /// group's list and groups that only appear in a host's.
+    fn link_groups(&mut self) {
+        for (host, entry) in &self.hosts {
+            for group in &entry.groups {
+                extend_unique(&mut self.groups.entry(group.clone()).or_default().hosts, [host.clone()]);
+            }
+        }
+        for (group, entry) in &self.groups {
+            for host in &entry.hosts {
+                extend_unique(&mut self.hosts.entry(host.clone()).or_default().groups, [group.clone()]);
+            }
+        }
+        for entry in self.hosts.values_mut() {
+            entry.groups.sort();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+
+    pub fn host(&self, name: &str) -> Option<Host> {
+        let entry = self.hosts.get(name)?;
+        let mut vars = self.vars.clone();
+        for group in &entry.groups {
+            if let Some(group) = self.groups.get(group) {
+                vars.extend(group.vars.clone());
+            }
+        }
+        vars.extend(entry.vars.clone());
+        Some(Host {
+            name: name.to_string(),
+            address: entry.address.clone().unwrap_or_else(|| name.to_string()),
+            user: entry.user.clone(),
+            groups: entry.groups.clone(),
+            tags: entry.tags.clone(),
+            vars,
+        })
+    }
+
+    pub fn hosts(&self) -> Vec<Host> {
+        self.hosts.keys().filter_map(|name| self.host(name)).collect()
+    }
+
+    /// Hosts matching a `--targets` expression; an error if there are none.
+    pub fn targets(&self, expression: &str) -> Result<Vec<Host>> {
+        let hosts = self.select(&expression.parse()?)?;
+        if hosts.is_empty() {
+            return Err(SigilError::resource_not_found(format!("Hosts matching '{}'", expression)));
+        }
+        Ok(hosts)
+    }
+}
+
+fn extend_unique(list: &mut Vec<String>, items: impl IntoIterator<Item = String>) {
+    for item in items {
+        if !list.contains(&item) {
+            list.push(item);
+        }
+    }
+}
+
+pub async fn handle_command(cmd: &InventoryCommands, config: &Config) -> Result<()> {
+    let inventory = Inventory::load(config).await?;
+
+    match cmd {
+        InventoryCommands::List { targets } => {
+            let hosts = match targets {
+                Some(expression) => inventory.select(&expression.parse()?)?,
+                None => inventory.hosts(),
+            };
+            if inventory.is_empty() && !output::is_structured() {
+                println!("{}", output::decorate("📂", format!("No hosts in {}", config.inventory.file.display())));
+            } else if hosts.is_empty() && !output::is_structured() {
+                println!("No hosts match");
+            } else {
+                output::print(hosts.as_slice())?;
+            }
+        }
+        InventoryCommands::Show { host } => {
+            let host = inventory
+                .host(host)
+                .ok_or_else(|| SigilError::resource_not_found(format!("Host '{}' in inventory", host)))?;
+            output::print(&host)?;
+        }
+        InventoryCommands::Ping { targets, parallel } => {
+            ping(&inventory.targets(targets)?, parallel.map(|n| n as usize), config).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Log in to each host and run `true`.
+async fn ping(hosts: &[Host], parallelism: Option<usize>, config: &Config) -> Result<()> {
+    info!("🏓 Pinging {} host(s)", hosts.len());
+    let runner = Arc::new(SshRunner::new(config, parallelism));
+    let command = RemoteCommand::system("true", &[], &HashMap::new(), None);
+    let results = runner.run(hosts.iter().map(|host| host.job(command.clone())).collect()).await?;
+
+    let mut failed = 0;
+    for (host, result) in hosts.iter().zip(&results) {
+        if result.success() {
+            println!("✅ {} ({}): reachable", host.name, host.address);
+        } else {
+            println!("❌ {} ({}): {}", host.name, host.address, result.describe());
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(SigilError::Network(format!("{} of {} hosts unreachable", failed, results.len())));
+    }
+    Ok(())
+}
@@ -0,0 +1,260 @@
+use super::{Host, Inventory};
+use crate::error::{Result, SigilError};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+/// A `--targets` expression: comma-separated patterns such as
+/// `group:web,!tag:canary`.
+///
+/// Plain patterns add hosts, `&` patterns keep only hosts that also match,
+/// and `!` patterns remove hosts. An expression with only `&`/`!` patterns
+/// starts from every host. A pattern is `all`, `host:NAME`, `group:NAME`,
+/// `tag:NAME`, or a bare name that may be a host or a group; names may use
+/// `*` wildcards.
+#[derive(Debug, Clone)]
+pub struct Selector {
+    expression: String,
+    terms: Vec<(Op, Pattern)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Add,
+    Intersect,
+    Remove,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    All,
+    Host(String),
+    Group(String),
+    Tag(String),
+    Name(String),
+}
+
+impl FromStr for Selector {
+    type Err = SigilError;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let invalid = |reason: String| SigilError::invalid_config(format!("--targets '{}'", expression), reason);
+
+        let mut terms = Vec::new();
+        for term in expression.split(',').map(str::trim) {
+            let (op, pattern) = match term.chars().next() {
+                Some('!') => (Op::Remove, &term[1..]),
+                Some('&') => (Op::Intersect, &term[1..]),
+                _ => (Op::Add, term),
+            };
+            let pattern = match pattern.split_once(':') {
+                _ if pattern == "all" || pattern == "*" => Pattern::All,
+                Some(("host", name)) => Pattern::Host(name.to_string()),
+                Some(("group", name)) => Pattern::Group(name.to_string()),
+                Some(("tag", name)) => Pattern::Tag(name.to_string()),
+                Some((kind, _)) => return Err(invalid(format!("Unknown pattern kind '{}:'", kind))),
+                None => Pattern::Name(pattern.to_string()),
+            };
+            if matches!(&pattern, Pattern::Host(name) | Pattern::Group(name) | Pattern::Tag(name) | Pattern::Name(name) if name.is_empty()) {
+                return Err(invalid(format!("Empty pattern in '{}'", term)));
+            }
+            terms.push((op, pattern));
+        }
+
+        Ok(Selector {
+            expression: expression.to_string(),
+            terms,
+        })
+    }
+}
+
+impl Inventory {
+    /// Hosts matching `selector`, in name order. Naming a host or group that
+    /// does not exist is an error rather than an empty match, so a typo
+    /// cannot silently shrink a run.
+    pub fn select(&self, selector: &Selector) -> Result<Vec<Host>> {
+        let mut selected: BTreeSet<&str> = if selector.terms.iter().any(|(op, _)| *op == Op::Add) {
+            BTreeSet::new()
+        } else {
+            self.hosts.keys().map(String::as_str).collect()
+        };
+
+        for (op, pattern) in &selector.terms {
+            let matched = self.resolve(pattern, &selector.expression)?;
+            match op {
+                Op::Add => selected.extend(matched),
+                Op::Intersect => selected.retain(|host| matched.contains(host)),
+                Op::Remove => selected.retain(|host| !matched.contains(host)),
+            }
+        }
+
+        Ok(selected.into_iter().filter_map(|name| self.host(name)).collect())
+    }
+
+    fn resolve(&self, pattern: &Pattern, expression: &str) -> Result<BTreeSet<&str>> {
+        let hosts = |name: &str| -> BTreeSet<&str> {
+            self.hosts.keys().filter(|host| glob(name, host)).map(String::as_str).collect()
+        };
+        let groups = |name: &str| -> BTreeSet<&str> {
+            self.groups
+                .iter()
+                .filter(|(group, _)| glob(name, group))
+                .flat_map(|(_, group)| group.hosts.iter().map(String::as_str))
+                .collect()
+        };
+        let unknown = |what: &str, name: &str| {
+            SigilError::resource_not_found(format!("{} '{}' in inventory (from '{}')", what, name, expression))
+        };
+
+        Ok(match pattern {
+            Pattern::All => self.hosts.keys().map(String::as_str).collect(),
+            Pattern::Host(name) => {
+                if !name.contains('*') && !self.hosts.contains_key(name) {
+                    return Err(unknown("Host", name));
+                }
+                hosts(name)
+            }
+            Pattern::Group(name) => {
+                if !name.contains('*') && !self.groups.contains_key(name) {
+                    return Err(unknown("Group", name));
+                }
+                groups(name)
+            }
+            Pattern::Tag(name) => self
+                .hosts
+                .iter()
+                .filter(|(_, host)| host.tags.iter().any(|tag| glob(&name.to_lowercase(), &tag.to_lowercase())))
+                .map(|(host, _)| host.as_str())
+                .collect(),
+            Pattern::Name(name) => {
+                if !name.contains('*') && !self.hosts.contains_key(name) && !self.groups.contains_key(name) {
+                    return Err(unknown("Host or group", name));
+                }
+                let mut matched = hosts(name);
+                matched.extend(groups(name));
+                matched
+            }
+        })
+    }
+}
+
+/// Match `text` against a pattern where `*` stands for any run of characters.
+fn glob(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inventory() -> Inventory {
+        let mut inventory = Inventory::default();
+        inventory.merge(
+            toml::from_str(
+                r#"
+                [hosts.web1]
+                tags = ["canary", "Prod"]
+                [hosts.web2]
+                tags = ["prod"]
+                [hosts.db1]
+                tags = ["prod"]
+                groups = ["db"]
+                [hosts.bastion]
+                [groups.web]
+                hosts = ["web1", "web2"]
+                "#,
+            )
+            .expect("valid inventory"),
+        );
+        inventory.link_groups();
+        inventory
+    }
+
+    /// The hosts an expression selects, or the error message.
+    fn select(expression: &str) -> std::result::Result<Vec<String>, String> {
+        let selector: Selector = expression.parse().map_err(|e: SigilError| e.to_string())?;
+        let hosts = inventory().select(&selector).map_err(|e| e.to_string())?;
+        Ok(hosts.into_iter().map(|host| host.name).collect())
+    }
+
+    #[test]
+    fn selects_hosts() {
+        for (expression, expected) in [
+            ("all", &["bastion", "db1", "web1", "web2"][..]),
+            ("*", &["bastion", "db1", "web1", "web2"]),
+            ("web1", &["web1"]),
+            ("web", &["web1", "web2"]),
+            ("web1, db1", &["db1", "web1"]),
+            ("db*", &["db1"]),
+            ("host:web*", &["web1", "web2"]),
+            ("host:*1", &["db1", "web1"]),
+            ("group:web", &["web1", "web2"]),
+            ("group:*", &["db1", "web1", "web2"]),
+            ("tag:canary", &["web1"]),
+            ("tag:PROD", &["db1", "web1", "web2"]),
+            ("group:web,!tag:canary", &["web2"]),
+            ("group:web,&tag:canary", &["web1"]),
+            ("!group:web", &["bastion", "db1"]),
+            ("&tag:prod,!db1", &["web1", "web2"]),
+            ("!web1,web1", &["web1"]),
+            ("host:nope*", &[]),
+            ("tag:nope", &[]),
+        ] {
+            assert_eq!(select(expression), Ok(expected.iter().map(|name| name.to_string()).collect()), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_expressions_and_unknown_names() {
+        for (expression, error) in [
+            ("nope", "Host or group 'nope'"),
+            ("host:nope", "Host 'nope'"),
+            ("group:web1", "Group 'web1'"),
+            ("web,!nope", "Host or group 'nope'"),
+            ("os:linux", "Unknown pattern kind 'os:'"),
+            ("host:", "Empty pattern in 'host:'"),
+            ("!", "Empty pattern in '!'"),
+            ("web1,,db1", "Empty pattern in ''"),
+        ] {
+            match select(expression) {
+                Err(message) => assert!(message.contains(error), "{}: {}", expression, message),
+                Ok(hosts) => panic!("{} selected {:?}", expression, hosts),
+            }
+        }
+    }
+
+    #[test]
+    fn globs() {
+        for (pattern, text, expected) in [
+            ("web1", "web1", true),
+            ("web1", "web10", false),
+            ("web*", "web", true),
+            ("web*", "web10", true),
+            ("*", "", true),
+            ("*1", "db1", true),
+            ("*1", "db2", false),
+            ("w*b*1", "web1", true),
+            ("a*a", "a", false),
+            ("a*a", "aa", true),
+            ("a*a", "aba", true),
+            ("a*b*c", "acb", false),
+            ("**", "x", true),
+        ] {
+            assert_eq!(glob(pattern, text), expected, "{} against {}", pattern, text);
+        }
+    }
+}
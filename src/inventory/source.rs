@@ -0,0 +1,152 @@
+use super::{HostEntry, InventoryFile, Vars};
+use crate::config::Config;
+use crate::error::{Result, SigilError};
+use crate::modules::proxmox::ProxmoxClient;
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+const MODULE: &str = "inventory";
+
+/// Somewhere hosts are discovered at run time rather than listed by hand.
+#[async_trait]
+pub trait InventorySource: Send + Sync {
+    /// Shown in progress messages and errors.
+    fn describe(&self) -> String;
+
+    /// Hosts and groups to merge into the inventory. `base_dir` is the
+    /// directory of the inventory file.
+    async fn load(&self, config: &Config, base_dir: &Path) -> Result<InventoryFile>;
+}
+
+/// A `[[sources]]` entry in `inventory.toml`.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SourceConfig {
+    Proxmox(ProxmoxSource),
+    Command(CommandSource),
+}
+
+impl SourceConfig {
+    pub fn source(&self) -> &dyn InventorySource {
+        match self {
+            SourceConfig::Proxmox(source) => source,
+            SourceConfig::Command(source) => source,
+        }
+    }
+}
+
+/// VMs and containers of the configured Proxmox cluster. Each guest becomes
+/// a host named after it, with its Proxmox tags and `proxmox_*` variables.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxmoxSource {
+    /// Group every discovered guest joins.
+    pub group: String,
+    /// Only guests with this Proxmox tag.
+    pub tag: Option<String>,
+    /// Skip guests that are not running.
+    pub running_only: bool,
+    /// Appended to guest names to form addresses, e.g. `lan` for `web1.lan`.
+    pub domain: Option<String>,
+}
+
+impl Default for ProxmoxSource {
+    fn default() -> Self {
+        ProxmoxSource {
+            group: "proxmox".to_string(),
+            tag: None,
+            running_only: true,
+            domain: None,
+        }
+    }
+}
+
+#[async_trait]
+impl InventorySource for ProxmoxSource {
+    fn describe(&self) -> String {
+        "proxmox".to_string()
+    }
+
+    async fn load(&self, config: &Config, _base_dir: &Path) -> Result<InventoryFile> {
+        let client = ProxmoxClient::connect(config).await?;
+        let mut found = InventoryFile::default();
+        for guest in client.guests().await? {
+            if self.running_only && guest.status != "running" {
+                continue;
+            }
+            if self.tag.as_ref().is_some_and(|tag| !guest.has_tag(tag)) {
+                continue;
+            }
+            let name = match guest.name.as_str() {
+                "" => guest.vmid.to_string(),
+                name => name.to_string(),
+            };
+            let vars = Vars::from([
+                ("proxmox_vmid".to_string(), Value::from(guest.vmid)),
+                ("proxmox_node".to_string(), Value::from(guest.node.clone())),
+                ("proxmox_type".to_string(), Value::from(guest.kind.clone())),
+            ]);
+            let host = HostEntry {
+                address: self.domain.as_ref().map(|domain| format!("{}.{}", name, domain)),
+                user: None,
+                tags: guest.tag_names().map(str::to_string).collect(),
+                groups: vec![self.group.clone()],
+                vars,
+            };
+            found.hosts.insert(name, host);
+        }
+        Ok(found)
+    }
+}
+
+/// A program that prints inventory JSON (`vars`, `hosts`, `groups`) on
+/// stdout. It runs in the inventory file's directory, and a relative path
+/// such as `./hosts.sh` is taken from there too.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CommandSource {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[async_trait]
+impl InventorySource for CommandSource {
+    fn describe(&self) -> String {
+        format!("command '{}'", self.command)
+    }
+
+    async fn load(&self, config: &Config, base_dir: &Path) -> Result<InventoryFile> {
+        let timeout = Duration::from_secs(config.general.timeout_seconds);
+        let program = match Path::new(&self.command) {
+            path if path.is_relative() && self.command.contains('/') => base_dir.join(path),
+            path => path.to_path_buf(),
+        };
+        let mut command = Command::new(program);
+        command
+            .args(&self.args)
+            .current_dir(base_dir)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+
+        let output = tokio::time::timeout(timeout, command.output())
+            .await
+            .map_err(|_| SigilError::module(MODULE.to_string(), format!("{} timed out after {}s", self.describe(), timeout.as_secs())))?
+            .map_err(|e| SigilError::system_command(self.command.as_str(), &e.to_string()))?;
+        if !output.status.success() {
+            return Err(SigilError::system_command(
+                self.command.as_str(),
+                String::from_utf8_lossy(&output.stderr).trim(),
+            ));
+        }
+        serde_json::from_slice(&output.stdout).map_err(|e| {
+            SigilError::module(MODULE.to_string(), format!("{} printed invalid inventory JSON: {}", self.describe(), e))
+        })
+    }
+}
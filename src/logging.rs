@@ -0,0 +1,238 @@
+//! Tracing setup from `[logging]`: console and file output, formats,
+//! levels and log file rotation.
+//!
+//! A console logger with the default settings is installed first so that
+//! configuration loading can log; [`apply`] then swaps in the configured
+//! outputs and levels.
+
+use crate::config::validate::LOG_LEVELS;
+use crate::config::{Config, LoggingConfig};
+use crate::secrets::redact::{RedactingMakeWriter, RedactingWriter};
+use chrono::{DateTime, Local};
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use tracing::warn;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+
+/// Name of the current log file in `general.log_dir`.
+const LOG_FILE: &str = "sigil.log";
+
+type Output = Vec<Box<dyn Layer<Registry> + Send + Sync>>;
+
+struct Handles {
+    output: reload::Handle<Output, Registry>,
+    filter: reload::Handle<EnvFilter, tracing_subscriber::layer::Layered<reload::Layer<Output, Registry>, Registry>>,
+    verbose: u8,
+}
+
+static HANDLES: OnceLock<Handles> = OnceLock::new();
+
+/// Install a console logger with the default `[logging]` settings, `verbose`
+/// levels above the default.
+pub fn init(verbose: u8) {
+    let defaults = LoggingConfig::default();
+    let (output, output_handle) = reload::Layer::new(vec![console(&defaults.format)]);
+    let (filter, filter_handle) = reload::Layer::new(filter(&defaults, verbose));
+    tracing_subscriber::registry().with(output).with(filter).init();
+    let _ = HANDLES.set(Handles {
+        output: output_handle,
+        filter: filter_handle,
+        verbose,
+    });
+}
+
+/// Log as `config.logging` asks from here on.
+pub fn apply(config: &Config) {
+    let Some(handles) = HANDLES.get() else {
+        return;
+    };
+    let logging = &config.logging;
+
+    let mut output: Output = Vec::new();
+    if logging.console_enabled {
+        output.push(console(&logging.format));
+    }
+    let mut file_error = None;
+    if logging.file_enabled {
+        match LogFile::open(&config.general.log_dir, logging) {
+            Ok(file) => output.push(layer(&logging.file_format, SharedLogFile(Arc::new(Mutex::new(file))), false)),
+            Err(e) => file_error = Some(e),
+        }
+    }
+    let _ = handles.output.reload(output);
+    let _ = handles.filter.reload(filter(logging, handles.verbose));
+
+    if let Some(e) = file_error {
+        warn!("⚠️  Not logging to {}: {}", config.general.log_dir.display(), e);
+    }
+}
+
+fn console(format: &str) -> Box<dyn Layer<Registry> + Send + Sync> {
+    let ansi = !crate::output::no_color() && io::stderr().is_terminal();
+    layer(format, RedactingMakeWriter, ansi)
+}
+
+/// A formatting layer for one output. Unknown formats, which `sigil config
+/// validate` reports, fall back to `full`.
+fn layer<W>(format: &str, writer: W, ansi: bool) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_writer(writer).with_ansi(ansi);
+    match format {
+        "json" => layer.json().boxed(),
+        "pretty" => layer.pretty().boxed(),
+        "compact" => layer.compact().boxed(),
+        _ => layer.boxed(),
+    }
+}
+
+/// `RUST_LOG` if set, otherwise `level` raised by `verbose` steps with the
+/// per-module levels on top.
+fn filter(logging: &LoggingConfig, verbose: u8) -> EnvFilter {
+    if std::env::var_os(EnvFilter::DEFAULT_ENV).is_some() {
+        return EnvFilter::from_default_env();
+    }
+    let level = LOG_LEVELS.iter().position(|level| *level == logging.level).unwrap_or(2);
+    let mut directives = vec![LOG_LEVELS[level.saturating_sub(verbose as usize)].to_string()];
+    directives.extend(
+        logging
+            .modules
+            .iter()
+            .filter(|(_, level)| LOG_LEVELS.contains(&level.as_str()))
+            .map(|(target, level)| format!("{}={}", target, level)),
+    );
+    EnvFilter::try_new(directives.join(",")).unwrap_or_else(|_| EnvFilter::new(&directives[0]))
+}
+
+/// `sigil.log`, moved aside as `sigil.<time>.log` when it grows past the
+/// size limit or the rotation period ends. Only the newest `max_files`
+/// rotated files are kept.
+struct LogFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+    /// The rotation period the current file was started in.
+    period: String,
+    rotation: String,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl LogFile {
+    fn open(dir: &Path, logging: &LoggingConfig) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = open_append(&dir.join(LOG_FILE))?;
+        let metadata = file.metadata()?;
+        let started: DateTime<Local> = metadata.modified().map(DateTime::from).unwrap_or_else(|_| Local::now());
+        Ok(LogFile {
+            dir: dir.to_path_buf(),
+            file,
+            size: metadata.len(),
+            period: period(&logging.rotation, started),
+            rotation: logging.rotation.clone(),
+            max_size: logging.max_file_size_mb * 1024 * 1024,
+            max_files: logging.max_files,
+        })
+    }
+
+    fn write_event(&mut self, buf: &[u8]) -> io::Result<()> {
+        let now = period(&self.rotation, Local::now());
+        let full = self.max_size > 0 && self.size > 0 && self.size + buf.len() as u64 > self.max_size;
+        if now != self.period || full {
+            // Keep logging to the old file if it cannot be moved aside.
+            if self.rotate().is_ok() {
+                self.period = now;
+            }
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let current = self.dir.join(LOG_FILE);
+        let stamp = Local::now().format("%Y%m%d-%H%M%S");
+        let mut rotated = self.dir.join(format!("sigil.{}.log", stamp));
+        let mut n = 1;
+        while rotated.exists() {
+            rotated = self.dir.join(format!("sigil.{}-{}.log", stamp, n));
+            n += 1;
+        }
+        fs::rename(&current, &rotated)?;
+        self.file = open_append(&current)?;
+        self.size = 0;
+        self.prune();
+        Ok(())
+    }
+
+    /// Remove all but the newest `max_files` rotated files. Their names
+    /// sort by age.
+    fn prune(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut rotated: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name != LOG_FILE && name.starts_with("sigil.") && name.ends_with(".log"))
+            })
+            .collect();
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(self.max_files);
+        for path in &rotated[..excess] {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Identifies the rotation period `time` falls in.
+fn period(rotation: &str, time: DateTime<Local>) -> String {
+    match rotation {
+        "hourly" => time.format("%Y%m%d%H").to_string(),
+        "daily" => time.format("%Y%m%d").to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Log files hold the same (masked) lines as the console, kept owner-only.
+fn open_append(path: &Path) -> io::Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+#[derive(Clone)]
+struct SharedLogFile(Arc<Mutex<LogFile>>);
+
+impl Write for SharedLogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).write_event(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).file.flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for SharedLogFile {
+    type Writer = RedactingWriter<SharedLogFile>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.clone())
+    }
+}
@@ -1,37 +1,165 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
+use clap_complete::CompleteEnv;
 use anyhow::Result;
-use tracing::info;
+use std::process::ExitCode;
+use tracing::debug;
 
+mod agent;
+mod audit;
 mod cli;
+mod completions;
 mod config;
+mod inventory;
+mod logging;
+mod man;
 mod runtime;
 mod modules;
+mod output;
+mod secrets;
+mod tui;
 mod error;
 
-use cli::{Cli, Commands};
-use config::Config;
+use cli::{Cli, Commands, SecretCommands};
+use config::{Config, LoadOptions};
+use error::SigilError;
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+async fn main() -> ExitCode {
+    // Answers TAB presses from the scripts `sigil completions` prints.
+    CompleteEnv::with_factory(Cli::command).var(completions::ENV_VAR).complete();
 
-    info!("🔮 Sigil starting up...");
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    output::init(cli.output, cli.no_emoji);
 
-    let cli = Cli::parse();
-    let config = Config::load().await?;
+    logging::init(cli.verbose);
+    debug!("🔮 Sigil starting up...");
+
+    // Loaded per command so `sigil config validate` can report a broken file.
+    let options = LoadOptions {
+        path: cli.config.clone(),
+        profile: cli.profile.clone(),
+        strict: cli.strict_config,
+    };
+
+    let event = audit::Event::from_matches(&matches);
+    let result = run(&cli, &options).await;
+    if let Some(event) = event {
+        // Read again so `config` commands are recorded where the edited
+        // configuration says.
+        let settings = Config::load_layered(&options)
+            .map(|loaded| loaded.config.audit)
+            .unwrap_or_default();
+        audit::record(&settings, event, &result);
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+/// Exit code for a failed command, from the first `SigilError` behind it;
+/// see `error::EXIT_CODES`. Config and I/O errors raised without one get
+/// the code of the variant that would wrap them.
+fn exit_code(error: &anyhow::Error) -> u8 {
+    for cause in error.chain() {
+        if let Some(e) = cause.downcast_ref::<SigilError>() {
+            return e.exit_code();
+        }
+        if cause.is::<::config::ConfigError>() {
+            return 78;
+        }
+        if cause.is::<std::io::Error>() {
+            return 74;
+        }
+    }
+    1
+}
+
+async fn run(cli: &Cli, options: &LoadOptions) -> Result<()> {
+    let load_config = || async {
+        let config = Config::load(options).await?;
+        logging::apply(&config);
+        Ok::<_, anyhow::Error>(config)
+    };
+
+    if let Some(address) = &cli.agent {
+        let config = load_config().await?;
+        agent::client::handle_command(&cli.command, address, &config).await?;
+        return Ok(());
+    }
 
     match &cli.command {
         Commands::System(args) => {
+            let config = load_config().await?;
+            config.print_header();
             modules::system::handle_command(args, &config).await?;
         }
         Commands::Task(args) => {
+            let config = load_config().await?;
+            config.print_header();
             runtime::task_runner::handle_command(args, &config).await?;
         }
+        Commands::Secret(SecretCommands::Agent { socket, ttl }) => {
+            // Runs detached with no terminal; needs no configuration.
+            secrets::agent::serve(socket, std::time::Duration::from_secs(*ttl)).await?;
+        }
+        Commands::Secret(args) => {
+            let config = load_config().await?;
+            config.print_header();
+            secrets::handle_command(args, &config).await?;
+        }
+        Commands::Proxmox(args) => {
+            let config = load_config().await?;
+            config.print_header();
+            modules::proxmox::handle_command(args, &config).await?;
+        }
+        Commands::Aws(args) => {
+            let config = load_config().await?;
+            config.print_header();
+            modules::aws::handle_command(args, &config).await?;
+        }
+        Commands::Azure(args) => {
+            let config = load_config().await?;
+            config.print_header();
+            modules::azure::handle_command(args, &config).await?;
+        }
+        Commands::Container(args) => {
+            let config = load_config().await?;
+            config.print_header();
+            modules::container::handle_command(args, &config).await?;
+        }
+        Commands::Inventory(args) => {
+            let config = load_config().await?;
+            config.print_header();
+            inventory::handle_command(args, &config).await?;
+        }
+        Commands::Audit(args) => {
+            let config = load_config().await?;
+            config.print_header();
+            audit::handle_command(args, &config).await?;
+        }
+        Commands::Tui { services } => {
+            let config = load_config().await?;
+            config.print_header();
+            tui::handle_command(services, &config, options).await?;
+        }
+        Commands::Agent { listen } => {
+            let config = load_config().await?;
+            config.print_header();
+            agent::server::serve(&config, options, listen.as_deref()).await?;
+        }
         Commands::Config(args) => {
-            config::handle_command(args).await?;
+            config::handle_command(args, options).await?;
+        }
+        Commands::Completions { shell } => {
+            completions::print_script(*shell)?;
+        }
+        Commands::Man { commands, dir } => {
+            man::handle_command(commands, dir.as_deref())?;
         }
         Commands::Version => {
             println!("Sigil v{}", env!("CARGO_PKG_VERSION"));
@@ -0,0 +1,38 @@
+//! `sigil man`: roff manual pages generated from the `Cli` definition.
+//! Pages are named like `git`'s, `sigil-task-run.1` for `sigil task run`.
+
+use crate::cli::Cli;
+use crate::error::{Result, SigilError};
+use crate::output::{self, say};
+use clap::CommandFactory;
+use clap_mangen::Man;
+use std::io::Write;
+use std::path::Path;
+
+/// Print the page for `sigil <commands...>`, or write pages for every
+/// command into `dir`.
+pub fn handle_command(commands: &[String], dir: Option<&Path>) -> Result<()> {
+    let mut cli = Cli::command().disable_help_subcommand(true);
+    cli.build();
+
+    if let Some(dir) = dir {
+        std::fs::create_dir_all(dir)?;
+        clap_mangen::generate_to(cli, dir)?;
+        say!("{}", output::decorate("📖", format!("Wrote man pages to {}", dir.display())));
+        return Ok(());
+    }
+
+    let mut command = &cli;
+    for name in commands {
+        command = command
+            .find_subcommand(name)
+            .filter(|found| !found.is_hide_set())
+            .ok_or_else(|| {
+                SigilError::resource_not_found(format!("Command 'sigil {}'", commands.join(" ")))
+            })?;
+    }
+    let mut page = Vec::new();
+    Man::new(command.clone()).render(&mut page)?;
+    std::io::stdout().write_all(&page)?;
+    Ok(())
+}
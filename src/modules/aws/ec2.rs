@@ -0,0 +1,791 @@
+use super::sigv4::{self, Signer};
+use super::{aws_config, credentials, region, Credentials};
+use crate::cli::{Ec2Commands, InstanceSelector, RegionSelector};
+use crate::config::Config;
+use crate::error::{Result, SigilError};
+use crate::output;
+use reqwest::{Client, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tracing::debug;
+
+const MODULE: &str = "ec2";
+
+const API_VERSION: &str = "2016-11-15";
+
+/// Marks snapshots created by sigil; retention only ever deletes these.
+pub const MANAGED_TAG: &str = "sigil:managed";
+
+/// Instance a sigil snapshot was taken from.
+pub const INSTANCE_TAG: &str = "sigil:instance";
+
+/// `<fooSet><item>...</item></fooSet>`
+#[derive(Deserialize)]
+struct ItemSet<T> {
+    #[serde(default = "Vec::new", rename = "item")]
+    items: Vec<T>,
+}
+
+impl<T> Default for ItemSet<T> {
+    fn default() -> Self {
+        ItemSet { items: Vec::new() }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DescribeInstancesResponse {
+    #[serde(default)]
+    reservation_set: ItemSet<Reservation>,
+    next_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Reservation {
+    #[serde(default)]
+    instances_set: ItemSet<RawInstance>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawInstance {
+    instance_id: String,
+    #[serde(default)]
+    instance_type: String,
+    instance_state: StateName,
+    placement: Option<Placement>,
+    private_ip_address: Option<String>,
+    ip_address: Option<String>,
+    launch_time: Option<String>,
+    #[serde(default)]
+    tag_set: ItemSet<Tag>,
+    #[serde(default)]
+    block_device_mapping: ItemSet<BlockDevice>,
+}
+
+#[derive(Deserialize)]
+struct StateName {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Placement {
+    availability_zone: String,
+}
+
+#[derive(Deserialize)]
+struct Tag {
+    key: String,
+    #[serde(default)]
+    value: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlockDevice {
+    device_name: String,
+    ebs: Option<Ebs>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Ebs {
+    volume_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StateChangeResponse {
+    #[serde(default)]
+    instances_set: ItemSet<RawStateChange>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawStateChange {
+    instance_id: String,
+    current_state: StateName,
+    previous_state: StateName,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DescribeRegionsResponse {
+    #[serde(default)]
+    region_info: ItemSet<RegionInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegionInfo {
+    region_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSnapshot {
+    snapshot_id: String,
+    #[serde(default)]
+    start_time: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DescribeSnapshotsResponse {
+    #[serde(default)]
+    snapshot_set: ItemSet<RawSnapshot>,
+    next_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ErrorResponse {
+    errors: ErrorList,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ErrorList {
+    error: ApiError,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ApiError {
+    code: String,
+    #[serde(default)]
+    message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Volume {
+    pub device: String,
+    pub volume_id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Instance {
+    pub region: String,
+    pub instance_id: String,
+    pub name: Option<String>,
+    pub state: String,
+    pub instance_type: String,
+    pub availability_zone: Option<String>,
+    pub private_ip: Option<String>,
+    pub public_ip: Option<String>,
+    pub launch_time: Option<String>,
+    pub tags: BTreeMap<String, String>,
+    pub volumes: Vec<Volume>,
+}
+
+impl Instance {
+    fn from_raw(region: &str, raw: RawInstance) -> Self {
+        let tags: BTreeMap<String, String> = raw.tag_set.items.into_iter().map(|tag| (tag.key, tag.value)).collect();
+        Instance {
+            region: region.to_string(),
+            instance_id: raw.instance_id,
+            name: tags.get("Name").cloned(),
+            state: raw.instance_state.name,
+            instance_type: raw.instance_type,
+            availability_zone: raw.placement.map(|placement| placement.availability_zone),
+            private_ip: raw.private_ip_address,
+            public_ip: raw.ip_address,
+            launch_time: raw.launch_time,
+            tags,
+            volumes: raw
+                .block_device_mapping
+                .items
+                .into_iter()
+                .filter_map(|device| {
+                    Some(Volume {
+                        volume_id: device.ebs?.volume_id,
+                        device: device.device_name,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{} ({})", self.instance_id, name),
+            None => self.instance_id.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StateChange {
+    pub region: String,
+    pub instance_id: String,
+    pub previous: String,
+    pub current: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotRecord {
+    pub region: String,
+    pub instance_id: String,
+    pub volume_id: String,
+    pub snapshot_id: String,
+    /// `created` or `deleted`.
+    pub action: &'static str,
+}
+
+/// `Name=...`/`Values=...` filter of a Describe call.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+impl Filter {
+    pub fn new(name: &str, values: &[String]) -> Self {
+        Filter {
+            name: name.to_string(),
+            values: values.to_vec(),
+        }
+    }
+
+    /// `key=value` matches the tag's value, a bare `key` any instance
+    /// carrying the tag.
+    pub fn tag(spec: &str) -> Self {
+        match spec.split_once('=') {
+            Some((key, value)) => Filter::new(&format!("tag:{}", key), &[value.to_string()]),
+            None => Filter::new("tag-key", &[spec.to_string()]),
+        }
+    }
+}
+
+/// Numbered parameters for a list of filters: `Filter.1.Name`, `Filter.1.Value.1`, ...
+fn filter_params(filters: &[Filter]) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    for (index, filter) in filters.iter().enumerate() {
+        params.push((format!("Filter.{}.Name", index + 1), filter.name.clone()));
+        for (value_index, value) in filter.values.iter().enumerate() {
+            params.push((format!("Filter.{}.Value.{}", index + 1, value_index + 1), value.clone()));
+        }
+    }
+    params
+}
+
+fn numbered(prefix: &str, values: &[String]) -> Vec<(String, String)> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| (format!("{}.{}", prefix, index + 1), value.clone()))
+        .collect()
+}
+
+/// EC2 Query API client for one region.
+#[derive(Clone)]
+pub struct Ec2Client {
+    client: Client,
+    credentials: Credentials,
+    region: String,
+    endpoint: Option<String>,
+}
+
+impl Ec2Client {
+    pub async fn connect(config: &Config) -> Result<Self> {
+        let aws = aws_config(config);
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.general.timeout_seconds))
+            .build()
+            .map_err(|e| SigilError::Network(format!("Cannot create HTTP client: {}", e)))?;
+        Ok(Ec2Client {
+            client,
+            credentials: credentials(config, &aws).await?,
+            region: region(&aws),
+            endpoint: aws.ec2_endpoint.clone(),
+        })
+    }
+
+    /// The same client talking to another region.
+    pub fn for_region(&self, region: &str) -> Self {
+        Ec2Client {
+            region: region.to_string(),
+            ..self.clone()
+        }
+    }
+
+    fn url(&self) -> Result<Url> {
+        let url = match &self.endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => format!("https://ec2.{}.amazonaws.com/", self.region),
+        };
+        Url::parse(&url).map_err(|e| SigilError::invalid_config("modules.aws.ec2_endpoint".to_string(), e.to_string()))
+    }
+
+    /// Call an API action and parse its XML response.
+    async fn call<T: DeserializeOwned>(&self, action: &str, params: Vec<(String, String)>) -> Result<T> {
+        let url = self.url()?;
+        let body = [("Action".to_string(), action.to_string()), ("Version".to_string(), API_VERSION.to_string())]
+            .into_iter()
+            .chain(params)
+            .map(|(name, value)| format!("{}={}", sigv4::uri_encode(&name, false), sigv4::uri_encode(&value, false)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let content_type = ("content-type".to_string(), "application/x-www-form-urlencoded; charset=utf-8".to_string());
+        let signer = Signer {
+            credentials: &self.credentials,
+            region: &self.region,
+            service: "ec2",
+        };
+        let headers = signer.sign(
+            "POST",
+            &url,
+            std::slice::from_ref(&content_type),
+            &sigv4::sha256_hex(body.as_bytes()),
+            chrono::Utc::now(),
+        );
+
+        debug!("EC2 {} in {}", action, self.region);
+        let mut request = self.client.post(url).header(content_type.0, content_type.1).body(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| SigilError::Network(format!("EC2 request failed: {}", e)))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| SigilError::Network(format!("EC2 response unreadable: {}", e)))?;
+
+        if !status.is_success() {
+            let detail = match quick_xml::de::from_str::<ErrorResponse>(&text) {
+                Ok(error) => format!("{}: {}", error.errors.error.code, error.errors.error.message),
+                Err(_) => status.to_string(),
+            };
+            let message = format!("{} in {}: {}", action, self.region, detail);
+            return Err(match status {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => SigilError::Authentication(message),
+                _ => SigilError::module(MODULE.to_string(), message),
+            });
+        }
+        quick_xml::de::from_str(&text)
+            .map_err(|e| SigilError::module(MODULE.to_string(), format!("Unexpected {} response: {}", action, e)))
+    }
+
+    /// Regions enabled for the account.
+    pub async fn regions(&self) -> Result<Vec<String>> {
+        let response: DescribeRegionsResponse = self.call("DescribeRegions", Vec::new()).await?;
+        let mut regions: Vec<String> = response.region_info.items.into_iter().map(|r| r.region_name).collect();
+        regions.sort();
+        Ok(regions)
+    }
+
+    pub async fn instances(&self, filters: &[Filter]) -> Result<Vec<Instance>> {
+        let mut instances = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut params = filter_params(filters);
+            if let Some(token) = token.take() {
+                params.push(("NextToken".to_string(), token));
+            }
+            let page: DescribeInstancesResponse = self.call("DescribeInstances", params).await?;
+            instances.extend(
+                page.reservation_set
+                    .items
+                    .into_iter()
+                    .flat_map(|reservation| reservation.instances_set.items)
+                    .map(|raw| Instance::from_raw(&self.region, raw)),
+            );
+            match page.next_token.filter(|token| !token.is_empty()) {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+        Ok(instances)
+    }
+
+    async fn change_state(&self, action: &str, ids: &[String], extra: Vec<(String, String)>) -> Result<Vec<StateChange>> {
+        let mut params = numbered("InstanceId", ids);
+        params.extend(extra);
+        let response: StateChangeResponse = self.call(action, params).await?;
+        Ok(response
+            .instances_set
+            .items
+            .into_iter()
+            .map(|change| StateChange {
+                region: self.region.clone(),
+                instance_id: change.instance_id,
+                previous: change.previous_state.name,
+                current: change.current_state.name,
+            })
+            .collect())
+    }
+
+    pub async fn start(&self, ids: &[String]) -> Result<Vec<StateChange>> {
+        self.change_state("StartInstances", ids, Vec::new()).await
+    }
+
+    pub async fn stop(&self, ids: &[String], force: bool) -> Result<Vec<StateChange>> {
+        let extra = if force {
+            vec![("Force".to_string(), "true".to_string())]
+        } else {
+            Vec::new()
+        };
+        self.change_state("StopInstances", ids, extra).await
+    }
+
+    /// Reboot returns no state change; the request either succeeds or fails.
+    pub async fn reboot(&self, ids: &[String]) -> Result<()> {
+        let _: serde::de::IgnoredAny = self.call("RebootInstances", numbered("InstanceId", ids)).await?;
+        Ok(())
+    }
+
+    /// Snapshot a volume, tagged as sigil-managed.
+    pub async fn create_snapshot(&self, instance: &Instance, volume: &Volume, description: &str) -> Result<String> {
+        let name = format!("{} {}", instance.name.as_deref().unwrap_or(&instance.instance_id), volume.device);
+        let tags = [
+            ("Name", name.as_str()),
+            (MANAGED_TAG, "true"),
+            (INSTANCE_TAG, instance.instance_id.as_str()),
+        ];
+        let mut params = vec![
+            ("VolumeId".to_string(), volume.volume_id.clone()),
+            ("Description".to_string(), description.to_string()),
+            ("TagSpecification.1.ResourceType".to_string(), "snapshot".to_string()),
+        ];
+        for (index, (key, value)) in tags.iter().enumerate() {
+            params.push((format!("TagSpecification.1.Tag.{}.Key", index + 1), key.to_string()));
+            params.push((format!("TagSpecification.1.Tag.{}.Value", index + 1), value.to_string()));
+        }
+        let snapshot: RawSnapshot = self.call("CreateSnapshot", params).await?;
+        Ok(snapshot.snapshot_id)
+    }
+
+    /// sigil-managed snapshots of a volume, newest first.
+    async fn managed_snapshots(&self, volume_id: &str) -> Result<Vec<RawSnapshot>> {
+        let filters = [
+            Filter::new("volume-id", &[volume_id.to_string()]),
+            Filter::new(&format!("tag:{}", MANAGED_TAG), &["true".to_string()]),
+        ];
+        let mut snapshots = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut params = filter_params(&filters);
+            params.push(("Owner.1".to_string(), "self".to_string()));
+            if let Some(token) = token.take() {
+                params.push(("NextToken".to_string(), token));
+            }
+            let page: DescribeSnapshotsResponse = self.call("DescribeSnapshots", params).await?;
+            snapshots.extend(page.snapshot_set.items);
+            match page.next_token.filter(|token| !token.is_empty()) {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+        snapshots.sort_by(|a, b| b.start_time.cmp(&a.start_time));
+        Ok(snapshots)
+    }
+
+    pub async fn delete_snapshot(&self, snapshot_id: &str) -> Result<()> {
+        let _: serde::de::IgnoredAny = self
+            .call("DeleteSnapshot", vec![("SnapshotId".to_string(), snapshot_id.to_string())])
+            .await?;
+        Ok(())
+    }
+
+    /// Snapshot every EBS volume of an instance, then delete all but the
+    /// newest `retain` sigil snapshots of each volume, counting the new one.
+    pub async fn snapshot_instance(
+        &self,
+        instance: &Instance,
+        description: &str,
+        retain: Option<NonZeroUsize>,
+    ) -> Result<Vec<SnapshotRecord>> {
+        let record = |volume: &Volume, snapshot_id: String, action| SnapshotRecord {
+            region: self.region.clone(),
+            instance_id: instance.instance_id.clone(),
+            volume_id: volume.volume_id.clone(),
+            snapshot_id,
+            action,
+        };
+
+        let mut records = Vec::new();
+        for volume in &instance.volumes {
+            let created = self.create_snapshot(instance, volume, description).await?;
+            records.push(record(volume, created.clone(), "created"));
+
+            let Some(retain) = retain else {
+                continue;
+            };
+            let snapshots = self.managed_snapshots(&volume.volume_id).await?;
+            for old in snapshots.iter().filter(|s| s.snapshot_id != created).skip(retain.get() - 1) {
+                self.delete_snapshot(&old.snapshot_id).await?;
+                records.push(record(volume, old.snapshot_id.clone(), "deleted"));
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Run `task` against each region concurrently and collect the results.
+async fn fan_out<T, F, Fut>(client: &Ec2Client, regions: &[String], task: F) -> Result<Vec<T>>
+where
+    T: Send + 'static,
+    F: Fn(Ec2Client) -> Fut,
+    Fut: Future<Output = Result<Vec<T>>> + Send + 'static,
+{
+    let mut tasks = JoinSet::new();
+    for region in regions {
+        tasks.spawn(task(client.for_region(region)));
+    }
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let result = joined.map_err(|e| SigilError::module(MODULE.to_string(), format!("Region task failed: {}", e)))?;
+        results.extend(result?);
+    }
+    Ok(results)
+}
+
+async fn target_regions(client: &Ec2Client, selector: &RegionSelector) -> Result<Vec<String>> {
+    if selector.all_regions {
+        client.regions().await
+    } else if selector.regions.is_empty() {
+        Ok(vec![client.region.clone()])
+    } else {
+        Ok(selector.regions.clone())
+    }
+}
+
+/// Instances matching ids, tags and extra filters across the selected
+/// regions, sorted by region and id. Every named id must be found.
+async fn find_instances(client: &Ec2Client, selector: &InstanceSelector, extra: Vec<Filter>) -> Result<Vec<Instance>> {
+    let mut filters: Vec<Filter> = selector.tags.iter().map(|tag| Filter::tag(tag)).collect();
+    filters.extend(extra);
+    if !selector.instance_ids.is_empty() {
+        // A filter rather than InstanceId, which fails in regions without the instance.
+        filters.push(Filter::new("instance-id", &selector.instance_ids));
+    }
+
+    let regions = target_regions(client, &selector.regions).await?;
+    let mut instances = fan_out(client, &regions, move |client| {
+        let filters = filters.clone();
+        async move { client.instances(&filters).await }
+    })
+    .await?;
+    instances.sort_by(|a, b| (&a.region, &a.instance_id).cmp(&(&b.region, &b.instance_id)));
+
+    let missing: Vec<&String> = selector
+        .instance_ids
+        .iter()
+        .filter(|id| !instances.iter().any(|instance| &instance.instance_id == *id))
+        .collect();
+    if !missing.is_empty() {
+        return Err(SigilError::resource_not_found(format!(
+            "EC2 instances {} in {}",
+            missing.iter().map(|id| id.as_str()).collect::<Vec<_>>().join(", "),
+            regions.join(", ")
+        )));
+    }
+    Ok(instances)
+}
+
+/// Like [`find_instances`], for commands that change instances: an empty
+/// selection is an error rather than "everything".
+async fn select_instances(client: &Ec2Client, selector: &InstanceSelector) -> Result<Vec<Instance>> {
+    if selector.instance_ids.is_empty() && selector.tags.is_empty() {
+        return Err(SigilError::module(
+            MODULE.to_string(),
+            "Name instance ids or select them with --tag".to_string(),
+        ));
+    }
+    let instances = find_instances(client, selector, Vec::new()).await?;
+    if instances.is_empty() {
+        return Err(SigilError::resource_not_found(format!(
+            "EC2 instances tagged {}",
+            selector.tags.join(", ")
+        )));
+    }
+    Ok(instances)
+}
+
+/// Instance ids grouped by region, for per-region API calls.
+fn by_region(instances: &[Instance]) -> BTreeMap<String, Vec<String>> {
+    let mut regions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for instance in instances {
+        regions
+            .entry(instance.region.clone())
+            .or_default()
+            .push(instance.instance_id.clone());
+    }
+    regions
+}
+
+fn print_instances(instances: &[Instance]) {
+    if instances.is_empty() {
+        println!("No instances found");
+        return;
+    }
+    println!(
+        "{:<14} {:<20} {:<24} {:<10} {:<12} {:<15} {:<15}",
+        "REGION", "INSTANCE", "NAME", "STATE", "TYPE", "PRIVATE IP", "PUBLIC IP"
+    );
+    for instance in instances {
+        println!(
+            "{:<14} {:<20} {:<24} {:<10} {:<12} {:<15} {:<15}",
+            instance.region,
+            instance.instance_id,
+            instance.name.as_deref().unwrap_or("-"),
+            instance.state,
+            instance.instance_type,
+            instance.private_ip.as_deref().unwrap_or("-"),
+            instance.public_ip.as_deref().unwrap_or("-")
+        );
+    }
+}
+
+fn print_details(instance: &Instance) {
+    println!("=== {} ===", instance.label());
+    println!("Region: {}", instance.region);
+    if let Some(zone) = &instance.availability_zone {
+        println!("Availability zone: {}", zone);
+    }
+    println!("State: {}", instance.state);
+    println!("Type: {}", instance.instance_type);
+    println!("Private IP: {}", instance.private_ip.as_deref().unwrap_or("-"));
+    println!("Public IP: {}", instance.public_ip.as_deref().unwrap_or("-"));
+    if let Some(launched) = &instance.launch_time {
+        println!("Launched: {}", launched);
+    }
+    if !instance.volumes.is_empty() {
+        println!("Volumes:");
+        for volume in &instance.volumes {
+            println!("  {} {}", volume.device, volume.volume_id);
+        }
+    }
+    if !instance.tags.is_empty() {
+        println!("Tags:");
+        for (key, value) in &instance.tags {
+            println!("  {} = {}", key, value);
+        }
+    }
+    println!();
+}
+
+fn print_changes(changes: &[StateChange]) {
+    for change in changes {
+        println!(
+            "{}",
+            output::decorate(
+                "✅",
+                format!("{} ({}): {} → {}", change.instance_id, change.region, change.previous, change.current)
+            )
+        );
+    }
+}
+
+pub async fn handle_command(cmd: &Ec2Commands, config: &Config) -> Result<()> {
+    let client = Ec2Client::connect(config).await?;
+
+    match cmd {
+        Ec2Commands::List { selector, state } => {
+            let extra = state
+                .iter()
+                .map(|state| Filter::new("instance-state-name", std::slice::from_ref(state)))
+                .collect();
+            let instances = find_instances(&client, selector, extra).await?;
+            if !output::structured(&instances)? {
+                print_instances(&instances);
+            }
+        }
+        Ec2Commands::Describe { selector } => {
+            let instances = find_instances(&client, selector, Vec::new()).await?;
+            if !output::structured(&instances)? {
+                if instances.is_empty() {
+                    println!("No instances found");
+                } else {
+                    instances.iter().for_each(print_details);
+                }
+            }
+        }
+        Ec2Commands::Start { selector } => {
+            let instances = select_instances(&client, selector).await?;
+            let mut changes = Vec::new();
+            for (region, ids) in by_region(&instances) {
+                changes.extend(client.for_region(&region).start(&ids).await?);
+            }
+            if !output::structured(&changes)? {
+                print_changes(&changes);
+            }
+        }
+        Ec2Commands::Stop { selector, force } => {
+            let instances = select_instances(&client, selector).await?;
+            let mut changes = Vec::new();
+            for (region, ids) in by_region(&instances) {
+                changes.extend(client.for_region(&region).stop(&ids, *force).await?);
+            }
+            if !output::structured(&changes)? {
+                print_changes(&changes);
+            }
+        }
+        Ec2Commands::Reboot { selector } => {
+            let instances = select_instances(&client, selector).await?;
+            for (region, ids) in by_region(&instances) {
+                client.for_region(&region).reboot(&ids).await?;
+            }
+            let changes: Vec<StateChange> = instances
+                .into_iter()
+                .map(|instance| StateChange {
+                    region: instance.region,
+                    instance_id: instance.instance_id,
+                    previous: instance.state,
+                    current: "rebooting".to_string(),
+                })
+                .collect();
+            if !output::structured(&changes)? {
+                print_changes(&changes);
+            }
+        }
+        Ec2Commands::Snapshot {
+            selector,
+            retain,
+            description,
+        } => {
+            let instances = select_instances(&client, selector).await?;
+            let mut records = Vec::new();
+            for instance in &instances {
+                let description = description
+                    .clone()
+                    .unwrap_or_else(|| format!("sigil snapshot of {}", instance.instance_id));
+                let region = client.for_region(&instance.region);
+                records.extend(
+                    region
+                        .snapshot_instance(instance, &description, *retain)
+                        .await?,
+                );
+            }
+            if !output::structured(&records)? {
+                for record in &records {
+                    match record.action {
+                        "created" => println!(
+                            "{}",
+                            output::decorate(
+                                "📸",
+                                format!("{} of {} → {}", record.volume_id, record.instance_id, record.snapshot_id)
+                            )
+                        ),
+                        _ => println!(
+                            "{}",
+                            output::decorate("🗑️ ", format!("Deleted {} ({})", record.snapshot_id, record.volume_id))
+                        ),
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
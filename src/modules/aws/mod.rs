@@ -0,0 +1,186 @@
+use crate::cli::AwsCommands;
+use crate::config::{AwsConfig, Config};
+use crate::error::{Result, SigilError};
+use crate::secrets::redact;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub mod ec2;
+pub mod s3;
+pub mod sigv4;
+
+/// Access key for signing requests. Deliberately not `Debug`.
+#[derive(Clone)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+/// `[modules.aws]`, or the defaults when the section is absent so that
+/// environment credentials alone are enough.
+pub fn aws_config(config: &Config) -> AwsConfig {
+    config.modules.aws.clone().unwrap_or_default()
+}
+
+/// Region used when none is configured anywhere.
+pub const DEFAULT_REGION: &str = "us-east-1";
+
+/// `modules.aws.region`, `AWS_REGION`/`AWS_DEFAULT_REGION`, the profile's
+/// region in the shared config file, or [`DEFAULT_REGION`].
+pub fn region(aws: &AwsConfig) -> String {
+    aws.region
+        .clone()
+        .or_else(|| std::env::var("AWS_REGION").ok())
+        .or_else(|| std::env::var("AWS_DEFAULT_REGION").ok())
+        .or_else(|| profile_settings(&profile_name(aws))?.remove("region"))
+        .unwrap_or_else(|| DEFAULT_REGION.to_string())
+}
+
+/// `modules.aws.profile`, `AWS_PROFILE`, or `default`.
+fn profile_name(aws: &AwsConfig) -> String {
+    aws.profile
+        .clone()
+        .or_else(|| std::env::var("AWS_PROFILE").ok())
+        .unwrap_or_else(|| "default".to_string())
+}
+
+/// Find credentials, in order: keys in `[modules.aws]`, the configured
+/// profile, `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY`, then `AWS_PROFILE`
+/// or the `default` profile in the shared credentials and config files.
+pub async fn credentials(config: &Config, aws: &AwsConfig) -> Result<Credentials> {
+    let credentials = match (&aws.access_key_id, &aws.secret_access_key) {
+        (Some(access_key_id), Some(secret)) => Credentials {
+            access_key_id: access_key_id.clone(),
+            secret_access_key: secret.resolve(config).await?,
+            session_token: None,
+        },
+        _ => match aws.profile.is_some().then(|| profile_name(aws)) {
+            Some(profile) => profile_credentials(&profile)?.ok_or_else(|| {
+                SigilError::Authentication(format!(
+                    "Profile '{}' has no keys in {} or {}",
+                    profile,
+                    credentials_file().display(),
+                    config_file().display()
+                ))
+            })?,
+            None => match env_credentials() {
+                Some(credentials) => credentials,
+                None => profile_credentials(&profile_name(aws))?.ok_or_else(|| {
+                    SigilError::Authentication(
+                        "No AWS credentials: set modules.aws.access_key_id/secret_access_key, \
+                         AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY, or a profile in ~/.aws/credentials"
+                            .to_string(),
+                    )
+                })?,
+            },
+        },
+    };
+
+    redact::register(&credentials.secret_access_key);
+    if let Some(token) = &credentials.session_token {
+        redact::register(token);
+    }
+    Ok(credentials)
+}
+
+fn env_credentials() -> Option<Credentials> {
+    Some(Credentials {
+        access_key_id: std::env::var("AWS_ACCESS_KEY_ID").ok()?,
+        secret_access_key: std::env::var("AWS_SECRET_ACCESS_KEY").ok()?,
+        session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+    })
+}
+
+fn credentials_file() -> PathBuf {
+    std::env::var_os("AWS_SHARED_CREDENTIALS_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| aws_dir().join("credentials"))
+}
+
+fn config_file() -> PathBuf {
+    std::env::var_os("AWS_CONFIG_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| aws_dir().join("config"))
+}
+
+fn aws_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or_else(|| PathBuf::from("/tmp")).join(".aws")
+}
+
+/// Settings of a profile: `[name]` in the shared credentials file over
+/// `[profile name]` (plain `[default]`) in the shared config file. `None`
+/// if neither file has the profile.
+fn profile_settings(name: &str) -> Option<HashMap<String, String>> {
+    let config_section = match name {
+        "default" => "default".to_string(),
+        name => format!("profile {}", name),
+    };
+    let from_config = std::fs::read_to_string(config_file())
+        .ok()
+        .and_then(|content| ini_section(&content, &config_section));
+    let from_credentials = std::fs::read_to_string(credentials_file())
+        .ok()
+        .and_then(|content| ini_section(&content, name));
+
+    match (from_config, from_credentials) {
+        (None, None) => None,
+        (config, credentials) => {
+            let mut settings = config.unwrap_or_default();
+            settings.extend(credentials.unwrap_or_default());
+            Some(settings)
+        }
+    }
+}
+
+/// Keys of `profile`; `None` if no shared file has keys for it (the
+/// config file may only set its region).
+fn profile_credentials(profile: &str) -> Result<Option<Credentials>> {
+    let Some(mut settings) = profile_settings(profile) else {
+        return Ok(None);
+    };
+    let Some(access_key_id) = settings.remove("aws_access_key_id") else {
+        return Ok(None);
+    };
+    let secret_access_key = settings.remove("aws_secret_access_key").ok_or_else(|| {
+        SigilError::Authentication(format!("Profile '{}' has no aws_secret_access_key", profile))
+    })?;
+    Ok(Some(Credentials {
+        access_key_id,
+        secret_access_key,
+        session_token: settings.remove("aws_session_token"),
+    }))
+}
+
+/// Key/value pairs of one `[section]` of an INI file such as
+/// `~/.aws/credentials`.
+fn ini_section(content: &str, name: &str) -> Option<HashMap<String, String>> {
+    let mut section = None;
+    let mut current: Option<&str> = None;
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            current = Some(header.trim());
+            if current == Some(name) {
+                section.get_or_insert_with(HashMap::new);
+            }
+            continue;
+        }
+        if current != Some(name) {
+            continue;
+        }
+        if let (Some(values), Some((key, value))) = (section.as_mut(), line.split_once('=')) {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    section
+}
+
+pub async fn handle_command(cmd: &AwsCommands, config: &Config) -> Result<()> {
+    match cmd {
+        AwsCommands::S3(args) => s3::handle_command(args, config).await,
+        AwsCommands::Ec2(args) => ec2::handle_command(args, config).await,
+    }
+}
@@ -0,0 +1,863 @@
+use super::sigv4::{self, Signer, EMPTY_SHA256};
+use super::{aws_config, credentials, region, Credentials};
+use crate::cli::S3Commands;
+use crate::config::Config;
+use crate::error::{Result, SigilError};
+use crate::output::{self, say, Render, Text};
+use md5::{Digest, Md5};
+use reqwest::{Client, Method, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, info};
+
+const MODULE: &str = "s3";
+
+const MIB: u64 = 1024 * 1024;
+const TIB: u64 = 1024 * 1024 * MIB;
+
+/// Files up to this size are uploaded in a single request.
+pub const MULTIPART_THRESHOLD: u64 = 16 * MIB;
+
+/// Smallest part size of multipart uploads; see [`part_size`].
+pub const PART_SIZE: u64 = 8 * MIB;
+
+/// S3 accepts at most this many parts per upload...
+const MAX_PARTS: u64 = 10_000;
+
+/// ...and objects up to this size.
+pub const MAX_OBJECT_SIZE: u64 = 5 * TIB;
+
+/// Part size for a multipart upload of `size` bytes: [`PART_SIZE`], or for
+/// files that would need more than [`MAX_PARTS`] parts, the smallest whole
+/// number of MiB that fits. Local ETags are computed with the same size, so
+/// objects uploaded by other tools with other part sizes are treated as
+/// changed.
+pub fn part_size(size: u64) -> u64 {
+    size.div_ceil(MAX_PARTS).next_multiple_of(MIB).max(PART_SIZE)
+}
+
+/// Suffix of files being downloaded; renamed into place when complete.
+const PARTIAL_SUFFIX: &str = ".sigil-part";
+
+/// `s3://bucket/key`. An empty key or one ending in `/` is a prefix.
+#[derive(Debug, Clone)]
+pub struct S3Uri {
+    pub bucket: String,
+    pub key: String,
+}
+
+impl S3Uri {
+    pub fn parse(value: &str) -> Result<Self> {
+        let rest = value
+            .strip_prefix("s3://")
+            .ok_or_else(|| SigilError::module(MODULE.to_string(), format!("Not an s3:// URI: {}", value)))?;
+        let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(SigilError::module(MODULE.to_string(), format!("No bucket in {}", value)));
+        }
+        Ok(S3Uri {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        })
+    }
+
+    fn with_key(&self, key: &str) -> Self {
+        S3Uri {
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+        }
+    }
+
+    /// The key as a prefix: empty, or ending in `/`.
+    fn prefix(&self) -> String {
+        match self.key.trim_end_matches('/') {
+            "" => String::new(),
+            key => format!("{}/", key),
+        }
+    }
+}
+
+impl fmt::Display for S3Uri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "s3://{}/{}", self.bucket, self.key)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "PascalCase"))]
+pub struct Object {
+    pub key: String,
+    pub size: u64,
+    /// Without the surrounding quotes.
+    #[serde(rename(deserialize = "ETag"), deserialize_with = "unquote")]
+    pub etag: String,
+    #[serde(default)]
+    pub last_modified: String,
+}
+
+fn unquote<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<String, D::Error> {
+    Ok(String::deserialize(deserializer)?.trim_matches('"').to_string())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListBucketResult {
+    #[serde(default)]
+    contents: Vec<Object>,
+    #[serde(default)]
+    common_prefixes: Vec<CommonPrefix>,
+    #[serde(default)]
+    is_truncated: bool,
+    next_continuation_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CommonPrefix {
+    prefix: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InitiateMultipartUploadResult {
+    upload_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListMultipartUploadsResult {
+    #[serde(default)]
+    upload: Vec<PendingUpload>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PendingUpload {
+    key: String,
+    upload_id: String,
+    #[serde(default)]
+    initiated: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListPartsResult {
+    #[serde(default)]
+    part: Vec<UploadedPart>,
+    #[serde(default)]
+    is_truncated: bool,
+    next_part_number_marker: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct UploadedPart {
+    part_number: u64,
+    #[serde(rename = "ETag", deserialize_with = "unquote")]
+    etag: String,
+    size: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ErrorBody {
+    code: String,
+    #[serde(default)]
+    message: String,
+}
+
+/// Result of `sigil aws s3 list`: the prefixes one level down (none when
+/// recursive) and the objects.
+#[derive(Serialize)]
+struct Listing {
+    prefixes: Vec<String>,
+    objects: Vec<Object>,
+}
+
+impl Render for Listing {
+    fn render(&self, text: &mut Text) {
+        let prefixes = self
+            .prefixes
+            .iter()
+            .map(|prefix| vec![String::new(), "PRE".to_string(), prefix.clone()]);
+        let objects = self.objects.iter().map(|object| {
+            let modified = object.last_modified.get(..19).unwrap_or_default().replace('T', " ");
+            vec![modified, object.size.to_string(), object.key.clone()]
+        });
+        let rows: Vec<Vec<String>> = prefixes.chain(objects).collect();
+        text.table(&["MODIFIED", "SIZE", "KEY"], &rows);
+    }
+}
+
+/// Result of `sigil aws s3 delete`.
+#[derive(Serialize)]
+struct Deleted {
+    deleted: Vec<String>,
+    #[serde(skip)]
+    recursive: bool,
+}
+
+impl Render for Deleted {
+    fn render(&self, text: &mut Text) {
+        for uri in &self.deleted {
+            text.item("🗑️ ", format!("Deleted {}", uri));
+        }
+        if self.recursive {
+            text.item("✅", format!("{} objects deleted", self.deleted.len()));
+        }
+    }
+}
+
+/// What a transfer did, for the closing summary.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub transferred: usize,
+    pub unchanged: usize,
+    pub deleted: usize,
+}
+
+/// S3 client signing requests with SigV4. Talks to AWS, or to an
+/// S3-compatible server when `modules.aws.endpoint` is set.
+pub struct S3Client {
+    client: Client,
+    credentials: Credentials,
+    region: String,
+    endpoint: Option<String>,
+}
+
+impl S3Client {
+    pub async fn connect(config: &Config) -> Result<Self> {
+        let aws = aws_config(config);
+        let client = Client::builder()
+            .connect_timeout(Duration::from_secs(config.general.timeout_seconds))
+            .read_timeout(Duration::from_secs(config.general.timeout_seconds))
+            .build()
+            .map_err(|e| SigilError::Network(format!("Cannot create HTTP client: {}", e)))?;
+        Ok(S3Client {
+            client,
+            credentials: credentials(config, &aws).await?,
+            region: region(&aws),
+            endpoint: aws.endpoint.as_ref().map(|e| e.trim_end_matches('/').to_string()),
+        })
+    }
+
+    /// Path-style for custom endpoints and dotted bucket names (which break
+    /// TLS wildcards), virtual-hosted otherwise.
+    fn url(&self, bucket: &str, key: &str, query: &[(&str, &str)]) -> Result<Url> {
+        // Bucket-level requests go to `/<bucket>`, not `/<bucket>/`, path-style.
+        let key = match key {
+            "" => String::new(),
+            key => format!("/{}", sigv4::uri_encode(key, true)),
+        };
+        let mut url = match &self.endpoint {
+            Some(endpoint) => format!("{}/{}{}", endpoint, bucket, key),
+            None if bucket.contains('.') => format!("https://s3.{}.amazonaws.com/{}{}", self.region, bucket, key),
+            None => format!("https://{}.s3.{}.amazonaws.com/{}", bucket, self.region, key.trim_start_matches('/')),
+        };
+        if !query.is_empty() {
+            let query: Vec<String> = query
+                .iter()
+                .map(|(name, value)| format!("{}={}", sigv4::uri_encode(name, false), sigv4::uri_encode(value, false)))
+                .collect();
+            url = format!("{}?{}", url, query.join("&"));
+        }
+        Url::parse(&url).map_err(|e| SigilError::invalid_config("modules.aws.endpoint".to_string(), e.to_string()))
+    }
+
+    /// Sign and send a request. `Ok(None)` when the key does not exist.
+    async fn send(
+        &self,
+        method: Method,
+        bucket: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<Option<Response>> {
+        let url = self.url(bucket, key, query)?;
+        let payload = if body.is_empty() {
+            EMPTY_SHA256.to_string()
+        } else {
+            sigv4::sha256_hex(&body)
+        };
+        let signer = Signer {
+            credentials: &self.credentials,
+            region: &self.region,
+            service: "s3",
+        };
+        let headers = signer.sign(method.as_str(), &url, &[], &payload, chrono::Utc::now());
+
+        debug!("S3 {} {}", method, url);
+        let mut request = self.client.request(method.clone(), url.clone()).body(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| SigilError::Network(format!("S3 request failed: {}", e)))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(Some(response));
+        }
+        let bucket_region = response
+            .headers()
+            .get("x-amz-bucket-region")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let body = response.text().await.unwrap_or_default();
+        let error: Option<ErrorBody> = quick_xml::de::from_str(&body).ok();
+        let (code, message) = error.map(|e| (e.code, e.message)).unwrap_or_default();
+
+        if status == StatusCode::NOT_FOUND && code != "NoSuchBucket" {
+            return Ok(None);
+        }
+        if let Some(region) = bucket_region.filter(|region| *region != self.region) {
+            return Err(SigilError::invalid_config(
+                "modules.aws.region".to_string(),
+                format!("Bucket {} is in {}, not {}", bucket, region, self.region),
+            ));
+        }
+        let detail = match code.as_str() {
+            "" => status.to_string(),
+            _ => format!("{}: {}", code, message),
+        };
+        if status == StatusCode::FORBIDDEN {
+            return Err(SigilError::Authentication(format!("S3 denied {} {}: {}", method, url.path(), detail)));
+        }
+        Err(SigilError::module(MODULE.to_string(), format!("{} {}: {}", method, url.path(), detail)))
+    }
+
+    /// Like [`send`](Self::send), treating a missing key as an error.
+    async fn send_existing(
+        &self,
+        method: Method,
+        bucket: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<Response> {
+        self.send(method, bucket, key, query, body)
+            .await?
+            .ok_or_else(|| SigilError::resource_not_found(format!("s3://{}/{}", bucket, key)))
+    }
+
+    async fn send_xml<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        bucket: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<T> {
+        let response = self.send_existing(method, bucket, key, query, body).await?;
+        let text = response
+            .text()
+            .await
+            .map_err(|e| SigilError::Network(format!("S3 response unreadable: {}", e)))?;
+        parse_xml(&text)
+    }
+
+    /// Objects below `prefix`, and with a `delimiter` the common prefixes
+    /// ("directories") one level down.
+    pub async fn list(&self, bucket: &str, prefix: &str, delimiter: Option<&str>) -> Result<(Vec<Object>, Vec<String>)> {
+        let mut objects = Vec::new();
+        let mut prefixes = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if let Some(delimiter) = delimiter {
+                query.push(("delimiter", delimiter));
+            }
+            if let Some(token) = &token {
+                query.push(("continuation-token", token));
+            }
+            let page: ListBucketResult = self.send_xml(Method::GET, bucket, "", &query, Vec::new()).await?;
+            objects.extend(page.contents);
+            prefixes.extend(page.common_prefixes.into_iter().map(|p| p.prefix));
+            match page.next_continuation_token {
+                Some(next) if page.is_truncated => token = Some(next),
+                _ => break,
+            }
+        }
+        Ok((objects, prefixes))
+    }
+
+    pub async fn head(&self, bucket: &str, key: &str) -> Result<Option<Object>> {
+        let Some(response) = self.send(Method::HEAD, bucket, key, &[], Vec::new()).await? else {
+            return Ok(None);
+        };
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string()
+        };
+        Ok(Some(Object {
+            key: key.to_string(),
+            size: header("content-length").parse().unwrap_or(0),
+            etag: header("etag").trim_matches('"').to_string(),
+            last_modified: header("last-modified"),
+        }))
+    }
+
+    pub async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        self.send(Method::DELETE, bucket, key, &[], Vec::new()).await?;
+        Ok(())
+    }
+
+    /// Upload a file in one request or, above [`MULTIPART_THRESHOLD`], in
+    /// parts.
+    pub async fn upload_file(&self, path: &Path, bucket: &str, key: &str) -> Result<()> {
+        let size = tokio::fs::metadata(path).await?.len();
+        if size > MAX_OBJECT_SIZE {
+            return Err(SigilError::module(
+                MODULE.to_string(),
+                format!("{} is larger than the 5 TiB S3 allows for an object", path.display()),
+            ));
+        }
+        if size > MULTIPART_THRESHOLD {
+            return self.upload_multipart(path, size, bucket, key).await;
+        }
+        let body = tokio::fs::read(path).await?;
+        self.send_existing(Method::PUT, bucket, key, &[], body).await?;
+        Ok(())
+    }
+
+    /// Multipart upload that picks up an unfinished upload of the same key:
+    /// parts already on S3 with the same content are not sent again.
+    async fn upload_multipart(&self, path: &Path, size: u64, bucket: &str, key: &str) -> Result<()> {
+        let (upload_id, uploaded) = match self.pending_upload(bucket, key).await? {
+            Some(upload_id) => {
+                let parts = self.uploaded_parts(bucket, key, &upload_id).await?;
+                info!("♻️  Resuming upload of {} ({} parts already uploaded)", key, parts.len());
+                (upload_id, parts)
+            }
+            None => {
+                let created: InitiateMultipartUploadResult = self
+                    .send_xml(Method::POST, bucket, key, &[("uploads", "")], Vec::new())
+                    .await?;
+                (created.upload_id, HashMap::new())
+            }
+        };
+
+        let result = self.upload_parts(path, size, bucket, key, &upload_id, &uploaded).await;
+        let etags = result.map_err(|e| {
+            SigilError::module(
+                MODULE.to_string(),
+                format!("Upload of {} interrupted ({}); run the command again to resume", path.display(), e),
+            )
+        })?;
+
+        let parts: String = etags
+            .iter()
+            .enumerate()
+            .map(|(index, etag)| format!("<Part><PartNumber>{}</PartNumber><ETag>\"{}\"</ETag></Part>", index + 1, etag))
+            .collect();
+        let body = format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", parts);
+        let response = self
+            .send_existing(Method::POST, bucket, key, &[("uploadId", &upload_id)], body.into_bytes())
+            .await?;
+        // Completion can fail after the 200 status has been sent.
+        let text = response.text().await.unwrap_or_default();
+        if let Ok(error) = quick_xml::de::from_str::<ErrorBody>(&text) {
+            return Err(SigilError::module(
+                MODULE.to_string(),
+                format!("Completing upload of {}: {}: {}", key, error.code, error.message),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn upload_parts(
+        &self,
+        path: &Path,
+        size: u64,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        uploaded: &HashMap<u64, UploadedPart>,
+    ) -> Result<Vec<String>> {
+        let mut file = File::open(path).await?;
+        let part_size = part_size(size);
+        let count = size.div_ceil(part_size);
+        let mut etags = Vec::with_capacity(count as usize);
+
+        for number in 1..=count {
+            let part = read_chunk(&mut file, part_size).await?;
+            let md5 = hex::encode(Md5::digest(&part));
+            if uploaded
+                .get(&number)
+                .is_some_and(|done| done.etag == md5 && done.size == part.len() as u64)
+            {
+                etags.push(md5);
+                continue;
+            }
+
+            let number = number.to_string();
+            let response = self
+                .send_existing(
+                    Method::PUT,
+                    bucket,
+                    key,
+                    &[("partNumber", &number), ("uploadId", upload_id)],
+                    part,
+                )
+                .await?;
+            let etag = response
+                .headers()
+                .get("etag")
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim_matches('"').to_string())
+                .unwrap_or(md5);
+            debug!("Uploaded part {}/{} of {}", number, count, key);
+            etags.push(etag);
+        }
+        Ok(etags)
+    }
+
+    /// Most recent unfinished multipart upload of exactly `key`.
+    async fn pending_upload(&self, bucket: &str, key: &str) -> Result<Option<String>> {
+        let pending: ListMultipartUploadsResult = self
+            .send_xml(Method::GET, bucket, "", &[("uploads", ""), ("prefix", key)], Vec::new())
+            .await?;
+        Ok(pending
+            .upload
+            .into_iter()
+            .filter(|upload| upload.key == key)
+            .max_by(|a, b| a.initiated.cmp(&b.initiated))
+            .map(|upload| upload.upload_id))
+    }
+
+    async fn uploaded_parts(&self, bucket: &str, key: &str, upload_id: &str) -> Result<HashMap<u64, UploadedPart>> {
+        let mut parts = HashMap::new();
+        let mut marker = String::new();
+        loop {
+            let mut query = vec![("uploadId", upload_id)];
+            if !marker.is_empty() {
+                query.push(("part-number-marker", &marker));
+            }
+            let page: ListPartsResult = self.send_xml(Method::GET, bucket, key, &query, Vec::new()).await?;
+            parts.extend(page.part.into_iter().map(|part| (part.part_number, part)));
+            match page.next_part_number_marker {
+                Some(next) if page.is_truncated => marker = next.to_string(),
+                _ => break,
+            }
+        }
+        Ok(parts)
+    }
+
+    /// Download to `path`, via a temporary file so an interrupted download
+    /// never leaves a truncated file in place.
+    pub async fn download_file(&self, bucket: &str, key: &str, path: &Path) -> Result<()> {
+        let mut response = self.send_existing(Method::GET, bucket, key, &[], Vec::new()).await?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(PARTIAL_SUFFIX);
+        let partial = PathBuf::from(partial);
+
+        let mut file = File::create(&partial).await?;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| SigilError::Network(format!("S3 download of {} failed: {}", key, e)))?
+        {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        drop(file);
+        tokio::fs::rename(&partial, path).await?;
+        Ok(())
+    }
+
+    /// Upload a file or directory tree. Files whose content matches the
+    /// object are skipped unless `force`; with `delete`, objects under the
+    /// prefix without a local file are removed.
+    pub async fn upload(&self, source: &Path, destination: &S3Uri, force: bool, delete: bool) -> Result<Summary> {
+        let mut summary = Summary::default();
+        let (files, remote) = if source.is_file() {
+            let key = if destination.key.is_empty() || destination.key.ends_with('/') {
+                let name = source.file_name().unwrap_or_default().to_string_lossy();
+                format!("{}{}", destination.key, name)
+            } else {
+                destination.key.clone()
+            };
+            let remote: HashMap<String, Object> = self
+                .head(&destination.bucket, &key)
+                .await?
+                .map(|object| (key.clone(), object))
+                .into_iter()
+                .collect();
+            (vec![(source.to_path_buf(), key)], remote)
+        } else if source.is_dir() {
+            let prefix = destination.prefix();
+            let (objects, _) = self.list(&destination.bucket, &prefix, None).await?;
+            let remote = objects.into_iter().map(|object| (object.key.clone(), object)).collect();
+            (local_files(source, &prefix)?, remote)
+        } else {
+            return Err(SigilError::resource_not_found(source.display().to_string()));
+        };
+
+        for (path, key) in &files {
+            let target = destination.with_key(key);
+            if !force && unchanged(path, remote.get(key)).await? {
+                info!("⏭️  Unchanged: {}", target);
+                summary.unchanged += 1;
+                continue;
+            }
+            self.upload_file(path, &destination.bucket, key).await?;
+            say!("{}", output::decorate("⬆️ ", format!("{} → {}", path.display(), target)));
+            summary.transferred += 1;
+        }
+
+        if delete {
+            let local: HashSet<&String> = files.iter().map(|(_, key)| key).collect();
+            for key in remote.keys().filter(|key| !local.contains(key)) {
+                self.delete(&destination.bucket, key).await?;
+                say!("{}", output::decorate("🗑️ ", format!("Deleted {}", destination.with_key(key))));
+                summary.deleted += 1;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Download an object or everything below a prefix. Files whose
+    /// content matches the object are skipped unless `force`; with
+    /// `delete` (prefixes only), local files without an object are removed.
+    pub async fn download(&self, source: &S3Uri, destination: &Path, force: bool, delete: bool) -> Result<Summary> {
+        let single = match source.key.ends_with('/') || source.key.is_empty() {
+            true => None,
+            false => self.head(&source.bucket, &source.key).await?,
+        };
+        let plan: Vec<(Object, PathBuf)> = match single {
+            // Pruning against one object would empty the destination.
+            Some(_) if delete => {
+                return Err(SigilError::module(
+                    MODULE.to_string(),
+                    format!("{} is a single object; --delete needs a prefix such as {}/", source, source),
+                ))
+            }
+            Some(object) => {
+                let target = if destination.is_dir() || destination.to_string_lossy().ends_with('/') {
+                    let name = source.key.rsplit('/').next().unwrap_or(&source.key);
+                    destination.join(name)
+                } else {
+                    destination.to_path_buf()
+                };
+                vec![(object, target)]
+            }
+            None => {
+                let prefix = source.prefix();
+                let (objects, _) = self.list(&source.bucket, &prefix, None).await?;
+                if objects.is_empty() && !delete {
+                    return Err(SigilError::resource_not_found(source.to_string()));
+                }
+                objects
+                    .into_iter()
+                    .filter(|object| !object.key.ends_with('/'))
+                    .filter_map(|object| {
+                        let relative = safe_relative(&object.key[prefix.len()..])?;
+                        let target = destination.join(relative);
+                        Some((object, target))
+                    })
+                    .collect()
+            }
+        };
+
+        let mut summary = Summary::default();
+        for (object, target) in &plan {
+            let uri = source.with_key(&object.key);
+            if !force && unchanged(target, Some(object)).await? {
+                info!("⏭️  Unchanged: {}", target.display());
+                summary.unchanged += 1;
+                continue;
+            }
+            self.download_file(&source.bucket, &object.key, target).await?;
+            say!("{}", output::decorate("⬇️ ", format!("{} → {}", uri, target.display())));
+            summary.transferred += 1;
+        }
+
+        if delete && destination.is_dir() {
+            let wanted: HashSet<&PathBuf> = plan.iter().map(|(_, target)| target).collect();
+            for (path, _) in local_files(destination, "")? {
+                if !wanted.contains(&path) {
+                    tokio::fs::remove_file(&path).await?;
+                    say!("{}", output::decorate("🗑️ ", format!("Deleted {}", path.display())));
+                    summary.deleted += 1;
+                }
+            }
+        }
+        Ok(summary)
+    }
+}
+
+fn parse_xml<T: serde::de::DeserializeOwned>(text: &str) -> Result<T> {
+    quick_xml::de::from_str(text)
+        .map_err(|e| SigilError::module(MODULE.to_string(), format!("Unexpected S3 response: {}", e)))
+}
+
+/// Read up to `len` bytes, fewer only at the end of the file.
+async fn read_chunk(file: &mut File, len: u64) -> Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(len as usize);
+    file.take(len).read_to_end(&mut chunk).await?;
+    Ok(chunk)
+}
+
+/// The ETag S3 gives a file uploaded by [`S3Client::upload_file`]: the MD5
+/// of the content, or for multipart uploads the MD5 of the part MD5s
+/// followed by `-<parts>`.
+pub async fn local_etag(path: &Path, size: u64) -> Result<String> {
+    let mut file = File::open(path).await?;
+    if size <= MULTIPART_THRESHOLD {
+        let content = read_chunk(&mut file, size).await?;
+        return Ok(hex::encode(Md5::digest(&content)));
+    }
+    let mut digests = Md5::new();
+    let part_size = part_size(size);
+    let count = size.div_ceil(part_size);
+    for _ in 0..count {
+        digests.update(Md5::digest(read_chunk(&mut file, part_size).await?));
+    }
+    Ok(format!("{}-{}", hex::encode(digests.finalize()), count))
+}
+
+/// Whether the local file has the same size and content as the object.
+async fn unchanged(path: &Path, object: Option<&Object>) -> Result<bool> {
+    let (Some(object), Ok(metadata)) = (object, tokio::fs::metadata(path).await) else {
+        return Ok(false);
+    };
+    if !metadata.is_file() || metadata.len() != object.size {
+        return Ok(false);
+    }
+    Ok(local_etag(path, object.size).await? == object.etag)
+}
+
+/// Files below `root` with their keys (`prefix` + relative path with `/`).
+fn local_files(root: &Path, prefix: &str) -> Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(root).sort_by_file_name() {
+        let entry = entry.map_err(|e| SigilError::Io(e.into()))?;
+        let name = entry.file_name().to_string_lossy();
+        if !entry.file_type().is_file() || name.ends_with(PARTIAL_SUFFIX) {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(root).expect("walkdir yields paths below root");
+        let relative: Vec<String> = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        files.push((entry.path().to_path_buf(), format!("{}{}", prefix, relative.join("/"))));
+    }
+    Ok(files)
+}
+
+/// Relative path for a key below a prefix; `None` for keys that would
+/// escape the destination directory (`..`, absolute paths).
+fn safe_relative(key: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(key);
+    let safe = path.components().all(|component| matches!(component, Component::Normal(_)));
+    if safe && !key.is_empty() {
+        Some(path)
+    } else {
+        tracing::warn!("Skipping object key outside the destination: {}", key);
+        None
+    }
+}
+
+fn print_summary(verb: &str, summary: &Summary) {
+    let mut line = format!("{} {}, {} unchanged", summary.transferred, verb, summary.unchanged);
+    if summary.deleted > 0 {
+        line.push_str(&format!(", {} deleted", summary.deleted));
+    }
+    say!("{}", output::decorate("✅", line));
+}
+
+pub async fn handle_command(cmd: &S3Commands, config: &Config) -> Result<()> {
+    let client = S3Client::connect(config).await?;
+
+    match cmd {
+        S3Commands::Upload {
+            source,
+            destination,
+            force,
+        } => {
+            let summary = client.upload(source, &S3Uri::parse(destination)?, *force, false).await?;
+            print_summary("uploaded", &summary);
+        }
+        S3Commands::Download {
+            source,
+            destination,
+            force,
+        } => {
+            let summary = client.download(&S3Uri::parse(source)?, destination, *force, false).await?;
+            print_summary("downloaded", &summary);
+        }
+        S3Commands::Sync {
+            source,
+            destination,
+            delete,
+        } => match (source.starts_with("s3://"), destination.starts_with("s3://")) {
+            (false, true) => {
+                let summary = client
+                    .upload(Path::new(source), &S3Uri::parse(destination)?, false, *delete)
+                    .await?;
+                print_summary("uploaded", &summary);
+            }
+            (true, false) => {
+                let summary = client
+                    .download(&S3Uri::parse(source)?, Path::new(destination), false, *delete)
+                    .await?;
+                print_summary("downloaded", &summary);
+            }
+            _ => {
+                return Err(SigilError::module(
+                    MODULE.to_string(),
+                    "Sync needs one local path and one s3:// URI".to_string(),
+                ))
+            }
+        },
+        S3Commands::List { uri, recursive } => {
+            let uri = S3Uri::parse(uri)?;
+            let delimiter = (!recursive).then_some("/");
+            let (objects, prefixes) = client.list(&uri.bucket, &uri.key, delimiter).await?;
+            output::print(&Listing { prefixes, objects })?;
+        }
+        S3Commands::Delete { uri, recursive, all } => {
+            let uri = S3Uri::parse(uri)?;
+            if uri.prefix().is_empty() && !all {
+                return Err(SigilError::module(
+                    MODULE.to_string(),
+                    format!("{} names a whole bucket; pass --recursive --all to delete everything in it", uri),
+                ));
+            }
+            let mut deleted = Vec::new();
+            if *recursive {
+                let (objects, _) = client.list(&uri.bucket, &uri.prefix(), None).await?;
+                for object in &objects {
+                    client.delete(&uri.bucket, &object.key).await?;
+                    deleted.push(uri.with_key(&object.key).to_string());
+                }
+            } else {
+                if client.head(&uri.bucket, &uri.key).await?.is_none() {
+                    return Err(SigilError::resource_not_found(uri.to_string()));
+                }
+                client.delete(&uri.bucket, &uri.key).await?;
+                deleted.push(uri.to_string());
+            }
+            output::print(&Deleted {
+                deleted,
+                recursive: *recursive,
+            })?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,855 @@
+use crate::cli::{AzureCommands, ResourceGroupCommands, VmAction, VmCommands, VmSelector};
+use crate::config::Config;
+use crate::error::{Result, SigilError};
+use crate::output;
+use crate::secrets::redact;
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, LOCATION, RETRY_AFTER};
+use reqwest::{Client, Method, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio::time::sleep;
+use tracing::{debug, info};
+
+const MODULE: &str = "azure";
+
+const DEFAULT_AUTHORITY: &str = "https://login.microsoftonline.com";
+const DEFAULT_MANAGEMENT_ENDPOINT: &str = "https://management.azure.com";
+
+const RESOURCES_API_VERSION: &str = "2021-04-01";
+const COMPUTE_API_VERSION: &str = "2024-07-01";
+
+/// Cached access tokens, under `general.data_dir`.
+const TOKEN_CACHE_FILE: &str = "azure-tokens.json";
+
+/// Cached tokens are renewed this long before they expire.
+const TOKEN_EXPIRY_MARGIN_SECONDS: i64 = 300;
+
+/// Poll interval for long-running operations when Azure sends no `Retry-After`.
+const OPERATION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Upper bound on a `Retry-After` hint.
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResourceGroup {
+    pub name: String,
+    pub location: String,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub properties: ResourceGroupProperties,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceGroupProperties {
+    #[serde(default)]
+    pub provisioning_state: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VirtualMachine {
+    /// Full ARM resource id.
+    pub id: String,
+    pub name: String,
+    pub location: String,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub properties: VmProperties,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VmProperties {
+    pub hardware_profile: Option<HardwareProfile>,
+    #[serde(default)]
+    pub provisioning_state: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HardwareProfile {
+    pub vm_size: String,
+}
+
+impl VirtualMachine {
+    /// Resource group, taken from the resource id.
+    pub fn resource_group(&self) -> &str {
+        let mut segments = self.id.split('/');
+        segments
+            .by_ref()
+            .find(|segment| segment.eq_ignore_ascii_case("resourceGroups"));
+        segments.next().unwrap_or_default()
+    }
+
+    pub fn size(&self) -> &str {
+        self.properties
+            .hardware_profile
+            .as_ref()
+            .map_or("-", |profile| profile.vm_size.as_str())
+    }
+
+    /// `key=value` matches the tag's value, a bare `key` any VM carrying it.
+    pub fn has_tag(&self, spec: &str) -> bool {
+        match spec.split_once('=') {
+            Some((key, value)) => self.tags.get(key).is_some_and(|v| v == value),
+            None => self.tags.contains_key(spec),
+        }
+    }
+
+    fn label(&self) -> String {
+        format!("{} ({})", self.name, self.resource_group())
+    }
+}
+
+/// Runtime status of a VM.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceView {
+    #[serde(default)]
+    pub statuses: Vec<InstanceStatus>,
+    pub vm_agent: Option<VmAgent>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceStatus {
+    pub code: String,
+    #[serde(default)]
+    pub display_status: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VmAgent {
+    #[serde(default)]
+    pub statuses: Vec<InstanceStatus>,
+}
+
+impl InstanceView {
+    /// Value of the `<prefix>/<value>` status code, e.g. `PowerState/running`.
+    fn status(&self, prefix: &str) -> Option<&str> {
+        self.statuses
+            .iter()
+            .find_map(|status| status.code.strip_prefix(prefix)?.strip_prefix('/'))
+    }
+
+    pub fn power_state(&self) -> &str {
+        self.status("PowerState").unwrap_or("unknown")
+    }
+
+    pub fn provisioning_state(&self) -> &str {
+        self.status("ProvisioningState").unwrap_or("unknown")
+    }
+
+    /// Display status of the guest agent, if it reports one.
+    pub fn agent_status(&self) -> Option<&str> {
+        let agent = self.vm_agent.as_ref()?;
+        agent.statuses.first().map(|status| status.display_status.as_str())
+    }
+
+    /// Why the VM is not healthy, or `None` if it is provisioned, running
+    /// and its agent (if any) is ready.
+    pub fn problem(&self) -> Option<String> {
+        if !self.provisioning_state().eq_ignore_ascii_case("succeeded") {
+            return Some(format!("provisioning {}", self.provisioning_state()));
+        }
+        if self.power_state() != "running" {
+            return Some(self.power_state().to_string());
+        }
+        match self.agent_status() {
+            Some(status) if !status.eq_ignore_ascii_case("ready") => Some(format!("agent {}", status)),
+            _ => None,
+        }
+    }
+}
+
+/// Power operations on `/virtualMachines/{name}/<operation>`.
+#[derive(Debug, Clone, Copy)]
+pub enum PowerAction {
+    Start,
+    PowerOff,
+    Deallocate,
+}
+
+impl PowerAction {
+    fn operation(self) -> &'static str {
+        match self {
+            PowerAction::Start => "start",
+            PowerAction::PowerOff => "powerOff",
+            PowerAction::Deallocate => "deallocate",
+        }
+    }
+
+    fn verb(self) -> &'static str {
+        match self {
+            PowerAction::Start => "start",
+            PowerAction::PowerOff => "stop",
+            PowerAction::Deallocate => "deallocate",
+        }
+    }
+}
+
+/// How to follow an accepted long-running operation.
+#[derive(Debug)]
+pub enum Operation {
+    /// Finished synchronously.
+    Done,
+    /// `Azure-AsyncOperation`: reports `status` until it is terminal.
+    AsyncStatus(Url),
+    /// `Location`: answers 202 until the operation is over.
+    Location(Url),
+}
+
+impl Operation {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| Url::parse(value).ok())
+        };
+        match (header("azure-asyncoperation"), header(LOCATION.as_str())) {
+            (Some(url), _) => Operation::AsyncStatus(url),
+            (None, Some(url)) => Operation::Location(url),
+            (None, None) => Operation::Done,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    #[serde(default = "Vec::new")]
+    value: Vec<T>,
+    #[serde(rename = "nextLink")]
+    next_link: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedToken {
+    access_token: String,
+    /// Unix time.
+    expires_on: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: NumberOrString,
+}
+
+/// Entra ID sends `expires_in` as a number, older endpoints as a string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(i64),
+    String(String),
+}
+
+impl NumberOrString {
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            NumberOrString::Number(value) => Some(*value),
+            NumberOrString::String(value) => value.parse().ok(),
+        }
+    }
+}
+
+/// Service principal settings from `[modules.azure]`, falling back to the
+/// `AZURE_*` variables the Azure SDKs use.
+struct Settings {
+    subscription_id: String,
+    tenant_id: String,
+    client_id: String,
+    client_secret: String,
+    authority: Url,
+    management: Url,
+}
+
+impl Settings {
+    async fn load(config: &Config) -> Result<Self> {
+        let azure = config.modules.azure.clone().unwrap_or_default();
+        let setting = |value: &Option<String>, env: &str, key: &str| {
+            value
+                .clone()
+                .or_else(|| std::env::var(env).ok())
+                .ok_or_else(|| {
+                    SigilError::invalid_config(format!("modules.azure.{}", key), format!("Set it or {}", env))
+                })
+        };
+        let client_secret = match &azure.client_secret {
+            Some(secret) => secret.resolve(config).await?,
+            None => std::env::var("AZURE_CLIENT_SECRET").map_err(|_| {
+                SigilError::invalid_config(
+                    "modules.azure.client_secret".to_string(),
+                    "Set it or AZURE_CLIENT_SECRET".to_string(),
+                )
+            })?,
+        };
+        redact::register(&client_secret);
+
+        Ok(Settings {
+            subscription_id: setting(&azure.subscription_id, "AZURE_SUBSCRIPTION_ID", "subscription_id")?,
+            tenant_id: setting(&azure.tenant_id, "AZURE_TENANT_ID", "tenant_id")?,
+            client_id: setting(&azure.client_id, "AZURE_CLIENT_ID", "client_id")?,
+            client_secret,
+            authority: endpoint(azure.authority.as_deref(), DEFAULT_AUTHORITY, "authority")?,
+            management: endpoint(
+                azure.management_endpoint.as_deref(),
+                DEFAULT_MANAGEMENT_ENDPOINT,
+                "management_endpoint",
+            )?,
+        })
+    }
+
+    /// OAuth scope for the management API, e.g. `https://management.azure.com/.default`.
+    fn scope(&self) -> String {
+        format!("{}/.default", self.management.as_str().trim_end_matches('/'))
+    }
+
+    fn cache_key(&self) -> String {
+        format!("{}|{}|{}|{}", self.authority, self.tenant_id, self.client_id, self.scope())
+    }
+}
+
+fn endpoint(value: Option<&str>, default: &str, key: &str) -> Result<Url> {
+    Url::parse(value.unwrap_or(default))
+        .map_err(|e| SigilError::invalid_config(format!("modules.azure.{}", key), e.to_string()))
+}
+
+/// Client for Azure Resource Manager in one subscription.
+#[derive(Clone)]
+pub struct AzureClient {
+    client: Client,
+    settings: Arc<Settings>,
+    cache_path: PathBuf,
+    /// Shared by clones, so one refresh serves concurrent requests.
+    token: Arc<Mutex<String>>,
+}
+
+impl AzureClient {
+    /// Authenticate as the configured service principal, reusing a cached
+    /// token while it is valid.
+    pub async fn connect(config: &Config) -> Result<Self> {
+        let settings = Settings::load(config).await?;
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.general.timeout_seconds))
+            .build()
+            .map_err(|e| SigilError::Network(format!("Cannot create HTTP client: {}", e)))?;
+
+        let cache_path = config.general.data_dir.join(TOKEN_CACHE_FILE);
+        let token = match cached_token(&cache_path, &settings.cache_key()).await {
+            Some(token) => {
+                debug!("Using cached Azure token");
+                token
+            }
+            None => {
+                let token = request_token(&client, &settings).await?;
+                store_token(&cache_path, &settings.cache_key(), &token).await?;
+                token.access_token
+            }
+        };
+        redact::register(&token);
+
+        Ok(AzureClient {
+            client,
+            settings: Arc::new(settings),
+            cache_path,
+            token: Arc::new(Mutex::new(token)),
+        })
+    }
+
+    /// Replace a token ARM rejected, which can happen before it expires
+    /// (revoked credentials, a cache copied between machines). Does nothing
+    /// if a concurrent request already replaced it.
+    async fn refresh_token(&self, rejected: &str) -> Result<()> {
+        let mut token = self.token.lock().await;
+        if *token != rejected {
+            return Ok(());
+        }
+        debug!("Azure rejected the cached token; requesting a new one");
+        let fresh = request_token(&self.client, &self.settings).await?;
+        store_token(&self.cache_path, &self.settings.cache_key(), &fresh).await?;
+        redact::register(&fresh.access_token);
+        *token = fresh.access_token;
+        Ok(())
+    }
+
+    /// URL of an ARM path such as `/subscriptions/<id>/resourcegroups`,
+    /// below any path the management endpoint has.
+    fn url(&self, path: &str, api_version: &str) -> Url {
+        let mut url = self.settings.management.clone();
+        url.set_path(&format!("{}{}", url.path().trim_end_matches('/'), path));
+        url.query_pairs_mut().append_pair("api-version", api_version);
+        url
+    }
+
+    fn subscription_path(&self) -> String {
+        format!("/subscriptions/{}", self.settings.subscription_id)
+    }
+
+    async fn send_once(&self, method: &Method, url: &Url, token: &str) -> Result<Response> {
+        let mut request = self.client.request(method.clone(), url.clone()).bearer_auth(token);
+        if method != Method::GET {
+            // ARM rejects bodiless POSTs without a Content-Length.
+            request = request.header(CONTENT_LENGTH, 0);
+        }
+        request
+            .send()
+            .await
+            .map_err(|e| SigilError::Network(format!("Azure request failed: {}", e)))
+    }
+
+    /// Send a request, retrying once with a new token if ARM rejects the
+    /// current one.
+    async fn send(&self, method: Method, url: Url) -> Result<Response> {
+        debug!("Azure {} {}", method, url.path());
+        let token = self.token.lock().await.clone();
+        let mut response = self.send_once(&method, &url, &token).await?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            self.refresh_token(&token).await?;
+            let token = self.token.lock().await.clone();
+            response = self.send_once(&method, &url, &token).await?;
+        }
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        let detail = arm_error(&body).unwrap_or_else(|| body.trim().to_string());
+        Err(match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                SigilError::Authentication(format!("Azure denied access ({}): {}", status, detail))
+            }
+            StatusCode::NOT_FOUND => SigilError::resource_not_found(format!("{} ({})", url.path(), detail)),
+            _ => SigilError::module(
+                MODULE.to_string(),
+                format!("{} {} returned {}: {}", method, url.path(), status, detail),
+            ),
+        })
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: Url) -> Result<T> {
+        self.send(Method::GET, url)
+            .await?
+            .json()
+            .await
+            .map_err(|e| SigilError::Network(format!("Azure response unreadable: {}", e)))
+    }
+
+    /// Every item of a paged list, following `nextLink`.
+    async fn list<T: DeserializeOwned>(&self, url: Url) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut next = Some(url);
+        while let Some(url) = next.take() {
+            let page: Page<T> = self.get(url).await?;
+            items.extend(page.value);
+            next = page.next_link.as_deref().and_then(|link| Url::parse(link).ok());
+        }
+        Ok(items)
+    }
+
+    pub async fn resource_groups(&self) -> Result<Vec<ResourceGroup>> {
+        let path = format!("{}/resourcegroups", self.subscription_path());
+        let mut groups: Vec<ResourceGroup> = self.list(self.url(&path, RESOURCES_API_VERSION)).await?;
+        groups.sort_by_key(|group| group.name.to_lowercase());
+        Ok(groups)
+    }
+
+    /// VMs in the subscription, or in one resource group.
+    pub async fn vms(&self, resource_group: Option<&str>) -> Result<Vec<VirtualMachine>> {
+        let scope = match resource_group {
+            Some(group) => format!("{}/resourceGroups/{}", self.subscription_path(), group),
+            None => self.subscription_path(),
+        };
+        let path = format!("{}/providers/Microsoft.Compute/virtualMachines", scope);
+        let mut vms: Vec<VirtualMachine> = self.list(self.url(&path, COMPUTE_API_VERSION)).await?;
+        vms.sort_by_key(|vm| (vm.resource_group().to_lowercase(), vm.name.to_lowercase()));
+        Ok(vms)
+    }
+
+    pub async fn instance_view(&self, vm: &VirtualMachine) -> Result<InstanceView> {
+        self.get(self.url(&format!("{}/instanceView", vm.id), COMPUTE_API_VERSION))
+            .await
+    }
+
+    /// Instance views of many VMs, fetched concurrently, in the same order.
+    pub async fn instance_views(&self, vms: &[VirtualMachine]) -> Result<Vec<InstanceView>> {
+        let mut tasks = JoinSet::new();
+        for (index, vm) in vms.iter().enumerate() {
+            let client = self.clone();
+            let vm = vm.clone();
+            tasks.spawn(async move { (index, client.instance_view(&vm).await) });
+        }
+        let mut views: Vec<Option<InstanceView>> = (0..vms.len()).map(|_| None).collect();
+        while let Some(joined) = tasks.join_next().await {
+            let (index, view) =
+                joined.map_err(|e| SigilError::module(MODULE.to_string(), format!("Status request failed: {}", e)))?;
+            views[index] = Some(view?);
+        }
+        Ok(views.into_iter().map(Option::unwrap_or_default).collect())
+    }
+
+    /// Resolve a selector to VMs; every name must exist exactly once.
+    pub async fn select(&self, selector: &VmSelector) -> Result<Vec<VirtualMachine>> {
+        let vms = self.vms(selector.resource_group.as_deref()).await?;
+        if let Some(tag) = &selector.tag {
+            let tagged: Vec<VirtualMachine> = vms.into_iter().filter(|vm| vm.has_tag(tag)).collect();
+            if tagged.is_empty() {
+                return Err(SigilError::resource_not_found(format!("Azure VMs tagged '{}'", tag)));
+            }
+            return Ok(tagged);
+        }
+
+        let mut selected = Vec::with_capacity(selector.names.len());
+        for name in &selector.names {
+            let matches: Vec<&VirtualMachine> = vms.iter().filter(|vm| vm.name.eq_ignore_ascii_case(name)).collect();
+            match matches.as_slice() {
+                [] => return Err(SigilError::resource_not_found(format!("Azure VM {}", name))),
+                [vm] => selected.push((*vm).clone()),
+                _ => {
+                    return Err(SigilError::module(
+                        MODULE.to_string(),
+                        format!(
+                            "VM {} exists in resource groups {}; pass --resource-group",
+                            name,
+                            matches.iter().map(|vm| vm.resource_group()).collect::<Vec<_>>().join(", ")
+                        ),
+                    ))
+                }
+            }
+        }
+        Ok(selected)
+    }
+
+    pub async fn power(&self, vm: &VirtualMachine, action: PowerAction) -> Result<Operation> {
+        let url = self.url(&format!("{}/{}", vm.id, action.operation()), COMPUTE_API_VERSION);
+        let response = self.send(Method::POST, url).await?;
+        Ok(match response.status() {
+            StatusCode::ACCEPTED => Operation::from_headers(response.headers()),
+            _ => Operation::Done,
+        })
+    }
+
+    /// Poll a long-running operation until it ends; fails if it did not
+    /// succeed or runs longer than `timeout`.
+    pub async fn wait(&self, operation: &Operation, timeout: Duration) -> Result<()> {
+        let started = Instant::now();
+        loop {
+            let (url, interval) = match operation {
+                Operation::Done => return Ok(()),
+                Operation::AsyncStatus(url) => {
+                    let response = self.send(Method::GET, url.clone()).await?;
+                    let interval = retry_after(response.headers());
+                    let body: Value = response
+                        .json()
+                        .await
+                        .map_err(|e| SigilError::Network(format!("Azure response unreadable: {}", e)))?;
+                    match body["status"].as_str().unwrap_or("InProgress") {
+                        "Succeeded" => return Ok(()),
+                        status @ ("Failed" | "Canceled") => {
+                            let detail = arm_error(&body.to_string()).unwrap_or_else(|| status.to_string());
+                            return Err(SigilError::module(MODULE.to_string(), format!("Operation {}", detail)));
+                        }
+                        _ => (url, interval),
+                    }
+                }
+                Operation::Location(url) => {
+                    let response = self.send(Method::GET, url.clone()).await?;
+                    if response.status() != StatusCode::ACCEPTED {
+                        return Ok(());
+                    }
+                    (url, retry_after(response.headers()))
+                }
+            };
+            if started.elapsed() >= timeout {
+                return Err(SigilError::module(
+                    MODULE.to_string(),
+                    format!("Operation {} still running after {}s", url.path(), timeout.as_secs()),
+                ));
+            }
+            sleep(interval).await;
+        }
+    }
+}
+
+/// Wait suggested by `Retry-After`, capped at [`MAX_POLL_INTERVAL`].
+fn retry_after(headers: &HeaderMap) -> Duration {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(OPERATION_POLL_INTERVAL)
+        .min(MAX_POLL_INTERVAL)
+}
+
+/// `code: message` of an ARM error body (`{"error": {"code", "message"}}`).
+fn arm_error(body: &str) -> Option<String> {
+    let body: Value = serde_json::from_str(body).ok()?;
+    let error = body.get("error")?;
+    Some(format!(
+        "{}: {}",
+        error["code"].as_str().unwrap_or("Error"),
+        error["message"].as_str().unwrap_or_default()
+    ))
+}
+
+async fn cached_token(path: &Path, key: &str) -> Option<String> {
+    let content = tokio::fs::read_to_string(path).await.ok()?;
+    let mut cache: BTreeMap<String, CachedToken> = serde_json::from_str(&content).ok()?;
+    let token = cache.remove(key)?;
+    (token.expires_on - TOKEN_EXPIRY_MARGIN_SECONDS > chrono::Utc::now().timestamp()).then_some(token.access_token)
+}
+
+/// Add a token to the cache, dropping expired ones. The file holds bearer
+/// tokens, so it is written owner-only.
+async fn store_token(path: &Path, key: &str, token: &CachedToken) -> Result<()> {
+    let mut cache: BTreeMap<String, CachedToken> = match tokio::fs::read_to_string(path).await {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => BTreeMap::new(),
+    };
+    let now = chrono::Utc::now().timestamp();
+    cache.retain(|_, cached| cached.expires_on > now);
+    cache.insert(
+        key.to_string(),
+        CachedToken {
+            access_token: token.access_token.clone(),
+            expires_on: token.expires_on,
+        },
+    );
+    crate::secrets::file::write_private(path, &serde_json::to_vec_pretty(&cache)?).await
+}
+
+/// Client-credentials grant against `<authority>/<tenant>/oauth2/v2.0/token`.
+async fn request_token(client: &Client, settings: &Settings) -> Result<CachedToken> {
+    let mut url = settings.authority.clone();
+    url.path_segments_mut()
+        .map_err(|_| SigilError::invalid_config("modules.azure.authority".to_string(), "Not an http(s) URL".to_string()))?
+        .pop_if_empty()
+        .extend([settings.tenant_id.as_str(), "oauth2", "v2.0", "token"]);
+    info!("🔑 Requesting Azure token for {}", settings.client_id);
+
+    let response = client
+        .post(url)
+        .form(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", settings.client_id.as_str()),
+            ("client_secret", settings.client_secret.as_str()),
+            ("scope", settings.scope().as_str()),
+        ])
+        .send()
+        .await
+        .map_err(|e| SigilError::Network(format!("Azure token request failed: {}", e)))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| SigilError::Network(format!("Azure token response unreadable: {}", e)))?;
+    if !status.is_success() {
+        let detail = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|body| body["error_description"].as_str().or(body["error"].as_str()).map(str::to_string))
+            .unwrap_or_else(|| status.to_string());
+        return Err(SigilError::Authentication(format!(
+            "Azure rejected client {}: {}",
+            settings.client_id,
+            detail.lines().next().unwrap_or_default()
+        )));
+    }
+
+    let token: TokenResponse = serde_json::from_str(&body)?;
+    let expires_in = token.expires_in.as_i64().unwrap_or(0);
+    Ok(CachedToken {
+        access_token: token.access_token,
+        expires_on: chrono::Utc::now().timestamp() + expires_in,
+    })
+}
+
+pub async fn handle_command(cmd: &AzureCommands, config: &Config) -> Result<()> {
+    let client = AzureClient::connect(config).await?;
+    let timeout = Duration::from_secs(config.general.timeout_seconds);
+
+    match cmd {
+        AzureCommands::Rg(ResourceGroupCommands::List) => {
+            let groups = client.resource_groups().await?;
+            if output::structured(&groups)? {
+                return Ok(());
+            }
+            if groups.is_empty() {
+                println!("No resource groups found");
+                return Ok(());
+            }
+            println!("{:<32} {:<16} STATE", "NAME", "LOCATION");
+            for group in groups {
+                println!(
+                    "{:<32} {:<16} {}",
+                    group.name, group.location, group.properties.provisioning_state
+                );
+            }
+        }
+        AzureCommands::Vm(VmCommands::List {
+            resource_group,
+            location,
+        }) => {
+            let vms: Vec<VirtualMachine> = client
+                .vms(resource_group.as_deref())
+                .await?
+                .into_iter()
+                .filter(|vm| location.as_ref().is_none_or(|location| vm.location.eq_ignore_ascii_case(location)))
+                .collect();
+            if vms.is_empty() {
+                println!("No virtual machines found");
+                return Ok(());
+            }
+            let views = client.instance_views(&vms).await?;
+            if output::is_structured() {
+                let mut listed = Vec::new();
+                for (vm, view) in vms.iter().zip(&views) {
+                    let mut vm = serde_json::to_value(vm)?;
+                    vm["powerState"] = Value::from(view.power_state());
+                    listed.push(vm);
+                }
+                output::structured(&listed)?;
+                return Ok(());
+            }
+            println!(
+                "{:<24} {:<24} {:<16} {:<20} POWER",
+                "NAME", "RESOURCE GROUP", "LOCATION", "SIZE"
+            );
+            for (vm, view) in vms.iter().zip(&views) {
+                println!(
+                    "{:<24} {:<24} {:<16} {:<20} {}",
+                    vm.name,
+                    vm.resource_group(),
+                    vm.location,
+                    vm.size(),
+                    view.power_state()
+                );
+            }
+        }
+        AzureCommands::Vm(VmCommands::Start(action)) => power(&client, action, PowerAction::Start, timeout).await?,
+        AzureCommands::Vm(VmCommands::Stop(action)) => power(&client, action, PowerAction::PowerOff, timeout).await?,
+        AzureCommands::Vm(VmCommands::Deallocate(action)) => {
+            power(&client, action, PowerAction::Deallocate, timeout).await?
+        }
+        AzureCommands::Vm(VmCommands::Healthcheck {
+            resource_group,
+            location,
+            all_regions,
+        }) => {
+            let location = match (location, all_regions) {
+                (_, true) => None,
+                (Some(location), false) => Some(location.clone()),
+                (None, false) => Some(
+                    config
+                        .modules
+                        .azure
+                        .as_ref()
+                        .and_then(|azure| azure.location.clone())
+                        .ok_or_else(|| {
+                            SigilError::invalid_config(
+                                "modules.azure.location".to_string(),
+                                "Set it, or pass --location or --all-regions".to_string(),
+                            )
+                        })?,
+                ),
+            };
+            healthcheck(&client, resource_group.as_deref(), location.as_deref()).await?
+        }
+    }
+    Ok(())
+}
+
+/// Submit the action for every VM, then wait for the operations, which
+/// Azure runs concurrently. Failures are reported per VM and counted.
+async fn power(client: &AzureClient, action: &VmAction, power: PowerAction, timeout: Duration) -> Result<()> {
+    let vms = client.select(&action.target).await?;
+    let verb = power.verb();
+    let mut failed = 0;
+    let mut operations = Vec::with_capacity(vms.len());
+    for vm in &vms {
+        match client.power(vm, power).await {
+            Ok(operation) => {
+                info!("📤 {}: {}", vm.label(), verb);
+                operations.push((vm, operation));
+            }
+            Err(e) => {
+                eprintln!("❌ {}: {} failed: {}", vm.label(), verb, e);
+                failed += 1;
+            }
+        }
+    }
+
+    for (vm, operation) in operations {
+        if action.no_wait {
+            println!("📤 {}: {} accepted", vm.label(), verb);
+            continue;
+        }
+        match client.wait(&operation, timeout).await {
+            Ok(()) => println!("✅ {}: {}", vm.label(), verb),
+            Err(e) => {
+                eprintln!("❌ {}: {} failed: {}", vm.label(), verb, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(SigilError::module(
+            MODULE.to_string(),
+            format!("{} of {} VMs failed to {}", failed, vms.len(), verb),
+        ));
+    }
+    Ok(())
+}
+
+/// Report every VM in `location` (or everywhere) by region; fails if any
+/// VM is unhealthy.
+async fn healthcheck(client: &AzureClient, resource_group: Option<&str>, location: Option<&str>) -> Result<()> {
+    let mut vms: Vec<VirtualMachine> = client
+        .vms(resource_group)
+        .await?
+        .into_iter()
+        .filter(|vm| location.is_none_or(|location| vm.location.eq_ignore_ascii_case(location)))
+        .collect();
+    if vms.is_empty() {
+        println!("No virtual machines found");
+        return Ok(());
+    }
+    vms.sort_by_key(|vm| vm.location.to_lowercase());
+    let views = client.instance_views(&vms).await?;
+
+    let mut unhealthy = 0;
+    let mut region = None;
+    for (vm, view) in vms.iter().zip(&views) {
+        if region != Some(&vm.location) {
+            println!("=== {} ===", vm.location);
+            region = Some(&vm.location);
+        }
+        match view.problem() {
+            None => println!("✅ {}: healthy", vm.label()),
+            Some(problem) => {
+                println!("❌ {}: {}", vm.label(), problem);
+                unhealthy += 1;
+            }
+        }
+    }
+
+    if unhealthy > 0 {
+        return Err(SigilError::module(
+            MODULE.to_string(),
+            format!("{} of {} VMs unhealthy", unhealthy, vms.len()),
+        ));
+    }
+    println!("✅ All {} VMs healthy", vms.len());
+    Ok(())
+}
@@ -0,0 +1,749 @@
+use crate::cli::ContainerCommands;
+use crate::config::Config;
+use crate::error::{Result, SigilError};
+use crate::output;
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{CONTENT_TYPE, HOST};
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tracing::{debug, info};
+
+const MODULE: &str = "container";
+
+/// Sockets tried, in order, when none is configured.
+const DEFAULT_SOCKETS: &[&str] = &["/var/run/docker.sock", "/run/podman/podman.sock"];
+
+/// Sockets below `$XDG_RUNTIME_DIR` (rootless Podman and Docker).
+const USER_SOCKETS: &[&str] = &["podman/podman.sock", "docker.sock"];
+
+/// Header of a frame in a multiplexed (non-TTY) log stream: stream type,
+/// three zero bytes, big-endian payload length.
+const FRAME_HEADER_LEN: usize = 8;
+
+/// A row of `GET /containers/json`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerSummary {
+    pub id: String,
+    #[serde(default)]
+    pub names: Vec<String>,
+    pub image: String,
+    pub state: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub ports: Vec<Port>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Port {
+    #[serde(rename = "IP")]
+    pub ip: Option<String>,
+    #[serde(rename = "PrivatePort")]
+    pub private_port: u16,
+    #[serde(rename = "PublicPort")]
+    pub public_port: Option<u16>,
+    #[serde(rename = "Type")]
+    pub kind: String,
+}
+
+impl ContainerSummary {
+    /// First name without the leading `/`, or the short id.
+    pub fn name(&self) -> &str {
+        self.names
+            .first()
+            .map(|name| name.trim_start_matches('/'))
+            .unwrap_or_else(|| short_id(&self.id))
+    }
+
+    fn ports(&self) -> String {
+        self.ports
+            .iter()
+            .map(|port| match port.public_port {
+                Some(public) => format!(
+                    "{}:{}->{}/{}",
+                    port.ip.as_deref().unwrap_or("0.0.0.0"),
+                    public,
+                    port.private_port,
+                    port.kind
+                ),
+                None => format!("{}/{}", port.private_port, port.kind),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawStats {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    cpu_stats: CpuStats,
+    #[serde(default)]
+    precpu_stats: CpuStats,
+    #[serde(default)]
+    memory_stats: MemoryStats,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CpuStats {
+    #[serde(default)]
+    cpu_usage: CpuUsage,
+    system_cpu_usage: Option<u64>,
+    online_cpus: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CpuUsage {
+    #[serde(default)]
+    total_usage: u64,
+    percpu_usage: Option<Vec<u64>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MemoryStats {
+    usage: Option<u64>,
+    limit: Option<u64>,
+    #[serde(default)]
+    stats: HashMap<String, u64>,
+}
+
+/// CPU and memory usage of a running container.
+#[derive(Debug, Serialize)]
+pub struct ContainerStats {
+    pub name: String,
+    /// Percent of one CPU, so it can exceed 100 on multi-core hosts.
+    pub cpu_percent: f64,
+    /// Bytes, without the page cache.
+    pub memory_usage: u64,
+    pub memory_limit: u64,
+}
+
+impl ContainerStats {
+    fn from_raw(raw: RawStats) -> Self {
+        let cpu_delta = raw.cpu_stats.cpu_usage.total_usage as f64 - raw.precpu_stats.cpu_usage.total_usage as f64;
+        let system_delta = raw.cpu_stats.system_cpu_usage.unwrap_or(0) as f64
+            - raw.precpu_stats.system_cpu_usage.unwrap_or(0) as f64;
+        let cpus = raw
+            .cpu_stats
+            .online_cpus
+            .or_else(|| raw.cpu_stats.cpu_usage.percpu_usage.as_ref().map(|cpus| cpus.len() as u32))
+            .unwrap_or(1);
+        let cpu_percent = if cpu_delta > 0.0 && system_delta > 0.0 {
+            cpu_delta / system_delta * cpus as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        // Same as `docker stats`: cgroup v1 reports `cache`, v2 `inactive_file`.
+        let memory = &raw.memory_stats;
+        let cache = memory
+            .stats
+            .get("inactive_file")
+            .or_else(|| memory.stats.get("cache"))
+            .copied()
+            .unwrap_or(0);
+        ContainerStats {
+            name: raw.name.trim_start_matches('/').to_string(),
+            cpu_percent,
+            memory_usage: memory.usage.unwrap_or(0).saturating_sub(cache),
+            memory_limit: memory.limit.unwrap_or(0),
+        }
+    }
+
+    pub fn memory_percent(&self) -> f64 {
+        if self.memory_limit == 0 {
+            return 0.0;
+        }
+        self.memory_usage as f64 / self.memory_limit as f64 * 100.0
+    }
+}
+
+/// When to pull the image of a one-off container.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PullPolicy {
+    Always,
+    Missing,
+    Never,
+}
+
+/// A one-off container run as a task step, built from the task's `params`:
+///
+/// - `image` (required)
+/// - `command`: split on whitespace; use `sh -c` in the image for shell syntax
+/// - `name`, `workdir`, `user`, `network`
+/// - `volumes`: comma-separated `host:container[:ro]` binds
+/// - `pull`: `always`, `missing` (default) or `never`
+/// - `remove`: delete the container afterwards (default `true`)
+///
+/// The task's `environment` and `secrets` become the container's environment.
+#[derive(Debug)]
+pub struct RunSpec {
+    pub image: String,
+    pub name: Option<String>,
+    pub command: Option<Vec<String>>,
+    pub env: Vec<String>,
+    pub binds: Vec<String>,
+    pub network: Option<String>,
+    pub workdir: Option<String>,
+    pub user: Option<String>,
+    pub pull: PullPolicy,
+    pub remove: bool,
+}
+
+impl RunSpec {
+    pub fn from_params(params: &HashMap<String, String>, env: &HashMap<String, String>) -> Result<Self> {
+        let param = |key: &str| params.get(key).map(|value| value.trim()).filter(|value| !value.is_empty());
+        let invalid = |key: &str, reason: &str| {
+            SigilError::task_execution(format!("Container parameter '{}': {}", key, reason))
+        };
+
+        let image = param("image").ok_or_else(|| invalid("image", "required"))?.to_string();
+        let pull = match param("pull").unwrap_or("missing") {
+            "always" => PullPolicy::Always,
+            "missing" => PullPolicy::Missing,
+            "never" => PullPolicy::Never,
+            _ => return Err(invalid("pull", "must be always, missing or never")),
+        };
+        let remove = match param("remove").unwrap_or("true") {
+            "true" => true,
+            "false" => false,
+            _ => return Err(invalid("remove", "must be true or false")),
+        };
+        let mut env: Vec<String> = env.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+        env.sort();
+
+        Ok(RunSpec {
+            image,
+            name: param("name").map(str::to_string),
+            command: param("command").map(|command| command.split_whitespace().map(str::to_string).collect()),
+            env,
+            binds: param("volumes")
+                .map(|volumes| volumes.split(',').map(|bind| bind.trim().to_string()).collect())
+                .unwrap_or_default(),
+            network: param("network").map(str::to_string),
+            workdir: param("workdir").map(str::to_string),
+            user: param("user").map(str::to_string),
+            pull,
+            remove,
+        })
+    }
+}
+
+/// Client for the Docker Engine API, which Podman also serves.
+pub struct ContainerClient {
+    socket: PathBuf,
+}
+
+impl ContainerClient {
+    /// Use `modules.container.socket`, a `unix://` `DOCKER_HOST` or
+    /// `CONTAINER_HOST`, or the first default socket that exists.
+    pub fn connect(config: &Config) -> Result<Self> {
+        if let Some(socket) = config.modules.container.as_ref().and_then(|c| c.socket.clone()) {
+            return Ok(ContainerClient { socket });
+        }
+        for variable in ["DOCKER_HOST", "CONTAINER_HOST"] {
+            if let Ok(host) = std::env::var(variable) {
+                let path = host.strip_prefix("unix://").ok_or_else(|| {
+                    SigilError::invalid_config(variable.to_string(), "Only unix:// sockets are supported".to_string())
+                })?;
+                return Ok(ContainerClient {
+                    socket: PathBuf::from(path),
+                });
+            }
+        }
+
+        let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+        DEFAULT_SOCKETS
+            .iter()
+            .map(PathBuf::from)
+            .chain(
+                runtime_dir
+                    .iter()
+                    .flat_map(|dir| USER_SOCKETS.iter().map(move |socket| dir.join(socket))),
+            )
+            .find(|socket| socket.exists())
+            .map(|socket| ContainerClient { socket })
+            .ok_or_else(|| {
+                SigilError::resource_not_found(
+                    "Docker or Podman socket; set modules.container.socket or DOCKER_HOST".to_string(),
+                )
+            })
+    }
+
+    /// Send one request on a fresh connection. Error statuses become
+    /// errors; 304 (already started/stopped) is passed through.
+    async fn send(&self, method: Method, path: &str, body: Option<&Value>) -> Result<Response<Incoming>> {
+        let stream = UnixStream::connect(&self.socket)
+            .await
+            .map_err(|e| SigilError::Network(format!("Cannot connect to {}: {}", self.socket.display(), e)))?;
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(|e| SigilError::Network(format!("Container engine handshake failed: {}", e)))?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                debug!("Container engine connection closed: {}", e);
+            }
+        });
+
+        debug!("Container API {} {}", method, path);
+        let body = match body {
+            Some(body) => Full::new(Bytes::from(serde_json::to_vec(body)?)),
+            None => Full::new(Bytes::new()),
+        };
+        let request = Request::builder()
+            .method(method.clone())
+            .uri(path)
+            .header(HOST, "localhost")
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .map_err(|e| SigilError::module(MODULE.to_string(), format!("Invalid request {}: {}", path, e)))?;
+        let response = sender
+            .send_request(request)
+            .await
+            .map_err(|e| SigilError::Network(format!("Container API request failed: {}", e)))?;
+
+        let status = response.status();
+        if status.is_success() || status == StatusCode::NOT_MODIFIED {
+            return Ok(response);
+        }
+        let body = read_body(response).await?;
+        let message = serde_json::from_slice::<Value>(&body)
+            .ok()
+            .and_then(|body| body["message"].as_str().map(str::to_string))
+            .unwrap_or_else(|| String::from_utf8_lossy(&body).trim().to_string());
+        Err(match status {
+            StatusCode::NOT_FOUND => SigilError::resource_not_found(message),
+            _ => SigilError::module(MODULE.to_string(), format!("{} {} returned {}: {}", method, path, status, message)),
+        })
+    }
+
+    async fn json<T: DeserializeOwned>(&self, method: Method, path: &str, body: Option<&Value>) -> Result<T> {
+        let body = read_body(self.send(method, path, body).await?).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Send a request whose response body does not matter. Returns
+    /// `false` if the engine answered 304 (nothing to do).
+    async fn action(&self, method: Method, path: &str) -> Result<bool> {
+        let response = self.send(method, path, None).await?;
+        let changed = response.status() != StatusCode::NOT_MODIFIED;
+        read_body(response).await?;
+        Ok(changed)
+    }
+
+    pub async fn list(&self, all: bool) -> Result<Vec<ContainerSummary>> {
+        let mut containers: Vec<ContainerSummary> = self
+            .json(Method::GET, &api_path("/containers/json", &[("all", &all.to_string())]), None)
+            .await?;
+        containers.sort_by(|a, b| a.name().cmp(b.name()));
+        Ok(containers)
+    }
+
+    pub async fn inspect(&self, container: &str) -> Result<Value> {
+        self.json(Method::GET, &api_path(&format!("/containers/{}/json", container), &[]), None)
+            .await
+    }
+
+    /// Returns `false` if the container was already running.
+    pub async fn start(&self, container: &str) -> Result<bool> {
+        self.action(Method::POST, &api_path(&format!("/containers/{}/start", container), &[]))
+            .await
+    }
+
+    /// Returns `false` if the container was already stopped.
+    pub async fn stop(&self, container: &str, timeout: Option<u32>) -> Result<bool> {
+        let query = timeout.map(|t| t.to_string());
+        let query: Vec<(&str, &str)> = query.iter().map(|t| ("t", t.as_str())).collect();
+        self.action(Method::POST, &api_path(&format!("/containers/{}/stop", container), &query))
+            .await
+    }
+
+    pub async fn restart(&self, container: &str, timeout: Option<u32>) -> Result<()> {
+        let query = timeout.map(|t| t.to_string());
+        let query: Vec<(&str, &str)> = query.iter().map(|t| ("t", t.as_str())).collect();
+        self.action(Method::POST, &api_path(&format!("/containers/{}/restart", container), &query))
+            .await?;
+        Ok(())
+    }
+
+    /// One sample of CPU and memory usage.
+    pub async fn stats(&self, container: &str) -> Result<ContainerStats> {
+        let path = api_path(&format!("/containers/{}/stats", container), &[("stream", "false")]);
+        let mut stats = ContainerStats::from_raw(self.json(Method::GET, &path, None).await?);
+        if stats.name.is_empty() {
+            stats.name = container.to_string();
+        }
+        Ok(stats)
+    }
+
+    /// Copy a container's logs to `stdout`/`stderr`, until the container
+    /// stops when following.
+    pub async fn logs<O, E>(
+        &self,
+        container: &str,
+        follow: bool,
+        tail: Option<u32>,
+        timestamps: bool,
+        stdout: &mut O,
+        stderr: &mut E,
+    ) -> Result<()>
+    where
+        O: AsyncWrite + Unpin,
+        E: AsyncWrite + Unpin,
+    {
+        // A TTY container's log is raw; otherwise it is multiplexed.
+        let tty = self.inspect(container).await?["Config"]["Tty"].as_bool().unwrap_or(false);
+        let tail = tail.map_or_else(|| "all".to_string(), |n| n.to_string());
+        let path = api_path(
+            &format!("/containers/{}/logs", container),
+            &[
+                ("stdout", "true"),
+                ("stderr", "true"),
+                ("follow", &follow.to_string()),
+                ("timestamps", &timestamps.to_string()),
+                ("tail", &tail),
+            ],
+        );
+        let mut body = self.send(Method::GET, &path, None).await?.into_body();
+
+        let mut buffer: Vec<u8> = Vec::new();
+        while let Some(frame) = body.frame().await {
+            let frame = frame.map_err(|e| SigilError::Network(format!("Log stream interrupted: {}", e)))?;
+            let Some(data) = frame.data_ref() else {
+                continue;
+            };
+            if tty {
+                stdout.write_all(data).await?;
+                stdout.flush().await?;
+                continue;
+            }
+            buffer.extend_from_slice(data);
+            while buffer.len() >= FRAME_HEADER_LEN {
+                let size = u32::from_be_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]) as usize;
+                if buffer.len() < FRAME_HEADER_LEN + size {
+                    break;
+                }
+                let payload = &buffer[FRAME_HEADER_LEN..FRAME_HEADER_LEN + size];
+                if buffer[0] == 2 {
+                    stderr.write_all(payload).await?;
+                    stderr.flush().await?;
+                } else {
+                    stdout.write_all(payload).await?;
+                    stdout.flush().await?;
+                }
+                buffer.drain(..FRAME_HEADER_LEN + size);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pull an image, failing if the engine reports an error mid-stream.
+    pub async fn pull(&self, image: &str) -> Result<()> {
+        info!("⬇️  Pulling {}", image);
+        let (name, tag) = split_reference(image);
+        let response = self
+            .send(
+                Method::POST,
+                &api_path("/images/create", &[("fromImage", name), ("tag", tag)]),
+                None,
+            )
+            .await?;
+        let body = read_body(response).await?;
+        // Progress is a stream of JSON objects; errors arrive as `{"error": ...}`.
+        for line in body.split(|byte| *byte == b'\n') {
+            if let Ok(progress) = serde_json::from_slice::<Value>(line) {
+                if let Some(error) = progress["error"].as_str() {
+                    return Err(SigilError::module(
+                        MODULE.to_string(),
+                        format!("Cannot pull {}: {}", image, error),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn create(&self, spec: &RunSpec) -> Result<String> {
+        let mut body = json!({
+            "Image": spec.image,
+            "Env": spec.env,
+            "AttachStdout": true,
+            "AttachStderr": true,
+            "Tty": false,
+            "HostConfig": { "Binds": spec.binds },
+        });
+        if let Some(command) = &spec.command {
+            body["Cmd"] = json!(command);
+        }
+        if let Some(workdir) = &spec.workdir {
+            body["WorkingDir"] = json!(workdir);
+        }
+        if let Some(user) = &spec.user {
+            body["User"] = json!(user);
+        }
+        if let Some(network) = &spec.network {
+            body["HostConfig"]["NetworkMode"] = json!(network);
+        }
+        let query: Vec<(&str, &str)> = spec.name.iter().map(|name| ("name", name.as_str())).collect();
+        let created: Value = self
+            .json(Method::POST, &api_path("/containers/create", &query), Some(&body))
+            .await?;
+        created["Id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| SigilError::module(MODULE.to_string(), "Create response has no Id".to_string()))
+    }
+
+    /// Run a one-off container to completion, copying its output to
+    /// `stdout`/`stderr` as it arrives. Returns the exit code.
+    pub async fn run<O, E>(&self, spec: &RunSpec, mut stdout: O, mut stderr: E) -> Result<i64>
+    where
+        O: AsyncWrite + Unpin,
+        E: AsyncWrite + Unpin,
+    {
+        if spec.pull == PullPolicy::Always {
+            self.pull(&spec.image).await?;
+        }
+        let id = match self.create(spec).await {
+            Err(SigilError::ResourceNotFound { .. }) if spec.pull == PullPolicy::Missing => {
+                self.pull(&spec.image).await?;
+                self.create(spec).await?
+            }
+            result => result?,
+        };
+        info!("📦 Running {} as {}", spec.image, short_id(&id));
+
+        let result = async {
+            self.start(&id).await?;
+            self.logs(&id, true, None, false, &mut stdout, &mut stderr).await?;
+            let waited: Value = self
+                .json(Method::POST, &api_path(&format!("/containers/{}/wait", id), &[]), None)
+                .await?;
+            Ok(waited["StatusCode"].as_i64().unwrap_or(-1))
+        }
+        .await;
+
+        if spec.remove {
+            let path = api_path(&format!("/containers/{}", id), &[("force", "true")]);
+            if let Err(e) = self.action(Method::DELETE, &path).await {
+                debug!("Cannot remove container {}: {}", short_id(&id), e);
+            }
+        }
+        result
+    }
+}
+
+/// Split an image reference into name and tag or digest, defaulting to
+/// `latest` like the docker CLI. The engine pulls every tag of a
+/// repository when `tag` is empty.
+fn split_reference(image: &str) -> (&str, &str) {
+    if let Some((name, digest)) = image.split_once('@') {
+        // A digest wins over a tag given alongside it.
+        return (split_reference(name).0, digest);
+    }
+    // A colon before the last `/` belongs to a registry port.
+    let name_start = image.rfind('/').map_or(0, |slash| slash + 1);
+    match image[name_start..].rfind(':') {
+        Some(colon) => (&image[..name_start + colon], &image[name_start + colon + 1..]),
+        None => (image, "latest"),
+    }
+}
+
+/// Origin-form request target with an encoded query.
+fn api_path(path: &str, query: &[(&str, &str)]) -> String {
+    let mut url = Url::parse("http://localhost").expect("static URL");
+    url.set_path(path);
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(query);
+    }
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
+
+async fn read_body(response: Response<Incoming>) -> Result<Bytes> {
+    Ok(response
+        .into_body()
+        .collect()
+        .await
+        .map_err(|e| SigilError::Network(format!("Container API response unreadable: {}", e)))?
+        .to_bytes())
+}
+
+fn short_id(id: &str) -> &str {
+    &id[..id.len().min(12)]
+}
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+pub async fn handle_command(cmd: &ContainerCommands, config: &Config) -> Result<()> {
+    let client = &ContainerClient::connect(config)?;
+
+    match cmd {
+        ContainerCommands::List { all } => {
+            let containers = client.list(*all).await?;
+            if output::structured(&containers)? {
+                return Ok(());
+            }
+            if containers.is_empty() {
+                println!("No containers found");
+                return Ok(());
+            }
+            println!(
+                "{:<12}  {:<24} {:<32} {:<10} {:<24} PORTS",
+                "CONTAINER ID", "NAME", "IMAGE", "STATE", "STATUS"
+            );
+            for container in &containers {
+                println!(
+                    "{:<12}  {:<24} {:<32} {:<10} {:<24} {}",
+                    short_id(&container.id),
+                    container.name(),
+                    container.image,
+                    container.state,
+                    container.status,
+                    container.ports()
+                );
+            }
+        }
+        ContainerCommands::Inspect { container } => {
+            let inspect = client.inspect(container).await?;
+            if !output::structured(&inspect)? {
+                println!("{}", serde_json::to_string_pretty(&inspect)?);
+            }
+        }
+        ContainerCommands::Start { containers } => {
+            for_each(containers, "start", |name| async move {
+                Ok(match client.start(name).await? {
+                    true => "started",
+                    false => "already running",
+                })
+            })
+            .await?
+        }
+        ContainerCommands::Stop { containers, time } => {
+            for_each(containers, "stop", |name| async move {
+                Ok(match client.stop(name, *time).await? {
+                    true => "stopped",
+                    false => "already stopped",
+                })
+            })
+            .await?
+        }
+        ContainerCommands::Restart { containers, time } => {
+            for_each(containers, "restart", |name| async move {
+                client.restart(name, *time).await?;
+                Ok("restarted")
+            })
+            .await?
+        }
+        ContainerCommands::Logs {
+            container,
+            follow,
+            tail,
+            timestamps,
+        } => {
+            client
+                .logs(
+                    container,
+                    *follow,
+                    *tail,
+                    *timestamps,
+                    &mut tokio::io::stdout(),
+                    &mut tokio::io::stderr(),
+                )
+                .await?
+        }
+        ContainerCommands::Stats { containers } => {
+            let names: Vec<String> = if containers.is_empty() {
+                client.list(false).await?.iter().map(|c| c.name().to_string()).collect()
+            } else {
+                containers.clone()
+            };
+            if output::is_structured() {
+                let mut stats = Vec::new();
+                for name in &names {
+                    stats.push(client.stats(name).await?);
+                }
+                output::structured(&stats)?;
+                return Ok(());
+            }
+            if names.is_empty() {
+                println!("No running containers");
+                return Ok(());
+            }
+            println!("{:<24} {:>8} {:>24} {:>7}", "NAME", "CPU %", "MEM USAGE / LIMIT", "MEM %");
+            for name in &names {
+                let stats = client.stats(name).await?;
+                println!(
+                    "{:<24} {:>7.2}% {:>24} {:>6.2}%",
+                    stats.name,
+                    stats.cpu_percent,
+                    format!(
+                        "{:.1}MiB / {:.1}MiB",
+                        stats.memory_usage as f64 / MIB,
+                        stats.memory_limit as f64 / MIB
+                    ),
+                    stats.memory_percent()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Apply `operation` to each container, reporting failures per container.
+async fn for_each<'a, F, Fut>(containers: &'a [String], verb: &str, operation: F) -> Result<()>
+where
+    F: Fn(&'a str) -> Fut,
+    Fut: std::future::Future<Output = Result<&'static str>>,
+{
+    let mut failed = 0;
+    for name in containers {
+        match operation(name).await {
+            Ok(outcome) => println!("✅ {}: {}", name, outcome),
+            Err(e) => {
+                eprintln!("❌ {}: {} failed: {}", name, verb, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(SigilError::module(
+            MODULE.to_string(),
+            format!("{} of {} containers failed to {}", failed, containers.len(), verb),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_reference;
+
+    #[test]
+    fn splits_image_references() {
+        for (image, expected) in [
+            ("alpine", ("alpine", "latest")),
+            ("alpine:3.20", ("alpine", "3.20")),
+            ("library/redis:7", ("library/redis", "7")),
+            ("registry:5000/team/app", ("registry:5000/team/app", "latest")),
+            ("registry:5000/team/app:v2", ("registry:5000/team/app", "v2")),
+            ("alpine@sha256:abc", ("alpine", "sha256:abc")),
+            ("alpine:3.20@sha256:abc", ("alpine", "sha256:abc")),
+        ] {
+            assert_eq!(split_reference(image), expected, "{}", image);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+pub mod aws;
+pub mod azure;
+pub mod container;
+pub mod proxmox;
 pub mod system;
-
-pub use system::*;
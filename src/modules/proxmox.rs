@@ -0,0 +1,631 @@
+use crate::cli::{GuestAction, GuestSelector, ProxmoxCommands, SnapshotArgs, SnapshotCommands};
+use crate::config::{Config, ProxmoxConfig};
+use crate::error::{Result, SigilError};
+use crate::output;
+use reqwest::{Client, Method, RequestBuilder, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+const MODULE: &str = "proxmox";
+
+/// How often a running task's status is checked.
+const TASK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Task log lines shown when a task fails.
+const TASK_LOG_TAIL: usize = 5;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Node {
+    pub node: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub cpu: f64,
+    #[serde(default)]
+    pub maxcpu: u32,
+    #[serde(default)]
+    pub mem: u64,
+    #[serde(default)]
+    pub maxmem: u64,
+    #[serde(default)]
+    pub uptime: u64,
+}
+
+/// A VM or container, as listed in `/cluster/resources`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Guest {
+    pub vmid: u32,
+    #[serde(default)]
+    pub name: String,
+    pub node: String,
+    /// `qemu` or `lxc`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub status: String,
+    /// Semicolon-separated, as Proxmox stores them.
+    #[serde(default)]
+    pub tags: String,
+}
+
+impl Guest {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tag_names().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    pub fn tag_names(&self) -> impl Iterator<Item = &str> {
+        self.tags.split([';', ',', ' ']).filter(|t| !t.is_empty())
+    }
+
+    /// API path of this guest below `/nodes`.
+    fn path(&self) -> Vec<String> {
+        vec![
+            "nodes".to_string(),
+            self.node.clone(),
+            self.kind.clone(),
+            self.vmid.to_string(),
+        ]
+    }
+
+    fn label(&self) -> String {
+        match self.name.as_str() {
+            "" => self.vmid.to_string(),
+            name => format!("{} ({})", self.vmid, name),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub snaptime: Option<i64>,
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub vmstate: u8,
+}
+
+/// Power actions under `/status`.
+#[derive(Debug, Clone, Copy)]
+pub enum PowerAction {
+    Start,
+    Stop,
+    Shutdown,
+    Reboot,
+}
+
+impl PowerAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            PowerAction::Start => "start",
+            PowerAction::Stop => "stop",
+            PowerAction::Shutdown => "shutdown",
+            PowerAction::Reboot => "reboot",
+        }
+    }
+}
+
+enum Auth {
+    /// `PVEAPIToken=user@realm!tokenid=secret`
+    Token(String),
+    /// Ticket from `/access/ticket`; writes also need the CSRF token.
+    Ticket { ticket: String, csrf: String },
+}
+
+/// Client for the Proxmox VE HTTP API (`/api2/json`).
+pub struct ProxmoxClient {
+    client: Client,
+    base: Url,
+    auth: Auth,
+}
+
+impl ProxmoxClient {
+    /// Connect with `[modules.proxmox]`, preferring an API token over
+    /// username/password ticket auth.
+    pub async fn connect(config: &Config) -> Result<Self> {
+        let proxmox = config
+            .modules
+            .proxmox
+            .as_ref()
+            .ok_or_else(|| SigilError::invalid_config("modules.proxmox", "Proxmox is not configured"))?;
+
+        let client = Client::builder()
+            .danger_accept_invalid_certs(!proxmox.verify_ssl)
+            .timeout(Duration::from_secs(config.general.timeout_seconds))
+            .build()
+            .map_err(|e| SigilError::Network(format!("Cannot create HTTP client: {}", e)))?;
+        let base = api_base(&proxmox.endpoint)?;
+
+        let auth = match (&proxmox.token_id, &proxmox.token_secret) {
+            (Some(token_id), Some(secret)) => Auth::Token(format!(
+                "PVEAPIToken={}={}",
+                token_name(proxmox, token_id),
+                secret.resolve(config).await?
+            )),
+            _ => {
+                let password = proxmox.password.as_ref().ok_or_else(|| {
+                    SigilError::Authentication(
+                        "Set modules.proxmox.password or token_id/token_secret".to_string(),
+                    )
+                })?;
+                login(&client, &base, &proxmox.username, &password.resolve(config).await?).await?
+            }
+        };
+
+        Ok(ProxmoxClient { client, base, auth })
+    }
+
+    fn url<S: AsRef<str>>(&self, path: &[S]) -> Url {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("endpoint is an http(s) URL")
+            .extend(path.iter().map(AsRef::as_ref));
+        url
+    }
+
+    fn authorize(&self, method: &Method, request: RequestBuilder) -> RequestBuilder {
+        match &self.auth {
+            Auth::Token(token) => request.header("Authorization", token),
+            Auth::Ticket { ticket, csrf } => {
+                let request = request.header("Cookie", format!("PVEAuthCookie={}", ticket));
+                if *method == Method::GET {
+                    request
+                } else {
+                    request.header("CSRFPreventionToken", csrf)
+                }
+            }
+        }
+    }
+
+    /// Call the API and return the response's `data`. Parameters are sent
+    /// as a query for GET and as a form otherwise.
+    async fn call<S: AsRef<str>>(&self, method: Method, path: &[S], params: &[(&str, String)]) -> Result<Value> {
+        let url = self.url(path);
+        debug!("Proxmox {} {}", method, url.path());
+        let request = self.client.request(method.clone(), url.clone());
+        let request = if method == Method::GET {
+            request.query(params)
+        } else {
+            request.form(params)
+        };
+        let response = self
+            .authorize(&method, request)
+            .send()
+            .await
+            .map_err(|e| SigilError::Network(format!("Proxmox request failed: {}", e)))?;
+
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| SigilError::Network(format!("Proxmox response unreadable: {}", e)))?;
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            return Err(SigilError::Authentication(format!("Proxmox denied access ({})", status)));
+        }
+        if !status.is_success() {
+            return Err(SigilError::module(
+                MODULE.to_string(),
+                format!("{} {} returned {}: {}", method, url.path(), status, body.trim()),
+            ));
+        }
+        let body: Value = serde_json::from_str(&body)?;
+        Ok(body["data"].clone())
+    }
+
+    async fn get<T: DeserializeOwned, S: AsRef<str>>(&self, path: &[S], params: &[(&str, String)]) -> Result<T> {
+        Ok(serde_json::from_value(self.call(Method::GET, path, params).await?)?)
+    }
+
+    /// Submit an asynchronous operation and return its task id.
+    async fn submit<S: AsRef<str>>(&self, method: Method, path: &[S], params: &[(&str, String)]) -> Result<String> {
+        match self.call(method, path, params).await? {
+            Value::String(upid) => Ok(upid),
+            other => Err(SigilError::module(
+                MODULE.to_string(),
+                format!("Expected a task id, got {}", other),
+            )),
+        }
+    }
+
+    pub async fn nodes(&self) -> Result<Vec<Node>> {
+        let mut nodes: Vec<Node> = self.get(&["nodes"], &[]).await?;
+        nodes.sort_by(|a, b| a.node.cmp(&b.node));
+        Ok(nodes)
+    }
+
+    /// All VMs and containers in the cluster.
+    pub async fn guests(&self) -> Result<Vec<Guest>> {
+        let mut guests: Vec<Guest> = self
+            .get(&["cluster", "resources"], &[("type", "vm".to_string())])
+            .await?;
+        guests.sort_by_key(|guest| guest.vmid);
+        Ok(guests)
+    }
+
+    /// Resolve a selector to guests; every id must exist.
+    pub async fn select(&self, selector: &GuestSelector) -> Result<Vec<Guest>> {
+        let guests = self.guests().await?;
+        if let Some(tag) = &selector.tag {
+            let tagged: Vec<Guest> = guests.into_iter().filter(|guest| guest.has_tag(tag)).collect();
+            if tagged.is_empty() {
+                return Err(SigilError::resource_not_found(format!("Proxmox guests tagged '{}'", tag)));
+            }
+            return Ok(tagged);
+        }
+
+        let mut guests = guests;
+        let mut selected = Vec::with_capacity(selector.vmids.len());
+        for vmid in &selector.vmids {
+            let index = guests
+                .iter()
+                .position(|guest| guest.vmid == *vmid)
+                .ok_or_else(|| SigilError::resource_not_found(format!("Proxmox guest {}", vmid)))?;
+            selected.push(guests.swap_remove(index));
+        }
+        Ok(selected)
+    }
+
+    pub async fn power(&self, guest: &Guest, action: PowerAction) -> Result<String> {
+        let mut path = guest.path();
+        path.extend(["status".to_string(), action.as_str().to_string()]);
+        self.submit(Method::POST, &path, &[]).await
+    }
+
+    /// Snapshots of a guest, oldest first, without the `current` pseudo-entry.
+    pub async fn snapshots(&self, guest: &Guest) -> Result<Vec<Snapshot>> {
+        let mut path = guest.path();
+        path.push("snapshot".to_string());
+        let mut snapshots: Vec<Snapshot> = self.get(&path, &[]).await?;
+        snapshots.retain(|snapshot| snapshot.name != "current");
+        snapshots.sort_by_key(|snapshot| snapshot.snaptime);
+        Ok(snapshots)
+    }
+
+    pub async fn create_snapshot(
+        &self,
+        guest: &Guest,
+        name: &str,
+        description: Option<&str>,
+        vmstate: bool,
+    ) -> Result<String> {
+        let mut path = guest.path();
+        path.push("snapshot".to_string());
+        let mut params = vec![("snapname", name.to_string())];
+        if let Some(description) = description {
+            params.push(("description", description.to_string()));
+        }
+        // Containers have no memory state to save.
+        if vmstate && guest.kind == "qemu" {
+            params.push(("vmstate", "1".to_string()));
+        }
+        self.submit(Method::POST, &path, &params).await
+    }
+
+    pub async fn rollback_snapshot(&self, guest: &Guest, name: &str) -> Result<String> {
+        let mut path = guest.path();
+        path.extend(["snapshot".to_string(), name.to_string(), "rollback".to_string()]);
+        self.submit(Method::POST, &path, &[]).await
+    }
+
+    pub async fn delete_snapshot(&self, guest: &Guest, name: &str) -> Result<String> {
+        let mut path = guest.path();
+        path.extend(["snapshot".to_string(), name.to_string()]);
+        self.submit(Method::DELETE, &path, &[]).await
+    }
+
+    /// Poll a task until it stops; fails if it did not end with `OK` or
+    /// `WARNINGS: <n>` (reported as a warning), or runs longer than `timeout`.
+    pub async fn wait_task(&self, upid: &str, timeout: Duration) -> Result<()> {
+        let node = upid_node(upid)?;
+        let path = ["nodes", node, "tasks", upid, "status"];
+        let started = Instant::now();
+
+        loop {
+            let status = self.call(Method::GET, &path, &[]).await?;
+            if status["status"].as_str() == Some("stopped") {
+                let exit = status["exitstatus"].as_str().unwrap_or("unknown");
+                if exit == "OK" {
+                    return Ok(());
+                }
+                let log = self.task_log(node, upid).await.unwrap_or_default();
+                // Succeeded, but e.g. vzdump could not freeze the guest.
+                if let Some(count) = exit.strip_prefix("WARNINGS:") {
+                    warn!("⚠️  Task {} finished with {} warning(s){}", upid, count.trim(), log);
+                    return Ok(());
+                }
+                return Err(SigilError::module(
+                    MODULE.to_string(),
+                    format!("Task {} failed: {}{}", upid, exit, log),
+                ));
+            }
+            if started.elapsed() >= timeout {
+                return Err(SigilError::module(
+                    MODULE.to_string(),
+                    format!("Task {} still running after {}s", upid, timeout.as_secs()),
+                ));
+            }
+            sleep(TASK_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Last lines of a task's log, for error messages.
+    async fn task_log(&self, node: &str, upid: &str) -> Result<String> {
+        let lines: Vec<Value> = self.get(&["nodes", node, "tasks", upid, "log"], &[]).await?;
+        let tail: Vec<&str> = lines.iter().filter_map(|line| line["t"].as_str()).collect();
+        let tail = &tail[tail.len().saturating_sub(TASK_LOG_TAIL)..];
+        Ok(tail.iter().map(|line| format!("\n  {}", line)).collect())
+    }
+}
+
+/// `https://host:8006` → `https://host:8006/api2/json`
+fn api_base(endpoint: &str) -> Result<Url> {
+    let endpoint = endpoint.trim_end_matches('/');
+    let endpoint = if endpoint.ends_with("/api2/json") {
+        endpoint.to_string()
+    } else {
+        format!("{}/api2/json", endpoint)
+    };
+    Url::parse(&endpoint).map_err(|e| SigilError::invalid_config("modules.proxmox.endpoint".to_string(), e.to_string()))
+}
+
+/// `token_id` may be the full `user@realm!name` or just `name`.
+fn token_name(proxmox: &ProxmoxConfig, token_id: &str) -> String {
+    if token_id.contains('!') {
+        token_id.to_string()
+    } else {
+        format!("{}!{}", proxmox.username, token_id)
+    }
+}
+
+async fn login(client: &Client, base: &Url, username: &str, password: &str) -> Result<Auth> {
+    let mut url = base.clone();
+    url.path_segments_mut()
+        .expect("endpoint is an http(s) URL")
+        .extend(["access", "ticket"]);
+    let response = client
+        .post(url)
+        .form(&[("username", username), ("password", password)])
+        .send()
+        .await
+        .map_err(|e| SigilError::Network(format!("Proxmox login failed: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(SigilError::Authentication(format!(
+            "Proxmox rejected {} ({})",
+            username,
+            response.status()
+        )));
+    }
+
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| SigilError::Network(format!("Proxmox login response unreadable: {}", e)))?;
+    let field = |name: &str| {
+        body["data"][name]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| SigilError::Authentication(format!("Proxmox login response has no {}", name)))
+    };
+    let ticket = field("ticket")?;
+    crate::secrets::redact::register(&ticket);
+    Ok(Auth::Ticket {
+        ticket,
+        csrf: field("CSRFPreventionToken")?,
+    })
+}
+
+/// Node a task runs on: `UPID:<node>:<pid>:...`.
+fn upid_node(upid: &str) -> Result<&str> {
+    match upid.split(':').collect::<Vec<_>>().as_slice() {
+        ["UPID", node, ..] if !node.is_empty() => Ok(node),
+        _ => Err(SigilError::module(MODULE.to_string(), format!("Not a task id: {}", upid))),
+    }
+}
+
+pub async fn handle_command(cmd: &ProxmoxCommands, config: &Config) -> Result<()> {
+    let client = ProxmoxClient::connect(config).await?;
+    let timeout = Duration::from_secs(config.general.timeout_seconds);
+
+    match cmd {
+        ProxmoxCommands::Nodes => {
+            let nodes = client.nodes().await?;
+            if output::structured(&nodes)? {
+                return Ok(());
+            }
+            println!("{:<16} {:<8} {:>6} {:>16} {:>10}", "NODE", "STATUS", "CPU", "MEMORY", "UPTIME");
+            for node in nodes {
+                println!(
+                    "{:<16} {:<8} {:>5.1}% {:>16} {:>10}",
+                    node.node,
+                    node.status,
+                    node.cpu * 100.0,
+                    format!("{}/{} GB", node.mem / GIB, node.maxmem / GIB),
+                    format_uptime(node.uptime)
+                );
+            }
+        }
+        ProxmoxCommands::List { node, tag } => {
+            let guests: Vec<Guest> = client
+                .guests()
+                .await?
+                .into_iter()
+                .filter(|guest| node.as_ref().is_none_or(|node| &guest.node == node))
+                .filter(|guest| tag.as_ref().is_none_or(|tag| guest.has_tag(tag)))
+                .collect();
+            if output::structured(&guests)? {
+                return Ok(());
+            }
+            if guests.is_empty() {
+                println!("No guests found");
+                return Ok(());
+            }
+            println!("{:>6} {:<24} {:<5} {:<16} {:<8} TAGS", "VMID", "NAME", "TYPE", "NODE", "STATUS");
+            for guest in guests {
+                println!(
+                    "{:>6} {:<24} {:<5} {:<16} {:<8} {}",
+                    guest.vmid,
+                    guest.name,
+                    guest.kind,
+                    guest.node,
+                    guest.status,
+                    guest.tags.replace(';', ",")
+                );
+            }
+        }
+        ProxmoxCommands::Start(action) => power(&client, action, PowerAction::Start, timeout).await?,
+        ProxmoxCommands::Stop(action) => power(&client, action, PowerAction::Stop, timeout).await?,
+        ProxmoxCommands::Shutdown(action) => power(&client, action, PowerAction::Shutdown, timeout).await?,
+        ProxmoxCommands::Reboot(action) => power(&client, action, PowerAction::Reboot, timeout).await?,
+        ProxmoxCommands::Snapshot(args) => snapshot(&client, args, timeout).await?,
+        ProxmoxCommands::Task { upid } => {
+            info!("⏳ Waiting for {}", upid);
+            client.wait_task(upid, timeout).await?;
+            println!("✅ {} finished", upid);
+        }
+    }
+    Ok(())
+}
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+fn format_uptime(seconds: u64) -> String {
+    let days = seconds / 86_400;
+    let hours = seconds % 86_400 / 3_600;
+    let minutes = seconds % 3_600 / 60;
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else {
+        format!("{}h {}m", hours, minutes)
+    }
+}
+
+async fn power(client: &ProxmoxClient, action: &GuestAction, power: PowerAction, timeout: Duration) -> Result<()> {
+    let guests = client.select(&action.target).await?;
+    run_on_guests(client, &guests, action.no_wait, timeout, power.as_str(), |guest| {
+        client.power(guest, power)
+    })
+    .await
+}
+
+async fn snapshot(client: &ProxmoxClient, args: &SnapshotArgs, timeout: Duration) -> Result<()> {
+    match &args.command {
+        None => {
+            let name = args
+                .name
+                .clone()
+                .unwrap_or_else(|| chrono::Utc::now().format("sigil-%Y%m%d-%H%M%S").to_string());
+            let guests = client.select(&args.action.target).await?;
+            let operation = format!("snapshot {}", name);
+            run_on_guests(client, &guests, args.action.no_wait, timeout, &operation, |guest| {
+                client.create_snapshot(guest, &name, args.description.as_deref(), args.vmstate)
+            })
+            .await
+        }
+        Some(SnapshotCommands::List(selector)) => {
+            let guests = client.select(selector).await?;
+            if output::is_structured() {
+                let mut listed = Vec::new();
+                for guest in &guests {
+                    listed.push(serde_json::json!({
+                        "vmid": guest.vmid,
+                        "name": guest.name,
+                        "node": guest.node,
+                        "snapshots": client.snapshots(guest).await?,
+                    }));
+                }
+                output::structured(&listed)?;
+                return Ok(());
+            }
+            for guest in guests {
+                println!("=== {} on {} ===", guest.label(), guest.node);
+                let snapshots = client.snapshots(&guest).await?;
+                if snapshots.is_empty() {
+                    println!("  (no snapshots)");
+                }
+                for snapshot in snapshots {
+                    let taken = snapshot
+                        .snaptime
+                        .and_then(|time| chrono::DateTime::from_timestamp(time, 0))
+                        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_default();
+                    let memory = if snapshot.vmstate == 1 { " [RAM]" } else { "" };
+                    println!("  {:<32} {:<19}{} {}", snapshot.name, taken, memory, snapshot.description.trim());
+                }
+            }
+            Ok(())
+        }
+        Some(SnapshotCommands::Rollback { action, name }) => {
+            let guests = client.select(&action.target).await?;
+            let operation = format!("rollback to {}", name);
+            run_on_guests(client, &guests, action.no_wait, timeout, &operation, |guest| {
+                client.rollback_snapshot(guest, name)
+            })
+            .await
+        }
+        Some(SnapshotCommands::Delete { action, name }) => {
+            let guests = client.select(&action.target).await?;
+            let operation = format!("delete snapshot {}", name);
+            run_on_guests(client, &guests, action.no_wait, timeout, &operation, |guest| {
+                client.delete_snapshot(guest, name)
+            })
+            .await
+        }
+    }
+}
+
+/// Submit `submit` for every guest, then wait for the tasks, which Proxmox
+/// runs concurrently. Failures are reported per guest and counted.
+async fn run_on_guests<'a, F, Fut>(
+    client: &ProxmoxClient,
+    guests: &'a [Guest],
+    no_wait: bool,
+    timeout: Duration,
+    operation: &str,
+    submit: F,
+) -> Result<()>
+where
+    F: Fn(&'a Guest) -> Fut,
+    Fut: std::future::Future<Output = Result<String>>,
+{
+    let mut failed = 0;
+    let mut tasks = Vec::with_capacity(guests.len());
+    for guest in guests {
+        match submit(guest).await {
+            Ok(upid) => {
+                info!("📤 {}: {} ({})", guest.label(), operation, upid);
+                tasks.push((guest, upid));
+            }
+            Err(e) => {
+                eprintln!("❌ {}: {} failed: {}", guest.label(), operation, e);
+                failed += 1;
+            }
+        }
+    }
+
+    for (guest, upid) in tasks {
+        if no_wait {
+            println!("📤 {}: {} ({})", guest.label(), operation, upid);
+            continue;
+        }
+        match client.wait_task(&upid, timeout).await {
+            Ok(()) => println!("✅ {}: {}", guest.label(), operation),
+            Err(e) => {
+                eprintln!("❌ {}: {} failed: {}", guest.label(), operation, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(SigilError::module(
+            MODULE.to_string(),
+            format!("{} of {} guests failed to {}", failed, guests.len(), operation),
+        ));
+    }
+    Ok(())
+}
@@ -1,11 +1,18 @@
+use crate::audit;
 use crate::cli::SystemCommands;
 use crate::config::Config;
 use crate::error::{Result, SigilError};
+use crate::inventory::{Host, Inventory};
+use crate::modules::container::ContainerClient;
+use crate::output::{self, Render, Text};
+use crate::runtime::ssh::{self, RemoteCommand, SshRunner};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::process::Command;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SystemInfo {
@@ -52,25 +59,100 @@ pub struct ServiceStatus {
     pub cpu_usage: Option<f64>,
 }
 
+const GIB: u64 = 1024 * 1024 * 1024;
+
+impl Render for SystemInfo {
+    fn render(&self, text: &mut Text) {
+        text.heading("🖥️ ", "System Status");
+        text.field("Hostname", &self.hostname);
+        text.field("Uptime", &self.uptime);
+        text.field("Load Average", &self.load_average);
+        text.field(
+            "Memory",
+            format!(
+                "{:.1}% used ({} GB / {} GB)",
+                self.memory_info.usage_percent,
+                self.memory_info.used / GIB,
+                self.memory_info.total / GIB
+            ),
+        );
+        text.field("CPU", format!("{:.1}% usage ({} cores)", self.cpu_info.usage_percent, self.cpu_info.cores));
+        if let Some(temperature) = self.cpu_info.temperature {
+            text.field("Temperature", format!("{:.1}°C", temperature));
+        }
+        text.blank();
+        let rows: Vec<Vec<String>> = self
+            .disk_usage
+            .iter()
+            .map(|disk| {
+                vec![
+                    disk.mount_point.clone(),
+                    disk.size.clone(),
+                    disk.used.clone(),
+                    disk.available.clone(),
+                    disk.usage_percent.clone(),
+                    disk.filesystem.clone(),
+                ]
+            })
+            .collect();
+        text.table(&["MOUNT", "SIZE", "USED", "AVAIL", "USE%", "FILESYSTEM"], &rows);
+    }
+}
+
+impl Render for ServiceStatus {
+    fn render(&self, text: &mut Text) {
+        let yes_no = |value: bool| if value { output::decorate("✅", "Yes") } else { output::decorate("❌", "No") };
+        text.heading("🔍", format!("Service Status: {}", self.name));
+        text.field("Active", yes_no(self.active));
+        text.field("Enabled", yes_no(self.enabled));
+        text.field("Status", &self.status);
+        if let Some(cpu_usage) = self.cpu_usage {
+            text.field("CPU Usage", format!("{:.1}%", cpu_usage));
+        }
+        if let Some(memory_usage) = self.memory_usage {
+            text.field("Memory Usage", format!("{} MB", memory_usage / 1024 / 1024));
+        }
+    }
+}
+
+/// Print one monitor sample: a record of the NDJSON or YAML stream, or the
+/// text form followed by a blank line.
+fn print_sample<T: Render>(sample: &T) -> Result<()> {
+    if !output::stream(sample)? {
+        output::print(sample)?;
+        println!();
+    }
+    Ok(())
+}
+
 pub async fn handle_command(cmd: &SystemCommands, config: &Config) -> Result<()> {
     match cmd {
         SystemCommands::Monitor { 
             service, 
+            container,
             restart_if_high_cpu, 
             cpu_threshold 
         } => {
             if let Some(service_name) = service {
-                monitor_service(service_name, *restart_if_high_cpu, *cpu_threshold).await?;
+                let container = if *container { Some(ContainerClient::connect(config)?) } else { None };
+                monitor_service(service_name, container.as_ref(), *restart_if_high_cpu, *cpu_threshold, config).await?;
             } else {
                 monitor_system(config).await?;
             }
         }
-        SystemCommands::Exec { command, args } => {
-            execute_command(command, args).await?;
+        SystemCommands::Exec { command, args, hosts, targets, parallel } => {
+            let hosts = match targets {
+                Some(expression) => Inventory::load(config).await?.targets(expression)?,
+                None => hosts.iter().map(|host| Host::named(host)).collect(),
+            };
+            if hosts.is_empty() {
+                execute_command(command, args).await?;
+            } else {
+                execute_remote(command, args, &hosts, parallel.map(|n| n as usize), config).await?;
+            }
         }
         SystemCommands::Info => {
-            let info = get_system_info().await?;
-            println!("{}", serde_json::to_string_pretty(&info)?);
+            output::print(&get_system_info().await?)?;
         }
     }
     Ok(())
@@ -81,16 +163,7 @@ pub async fn monitor_system(config: &Config) -> Result<()> {
     
     loop {
         let info = get_system_info().await?;
-        
-        println!("=== System Status ===");
-        println!("Hostname: {}", info.hostname);
-        println!("Uptime: {}", info.uptime);
-        println!("Load Average: {}", info.load_average);
-        println!("Memory: {:.1}% used ({} GB / {} GB)", 
-                 info.memory_info.usage_percent,
-                 info.memory_info.used / 1024 / 1024 / 1024,
-                 info.memory_info.total / 1024 / 1024 / 1024);
-        println!("CPU: {:.1}% usage", info.cpu_info.usage_percent);
+        print_sample(&info)?;
         
         if info.cpu_info.usage_percent > config.modules.system.default_cpu_threshold as f64 {
             warn!("⚠️  High CPU usage detected: {:.1}%", info.cpu_info.usage_percent);
@@ -100,42 +173,47 @@ pub async fn monitor_system(config: &Config) -> Result<()> {
             warn!("⚠️  High memory usage detected: {:.1}%", info.memory_info.usage_percent);
         }
         
-        println!("--- Disk Usage ---");
-        for disk in &info.disk_usage {
-            println!("{}: {} ({}% used)", disk.mount_point, disk.size, disk.usage_percent);
-        }
-        
-        println!();
         sleep(Duration::from_secs(config.modules.system.monitor_interval_seconds)).await;
     }
 }
 
-pub async fn monitor_service(service_name: &str, restart_if_high_cpu: bool, cpu_threshold: u8) -> Result<()> {
+/// Watch a systemd unit, or the container of that name when `container`
+/// is given.
+pub async fn monitor_service(
+    service_name: &str,
+    container: Option<&ContainerClient>,
+    restart_if_high_cpu: bool,
+    cpu_threshold: u8,
+    config: &Config,
+) -> Result<()> {
     info!("🔍 Monitoring service: {}", service_name);
     
     loop {
-        let status = get_service_status(service_name).await?;
+        let status = match container {
+            Some(client) => get_container_status(client, service_name).await?,
+            None => get_service_status(service_name).await?,
+        };
         
-        println!("=== Service Status: {} ===", service_name);
-        println!("Active: {}", if status.active { "✅ Yes" } else { "❌ No" });
-        println!("Enabled: {}", if status.enabled { "✅ Yes" } else { "❌ No" });
-        println!("Status: {}", status.status);
+        print_sample(&status)?;
         
         if let Some(cpu_usage) = status.cpu_usage {
-            println!("CPU Usage: {:.1}%", cpu_usage);
-            
             if restart_if_high_cpu && cpu_usage > cpu_threshold as f64 {
                 warn!("🚨 High CPU usage for {}: {:.1}% > {}%", service_name, cpu_usage, cpu_threshold);
                 info!("🔄 Restarting service: {}", service_name);
-                restart_service(service_name).await?;
+                let event = audit::Event::new("system.restart")
+                    .param("service", service_name)
+                    .param("cpu_usage", cpu_usage);
+                let result = match container {
+                    Some(client) => client.restart(service_name, None).await.inspect(|_| {
+                        info!("✅ Successfully restarted container: {}", service_name);
+                    }),
+                    None => restart_service(service_name).await,
+                };
+                audit::record(&config.audit, event, &result);
+                result?;
             }
         }
         
-        if let Some(memory_usage) = status.memory_usage {
-            println!("Memory Usage: {} MB", memory_usage / 1024 / 1024);
-        }
-        
-        println!();
         sleep(Duration::from_secs(30)).await;
     }
 }
@@ -227,7 +305,7 @@ async fn get_disk_usage() -> Result<Vec<DiskInfo>> {
     Ok(disks)
 }
 
-async fn get_service_status(service_name: &str) -> Result<ServiceStatus> {
+pub async fn get_service_status(service_name: &str) -> Result<ServiceStatus> {
     let status_output = get_command_output("systemctl", &["status", service_name]).await
         .unwrap_or_else(|_| "inactive".to_string());
     
@@ -250,6 +328,29 @@ async fn get_service_status(service_name: &str) -> Result<ServiceStatus> {
     })
 }
 
+/// A container's state as a `ServiceStatus`: active while running,
+/// enabled when it has a restart policy.
+async fn get_container_status(client: &ContainerClient, name: &str) -> Result<ServiceStatus> {
+    let inspect = client.inspect(name).await?;
+    let state = &inspect["State"];
+    let active = state["Running"].as_bool().unwrap_or(false);
+    let restart_policy = inspect["HostConfig"]["RestartPolicy"]["Name"].as_str().unwrap_or("");
+    let stats = if active { Some(client.stats(name).await?) } else { None };
+
+    Ok(ServiceStatus {
+        name: name.to_string(),
+        active,
+        enabled: !matches!(restart_policy, "" | "no"),
+        status: format!(
+            "{} ({})",
+            state["Status"].as_str().unwrap_or("unknown"),
+            inspect["Config"]["Image"].as_str().unwrap_or("unknown image")
+        ),
+        memory_usage: stats.as_ref().map(|stats| stats.memory_usage),
+        cpu_usage: stats.as_ref().map(|stats| stats.cpu_percent),
+    })
+}
+
 async fn restart_service(service_name: &str) -> Result<()> {
     let output = Command::new("sudo")
         .arg("systemctl")
@@ -263,7 +364,7 @@ async fn restart_service(service_name: &str) -> Result<()> {
     } else {
         let error = String::from_utf8_lossy(&output.stderr);
         error!("❌ Failed to restart service {}: {}", service_name, error);
-        return Err(SigilError::system_command("systemctl restart", &error.to_string()));
+        return Err(SigilError::system_command("systemctl restart", error.as_ref()));
     }
     
     Ok(())
@@ -285,12 +386,37 @@ pub async fn execute_command(command: &str, args: &[String]) -> Result<()> {
     } else {
         let stderr = String::from_utf8_lossy(&output.stderr);
         eprintln!("❌ Command failed: {}", stderr);
-        return Err(SigilError::system_command(command, &stderr.to_string()));
+        return Err(SigilError::system_command(command, stderr.as_ref()).with_exit_status(output.status));
     }
     
     Ok(())
 }
 
+/// Run a command on each host over SSH, then report per-host status.
+pub async fn execute_remote(
+    command: &str,
+    args: &[String],
+    hosts: &[Host],
+    parallelism: Option<usize>,
+    config: &Config,
+) -> Result<()> {
+    info!("🚀 Executing on {} host(s): {} {}", hosts.len(), command, args.join(" "));
+
+    let runner = Arc::new(SshRunner::new(config, parallelism));
+    let remote = RemoteCommand::system(command, args, &HashMap::new(), None);
+    let results = runner.run(hosts.iter().map(|host| host.job(remote.clone())).collect()).await?;
+
+    let failed = ssh::summarize(&results);
+    if failed > 0 {
+        return Err(SigilError::system_command(
+            command,
+            &format!("{} of {} hosts failed", failed, results.len()),
+        )
+        .with_exit_code(ssh::common_exit_code(&results)));
+    }
+    Ok(())
+}
+
 async fn get_command_output(command: &str, args: &[&str]) -> Result<String> {
     let output = Command::new(command)
         .args(args)
@@ -301,7 +427,7 @@ async fn get_command_output(command: &str, args: &[&str]) -> Result<String> {
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     } else {
         let error = String::from_utf8_lossy(&output.stderr);
-        Err(SigilError::system_command(command, &error.to_string()))
+        Err(SigilError::system_command(command, error.as_ref()))
     }
 }
 
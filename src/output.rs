@@ -0,0 +1,220 @@
+//! How command results are printed, as chosen with the global `--output`
+//! and `--no-emoji` flags.
+//!
+//! `table` is the decorated text meant for people, `plain` the same text
+//! without headings, emoji or column headers, and `json`/`yaml` the
+//! serialized result. Commands print results through [`print`] or check
+//! [`structured`] first, and print progress messages with [`say!`] so they
+//! stay out of JSON and YAML on stdout.
+
+use crate::cli::OutputFormat;
+use crate::error::Result;
+use serde::Serialize;
+use std::fmt::{self, Display};
+use std::io::Write;
+use std::sync::OnceLock;
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    format: OutputFormat,
+    emoji: bool,
+}
+
+fn settings() -> Settings {
+    *SETTINGS.get_or_init(|| Settings {
+        format: OutputFormat::Table,
+        emoji: true,
+    })
+}
+
+/// Choose the output format for this process. Emoji are left out with
+/// `no_emoji`, when `NO_COLOR` is set, and in every format but `table`.
+pub fn init(format: OutputFormat, no_emoji: bool) {
+    let emoji = !no_emoji && !no_color() && matches!(format, OutputFormat::Table);
+    let _ = SETTINGS.set(Settings { format, emoji });
+}
+
+/// Whether `NO_COLOR` asks for undecorated output (see no-color.org).
+pub fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+pub fn format() -> OutputFormat {
+    settings().format
+}
+
+/// Whether stdout carries JSON or YAML.
+pub fn is_structured() -> bool {
+    matches!(format(), OutputFormat::Json | OutputFormat::Yaml)
+}
+
+/// `emoji`, or nothing when emoji are turned off.
+pub fn icon(emoji: &'static str) -> &'static str {
+    if settings().emoji {
+        emoji
+    } else {
+        ""
+    }
+}
+
+/// `text` after an icon and a space, or on its own without emoji.
+pub fn decorate(emoji: &'static str, text: impl Display) -> String {
+    match icon(emoji) {
+        "" => text.to_string(),
+        icon => format!("{} {}", icon, text),
+    }
+}
+
+/// Print `value` if JSON or YAML was chosen. Returns `false` for the text
+/// formats, which the caller prints itself.
+pub fn structured<T: Serialize + ?Sized>(value: &T) -> Result<bool> {
+    match format() {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        OutputFormat::Yaml => print!("{}", to_yaml(value)?),
+        OutputFormat::Table | OutputFormat::Plain => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Print one record of a stream, such as a monitor sample: a line of
+/// NDJSON, or a YAML document. Returns `false` for the text formats.
+pub fn stream<T: Serialize + ?Sized>(value: &T) -> Result<bool> {
+    let mut stdout = std::io::stdout().lock();
+    match format() {
+        OutputFormat::Json => writeln!(stdout, "{}", serde_json::to_string(value)?)?,
+        OutputFormat::Yaml => write!(stdout, "---\n{}", to_yaml(value)?)?,
+        OutputFormat::Table | OutputFormat::Plain => return Ok(false),
+    }
+    stdout.flush()?;
+    Ok(true)
+}
+
+fn to_yaml<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    serde_yaml::to_string(value).map_err(|e| crate::error::SigilError::module("output".to_string(), e.to_string()))
+}
+
+/// A command result with a text form for `table` and `plain` output.
+pub trait Render: Serialize {
+    fn render(&self, text: &mut Text);
+}
+
+/// Print a result in the chosen format.
+pub fn print<T: Render + ?Sized>(value: &T) -> Result<()> {
+    if structured(value)? {
+        return Ok(());
+    }
+    let mut text = Text::new();
+    value.render(&mut text);
+    print!("{}", text);
+    Ok(())
+}
+
+/// Write a progress or status line: to stdout for text output, and to
+/// stderr when stdout carries JSON or YAML.
+pub fn status(args: fmt::Arguments<'_>) {
+    if is_structured() {
+        eprintln!("{}", args);
+    } else {
+        println!("{}", args);
+    }
+}
+
+/// `println!` for messages that are not the command's result.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        $crate::output::status(format_args!($($arg)*))
+    };
+}
+pub(crate) use say;
+
+/// Text form of a result, built line by line.
+pub struct Text {
+    plain: bool,
+    out: String,
+}
+
+impl Text {
+    fn new() -> Self {
+        Text {
+            plain: matches!(format(), OutputFormat::Plain),
+            out: String::new(),
+        }
+    }
+
+    /// Whether this is `plain` output, for results that lay out
+    /// differently without decoration.
+    pub fn is_plain(&self) -> bool {
+        self.plain
+    }
+
+    pub fn line(&mut self, line: impl Display) {
+        self.out.push_str(&line.to_string());
+        self.out.push('\n');
+    }
+
+    /// A line starting with an icon.
+    pub fn item(&mut self, emoji: &'static str, line: impl Display) {
+        self.line(decorate(emoji, line));
+    }
+
+    /// An underlined title; left out of plain output.
+    pub fn heading(&mut self, emoji: &'static str, title: impl Display) {
+        if self.plain {
+            return;
+        }
+        let title = decorate(emoji, title);
+        let width = title.chars().count() + usize::from(title.chars().next().is_some_and(|c| !c.is_ascii()));
+        self.line(&title);
+        self.line("=".repeat(width));
+    }
+
+    /// `label: value`
+    pub fn field(&mut self, label: &str, value: impl Display) {
+        self.line(format!("{}: {}", label, value));
+    }
+
+    /// A separating empty line; left out of plain output.
+    pub fn blank(&mut self) {
+        if !self.plain {
+            self.out.push('\n');
+        }
+    }
+
+    /// Rows in aligned columns under `headers`, or tab-separated without
+    /// headers in plain output. The last column is not padded.
+    pub fn table(&mut self, headers: &[&str], rows: &[Vec<String>]) {
+        if self.plain {
+            for row in rows {
+                self.line(row.join("\t"));
+            }
+            return;
+        }
+        let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+        for row in rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let format_row = |cells: &mut dyn Iterator<Item = &str>| {
+            let cells: Vec<String> = cells
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            cells.join("  ").trim_end().to_string()
+        };
+        let header = format_row(&mut headers.iter().copied());
+        self.line(header);
+        for row in rows {
+            let line = format_row(&mut row.iter().map(String::as_str));
+            self.line(line);
+        }
+    }
+}
+
+impl Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.out)
+    }
+}
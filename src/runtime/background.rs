@@ -0,0 +1,103 @@
+//! `sigil task run` in a process of its own, with its output going to
+//! `<state_dir>/<id>.log`. Used by `sigil agent` and `sigil tui`.
+
+use super::task_runner::{self, TaskInstance, TaskStatus};
+use crate::config::{Config, LoadOptions};
+use crate::error::{Result, SigilError};
+use chrono::Utc;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use tokio::process::{Child, Command};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Where the output of the run with this instance ID goes.
+pub fn log_path(config: &Config, id: &Uuid) -> PathBuf {
+    config.tasks.state_dir.join(format!("{}.log", id))
+}
+
+/// Start `sigil task run <name> --instance-id <id>` followed by `args`,
+/// loading the same configuration as this process. The task process
+/// leads its own process group so [`cancel`] reaches its commands too.
+pub async fn spawn(id: Uuid, name: &str, args: &[String], options: &LoadOptions, config: &Config) -> Result<Child> {
+    tokio::fs::create_dir_all(&config.tasks.state_dir).await?;
+    let log = open_log(&log_path(config, &id))?;
+
+    let mut command = Command::new(std::env::current_exe()?);
+    if let Some(path) = &options.path {
+        command.arg("--config").arg(path);
+    }
+    if let Some(profile) = &options.profile {
+        command.arg("--profile").arg(profile);
+    }
+    if options.strict {
+        command.arg("--strict-config");
+    }
+    command.args(["task", "run", name, "--instance-id", &id.to_string()]);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .process_group(0);
+    command
+        .spawn()
+        .map_err(|e| SigilError::task_execution(format!("Failed to start task process: {}", e)))
+}
+
+/// Stop a task process and the commands it started.
+pub async fn cancel(child: &Child) -> Result<()> {
+    let Some(pid) = child.id() else {
+        // Already reaped.
+        return Ok(());
+    };
+    let status = Command::new("kill")
+        .args(["-TERM", "--", &format!("-{}", pid)])
+        .status()
+        .await
+        .map_err(|e| SigilError::system_command("kill", &e.to_string()))?;
+    if !status.success() {
+        return Err(SigilError::system_command("kill", &format!("could not signal process group {}", pid)));
+    }
+    Ok(())
+}
+
+/// Record how a task process ended. If it exited before saving a final
+/// state, `accepted` is saved as failed, or as cancelled when it was
+/// stopped with [`cancel`], so nothing waits on it forever.
+pub async fn settle(accepted: TaskInstance, exit: &std::io::Result<ExitStatus>, cancelled: bool, config: &Config) {
+    let mut instance = task_runner::load_task_instance_by_id(&accepted.id, config)
+        .await
+        .unwrap_or(accepted);
+    if instance.is_finished() {
+        return;
+    }
+    instance.completed_at = Some(Utc::now());
+    if cancelled {
+        instance.status = TaskStatus::Cancelled;
+        instance.error = Some("Cancelled".to_string());
+    } else {
+        let status = match exit {
+            Ok(status) => status.to_string(),
+            Err(e) => e.to_string(),
+        };
+        instance.status = TaskStatus::Failed;
+        instance.error = Some(format!("Task process ended without a result ({})", status));
+    }
+    if let Err(e) = task_runner::save_task_instance(&instance, config).await {
+        warn!("⚠️  Failed to save state for {}: {}", instance.id, e);
+    }
+}
+
+/// Task logs hold the same (masked) output a terminal would, but are kept
+/// owner-only like the rest of the state directory should be.
+fn open_log(path: &Path) -> std::io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.create_new(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
@@ -1,3 +1,4 @@
+pub mod background;
+pub mod ssh;
+pub mod strategy;
 pub mod task_runner;
-
-pub use task_runner::*;
@@ -0,0 +1,310 @@
+use crate::config::{Config, SshConfig};
+use crate::error::{Result, SigilError};
+use crate::output::{self, say};
+use crate::secrets::redact;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::debug;
+
+/// Exit status ssh uses for its own failures (connection, authentication).
+const SSH_FAILURE: i32 = 255;
+
+/// What to run on each host. The script is sent on ssh's stdin, so
+/// environment values (often secrets) never appear in a process list.
+#[derive(Debug, Clone)]
+pub struct RemoteCommand {
+    interpreter: &'static str,
+    script: String,
+}
+
+impl RemoteCommand {
+    /// A shell script, run with bash like local shell tasks.
+    pub fn shell(script: &str, env: &HashMap<String, String>, working_directory: Option<&Path>) -> Self {
+        RemoteCommand {
+            interpreter: "bash",
+            script: format!("{}{}\n", preamble(env, working_directory), script),
+        }
+    }
+
+    /// A program and its arguments, passed through verbatim.
+    pub fn system(
+        command: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+        working_directory: Option<&Path>,
+    ) -> Self {
+        let words: Vec<String> = std::iter::once(command).chain(args.iter().map(String::as_str)).map(quote).collect();
+        RemoteCommand {
+            interpreter: "sh",
+            script: format!("{}exec {}\n", preamble(env, working_directory), words.join(" ")),
+        }
+    }
+}
+
+/// `export`s and `cd` run before the command itself.
+fn preamble(env: &HashMap<String, String>, working_directory: Option<&Path>) -> String {
+    let mut names: Vec<&String> = env.keys().collect();
+    names.sort();
+    let mut preamble: String = names
+        .into_iter()
+        .map(|name| format!("export {}={}\n", name, quote(&env[name])))
+        .collect();
+    if let Some(dir) = working_directory {
+        preamble.push_str(&format!("cd {} || exit 1\n", quote(&dir.to_string_lossy())));
+    }
+    preamble
+}
+
+/// Single-quote a word for a POSIX shell.
+fn quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// One host's share of a remote run.
+#[derive(Debug, Clone)]
+pub struct RemoteJob {
+    /// Name used in output and results.
+    pub host: String,
+    /// What ssh connects to: a hostname, `user@host` or a `~/.ssh/config` alias.
+    pub destination: String,
+    /// Remote user; overrides `modules.ssh.user`.
+    pub user: Option<String>,
+    pub command: RemoteCommand,
+}
+
+/// Outcome of a command on one host.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostResult {
+    pub host: String,
+    /// `None` if the command was killed by a signal or ssh could not start.
+    pub exit_code: Option<i32>,
+    /// Batch of a rolling run the host was in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch: Option<usize>,
+    /// Not run because an earlier batch failed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skipped: bool,
+    #[serde(skip)]
+    pub stdout: String,
+    #[serde(skip)]
+    pub stderr: String,
+}
+
+impl HostResult {
+    pub fn skipped(host: &str) -> Self {
+        HostResult {
+            host: host.to_string(),
+            exit_code: None,
+            batch: None,
+            skipped: true,
+            stdout: String::new(),
+            stderr: String::new(),
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// Icon for this outcome in reports.
+    pub fn mark(&self) -> &'static str {
+        if self.skipped {
+            "⏭️ "
+        } else if self.success() {
+            "✅"
+        } else {
+            "❌"
+        }
+    }
+
+    pub fn failed(&self) -> bool {
+        !self.success() && !self.skipped
+    }
+
+    /// `exit 2`, or why ssh itself failed.
+    pub fn describe(&self) -> String {
+        if self.skipped {
+            return "skipped".to_string();
+        }
+        match self.exit_code {
+            Some(SSH_FAILURE) => match self.stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+                Some(reason) => format!("ssh failed: {}", reason.trim()),
+                None => "ssh failed".to_string(),
+            },
+            Some(code) => format!("exit {}", code),
+            None if self.stderr.is_empty() => "killed".to_string(),
+            None => self.stderr.trim().to_string(),
+        }
+    }
+}
+
+/// Runs commands on hosts through the ssh client, a bounded number at a time.
+pub struct SshRunner {
+    settings: SshConfig,
+    parallelism: usize,
+}
+
+impl SshRunner {
+    /// `parallelism` overrides `modules.ssh.parallelism`.
+    pub fn new(config: &Config, parallelism: Option<usize>) -> Self {
+        let settings = config.modules.ssh.clone();
+        SshRunner {
+            parallelism: parallelism.unwrap_or(settings.parallelism).max(1),
+            settings,
+        }
+    }
+
+    fn command(&self, job: &RemoteJob) -> Command {
+        let mut command = Command::new(&self.settings.program);
+        command
+            .args(["-o", "BatchMode=yes"])
+            .arg("-o")
+            .arg(format!("ConnectTimeout={}", self.settings.connect_timeout_seconds));
+        if let Some(user) = job.user.as_ref().or(self.settings.user.as_ref()) {
+            command.arg("-l").arg(user);
+        }
+        for option in &self.settings.options {
+            command.arg("-o").arg(option);
+        }
+        command.arg(&job.destination).arg(job.command.interpreter).arg("-s");
+        command
+    }
+
+    /// Run on one host, printing its output line by line as `[host] ...`.
+    pub async fn run_one(&self, job: &RemoteJob) -> HostResult {
+        let (host, remote) = (job.host.as_str(), &job.command);
+        let failed = |message: String| HostResult {
+            stderr: message,
+            skipped: false,
+            ..HostResult::skipped(host)
+        };
+        // Anything starting with `-` would be taken as an ssh option.
+        if job.destination.is_empty() || job.destination.starts_with('-') {
+            return failed(format!("invalid host '{}'", job.destination));
+        }
+
+        debug!("ssh {}: {} -s", job.destination, remote.interpreter);
+        let mut command = self.command(job);
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => return failed(format!("cannot run {}: {}", self.settings.program, e)),
+        };
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let script = remote.script.clone();
+        let feed = async move {
+            // The remote side may exit without reading everything.
+            let _ = stdin.write_all(script.as_bytes()).await;
+            drop(stdin);
+        };
+
+        let (_, stdout, stderr, status) =
+            tokio::join!(feed, prefix_lines(stdout, host, false), prefix_lines(stderr, host, true), child.wait());
+        match status {
+            Ok(status) => HostResult {
+                host: host.to_string(),
+                exit_code: status.code(),
+                batch: None,
+                skipped: false,
+                stdout,
+                stderr,
+            },
+            Err(e) => failed(e.to_string()),
+        }
+    }
+
+    /// Run every job, at most `parallelism` at once. Results are in the
+    /// order of `jobs`.
+    pub async fn run(self: &Arc<Self>, jobs: Vec<RemoteJob>) -> Result<Vec<HostResult>> {
+        let limit = Arc::new(Semaphore::new(self.parallelism));
+        let mut tasks = JoinSet::new();
+        let count = jobs.len();
+        for (index, job) in jobs.into_iter().enumerate() {
+            let runner = Arc::clone(self);
+            let limit = Arc::clone(&limit);
+            tasks.spawn(async move {
+                let _permit = limit.acquire_owned().await.expect("semaphore is never closed");
+                (index, runner.run_one(&job).await)
+            });
+        }
+
+        let mut results: Vec<Option<HostResult>> = (0..count).map(|_| None).collect();
+        while let Some(joined) = tasks.join_next().await {
+            let (index, result) =
+                joined.map_err(|e| SigilError::task_execution(format!("Remote execution failed: {}", e)))?;
+            results[index] = Some(result);
+        }
+        Ok(results.into_iter().flatten().collect())
+    }
+}
+
+/// Print each line of a stream as `[host] line` with secrets masked, and
+/// return the masked output without prefixes.
+async fn prefix_lines<R: AsyncRead + Unpin>(reader: R, host: &str, to_stderr: bool) -> String {
+    let mut reader = BufReader::new(reader);
+    let mut captured = String::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let text = redact::redact(&String::from_utf8_lossy(&line));
+        let text = text.trim_end_matches(['\r', '\n']);
+        let printed = format!("[{}] {}\n", host, text);
+        // Whole lines in one write keep parallel hosts from interleaving
+        // mid-line. JSON and YAML results own stdout.
+        let _ = if to_stderr || output::is_structured() {
+            std::io::stderr().lock().write_all(printed.as_bytes())
+        } else {
+            std::io::stdout().lock().write_all(printed.as_bytes())
+        };
+        captured.push_str(text);
+        captured.push('\n');
+    }
+    captured
+}
+
+/// Print one line per host and return how many failed.
+pub fn summarize(results: &[HostResult]) -> usize {
+    for result in results {
+        say!("{}", output::decorate(result.mark(), format!("{}: {}", result.host, result.describe())));
+    }
+    let failed = results.iter().filter(|result| result.failed()).count();
+    let skipped = results.iter().filter(|result| result.skipped).count();
+    if results.len() > 1 {
+        let mut report = format!("{} succeeded, {} failed", results.len() - failed - skipped, failed);
+        if skipped > 0 {
+            report.push_str(&format!(", {} skipped", skipped));
+        }
+        if let Some(batches) = results.iter().filter_map(|result| result.batch).max().filter(|&batches| batches > 1) {
+            report.push_str(&format!(" in {} batches", batches));
+        }
+        say!("{}", output::decorate("📊", report));
+    }
+    failed
+}
+
+/// The exit code every failed host shares, if they all share one; sigil
+/// exits with it.
+pub fn common_exit_code(results: &[HostResult]) -> Option<i32> {
+    let mut codes = results.iter().filter(|result| result.failed()).map(|result| result.exit_code);
+    let first = codes.next()??;
+    codes.all(|code| code == Some(first)).then_some(first)
+}
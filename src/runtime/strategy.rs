@@ -0,0 +1,337 @@
+use super::ssh::{HostResult, RemoteJob, SshRunner};
+use crate::error::{Result, SigilError};
+use crate::output::{self, say};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How many hosts run at once in a rolling run.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(try_from = "SerialValue", into = "SerialValue")]
+pub enum BatchSize {
+    /// Every host in one batch.
+    #[default]
+    All,
+    Count(usize),
+    /// A share of the hosts, rounded up.
+    Percent(u8),
+}
+
+/// `serial` as written in task files: a count or a string such as `"25%"`.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum SerialValue {
+    Count(usize),
+    Text(String),
+}
+
+impl TryFrom<SerialValue> for BatchSize {
+    type Error = SigilError;
+
+    fn try_from(value: SerialValue) -> Result<Self> {
+        match value {
+            SerialValue::Count(0) => "0".parse(),
+            SerialValue::Count(count) => Ok(BatchSize::Count(count)),
+            SerialValue::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl From<BatchSize> for SerialValue {
+    fn from(size: BatchSize) -> Self {
+        match size {
+            BatchSize::Count(count) => SerialValue::Count(count),
+            other => SerialValue::Text(other.to_string()),
+        }
+    }
+}
+
+impl FromStr for BatchSize {
+    type Err = SigilError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = || {
+            SigilError::invalid_config(
+                "strategy.serial".to_string(),
+                format!("'{}' is not 'all', a host count or a percentage like '25%'", text),
+            )
+        };
+        let text = text.trim();
+        if text.eq_ignore_ascii_case("all") {
+            return Ok(BatchSize::All);
+        }
+        match text.strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<u8>() {
+                Ok(percent @ 1..=100) => Ok(BatchSize::Percent(percent)),
+                _ => Err(invalid()),
+            },
+            None => match text.parse::<usize>() {
+                Ok(count) if count > 0 => Ok(BatchSize::Count(count)),
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for BatchSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchSize::All => write!(f, "all"),
+            BatchSize::Count(count) => write!(f, "{}", count),
+            BatchSize::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+/// How a task rolls out across its hosts: in batches, stopping once too
+/// many hosts have failed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Strategy {
+    /// Hosts per batch: `"all"` (default), a count such as `2`, or a
+    /// percentage of the hosts such as `"25%"`.
+    pub serial: BatchSize,
+    /// Share of all hosts that may fail before the remaining batches are
+    /// skipped. Unset means any failure stops the rollout.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(range(max = 100))]
+    pub max_fail_percentage: Option<u8>,
+    /// Wait between batches.
+    pub pause_seconds: u64,
+}
+
+impl Strategy {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_fail_percentage.is_some_and(|percent| percent > 100) {
+            return Err(SigilError::invalid_config(
+                "strategy.max_fail_percentage",
+                "must be between 0 and 100",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Number of hosts in each batch for `hosts` hosts.
+    pub fn batch_size(&self, hosts: usize) -> usize {
+        let size = match self.serial {
+            BatchSize::All => hosts,
+            BatchSize::Count(count) => count,
+            BatchSize::Percent(percent) => (hosts * percent as usize).div_ceil(100),
+        };
+        size.clamp(1, hosts.max(1))
+    }
+
+    /// Whether `failed` of `hosts` hosts is more than the rollout tolerates.
+    fn exceeded(&self, failed: usize, hosts: usize) -> bool {
+        match self.max_fail_percentage {
+            None => failed > 0,
+            Some(percent) => failed * 100 > hosts * percent as usize,
+        }
+    }
+}
+
+/// Run `jobs` batch by batch. Once failures exceed the strategy's limit,
+/// hosts in later batches are not contacted and come back as skipped.
+/// Results are in the order of `jobs`.
+pub async fn roll_out(runner: &Arc<SshRunner>, jobs: Vec<RemoteJob>, strategy: &Strategy) -> Result<Vec<HostResult>> {
+    let total = jobs.len();
+    let size = strategy.batch_size(total);
+    let batches = total.div_ceil(size);
+    let mut results = Vec::with_capacity(total);
+    let mut failed = 0;
+    let mut jobs = jobs.into_iter();
+
+    for batch in 1..=batches {
+        let batch_jobs: Vec<RemoteJob> = jobs.by_ref().take(size).collect();
+        if batches > 1 {
+            let hosts: Vec<&str> = batch_jobs.iter().map(|job| job.host.as_str()).collect();
+            say!("{}", output::decorate("🔁", format!("Batch {}/{}: {}", batch, batches, hosts.join(", "))));
+        }
+        for mut result in runner.run(batch_jobs).await? {
+            result.batch = (batches > 1).then_some(batch);
+            failed += usize::from(!result.success());
+            results.push(result);
+        }
+
+        if batch == batches {
+            break;
+        }
+        if strategy.exceeded(failed, total) {
+            say!(
+                "{}",
+                output::decorate(
+                    "🛑",
+                    format!("{} of {} hosts failed; skipping the remaining {} batch(es)", failed, total, batches - batch)
+                )
+            );
+            results.extend(jobs.by_ref().map(|job| HostResult::skipped(&job.host)));
+            break;
+        }
+        if strategy.pause_seconds > 0 {
+            say!(
+                "{}",
+                output::decorate("⏸️ ", format!("Pausing {}s before batch {}/{}", strategy.pause_seconds, batch + 1, batches))
+            );
+            tokio::time::sleep(Duration::from_secs(strategy.pause_seconds)).await;
+        }
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::runtime::ssh::RemoteCommand;
+    use std::collections::HashMap;
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+
+    fn strategy(serial: BatchSize, max_fail_percentage: Option<u8>) -> Strategy {
+        Strategy {
+            serial,
+            max_fail_percentage,
+            pause_seconds: 0,
+        }
+    }
+
+    #[test]
+    fn parses_batch_sizes() {
+        for (text, expected) in [
+            ("all", Some(BatchSize::All)),
+            ("ALL", Some(BatchSize::All)),
+            ("3", Some(BatchSize::Count(3))),
+            (" 2 ", Some(BatchSize::Count(2))),
+            ("25%", Some(BatchSize::Percent(25))),
+            ("100%", Some(BatchSize::Percent(100))),
+            ("0", None),
+            ("0%", None),
+            ("101%", None),
+            ("-1", None),
+            ("some", None),
+            ("", None),
+        ] {
+            assert_eq!(text.parse::<BatchSize>().ok(), expected, "parsing {:?}", text);
+        }
+    }
+
+    #[test]
+    fn batch_sizes_stay_between_one_and_all_hosts() {
+        for (serial, hosts, expected) in [
+            (BatchSize::All, 10, 10),
+            (BatchSize::All, 0, 1),
+            (BatchSize::Count(3), 10, 3),
+            (BatchSize::Count(20), 5, 5),
+            (BatchSize::Percent(25), 10, 3),
+            (BatchSize::Percent(1), 10, 1),
+            (BatchSize::Percent(100), 7, 7),
+        ] {
+            assert_eq!(strategy(serial, None).batch_size(hosts), expected, "{} of {} hosts", serial, hosts);
+        }
+    }
+
+    #[test]
+    fn failure_limits() {
+        for (max_fail_percentage, failed, hosts, expected) in [
+            (None, 0, 4, false),
+            (None, 1, 4, true),
+            (Some(0), 0, 4, false),
+            (Some(0), 1, 4, true),
+            (Some(50), 2, 4, false),
+            (Some(50), 3, 4, true),
+            (Some(100), 4, 4, false),
+        ] {
+            assert_eq!(
+                strategy(BatchSize::All, max_fail_percentage).exceeded(failed, hosts),
+                expected,
+                "{} of {} failed with max_fail_percentage {:?}",
+                failed,
+                hosts,
+                max_fail_percentage
+            );
+        }
+    }
+
+    /// Rolls out one host per batch with an ssh stand-in that fails on
+    /// hosts named `fail`, and returns each host's outcome.
+    #[cfg(unix)]
+    async fn roll_out_hosts(hosts: &[&str], max_fail_percentage: Option<u8>) -> Vec<(String, &'static str, Option<usize>)> {
+        let ssh = std::env::temp_dir().join(format!("sigil-ssh-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&ssh, "#!/bin/sh\ncat >/dev/null\nfor arg; do [ \"$arg\" = fail ] && exit 1; done\nexit 0\n").unwrap();
+        std::fs::set_permissions(&ssh, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut config = Config::default();
+        config.modules.ssh.program = ssh.to_string_lossy().into_owned();
+        let runner = Arc::new(SshRunner::new(&config, None));
+        let jobs = hosts
+            .iter()
+            .map(|host| RemoteJob {
+                host: host.to_string(),
+                destination: host.to_string(),
+                user: None,
+                command: RemoteCommand::shell("true", &HashMap::new(), None),
+            })
+            .collect();
+
+        let results = roll_out(&runner, jobs, &strategy(BatchSize::Count(1), max_fail_percentage)).await;
+        std::fs::remove_file(&ssh).unwrap();
+        results
+            .unwrap()
+            .iter()
+            .map(|result| {
+                let outcome = if result.skipped {
+                    "skipped"
+                } else if result.success() {
+                    "ok"
+                } else {
+                    "failed"
+                };
+                (result.host.clone(), outcome, result.batch)
+            })
+            .collect()
+    }
+
+    #[cfg(unix)]
+    fn outcomes(expected: &[(&str, &'static str, Option<usize>)]) -> Vec<(String, &'static str, Option<usize>)> {
+        expected.iter().map(|(host, outcome, batch)| (host.to_string(), *outcome, *batch)).collect()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn skips_later_batches_after_a_failure() {
+        let hosts = ["web1", "fail", "web2", "web3"];
+        let expected = outcomes(&[
+            ("web1", "ok", Some(1)),
+            ("fail", "failed", Some(2)),
+            ("web2", "skipped", None),
+            ("web3", "skipped", None),
+        ]);
+        assert_eq!(roll_out_hosts(&hosts, None).await, expected);
+        assert_eq!(roll_out_hosts(&hosts, Some(0)).await, expected);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn keeps_going_within_the_failure_limit() {
+        assert_eq!(
+            roll_out_hosts(&["web1", "fail", "web2", "web3"], Some(25)).await,
+            outcomes(&[
+                ("web1", "ok", Some(1)),
+                ("fail", "failed", Some(2)),
+                ("web2", "ok", Some(3)),
+                ("web3", "ok", Some(4)),
+            ])
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn a_failure_in_the_last_batch_skips_nothing() {
+        assert_eq!(
+            roll_out_hosts(&["web1", "fail"], None).await,
+            outcomes(&[("web1", "ok", Some(1)), ("fail", "failed", Some(2))])
+        );
+    }
+}
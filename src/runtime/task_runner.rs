@@ -1,16 +1,28 @@
 use crate::cli::TaskCommands;
 use crate::config::Config;
 use crate::error::{Result, SigilError};
+use crate::inventory::{Host, Inventory};
+use crate::modules::container;
+use crate::output::{self, say, Render, Text};
+use crate::runtime::ssh::{self, HostResult, RemoteCommand, SshRunner};
+use crate::runtime::strategy::{self, BatchSize, Strategy};
+use crate::secrets::{self, SecretRef};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use crate::secrets::redact::{self, StreamRedactor};
+use std::io::Write;
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use tokio::fs;
 use tracing::{info, warn};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct TaskDefinition {
     pub name: String,
     pub description: Option<String>,
@@ -19,17 +31,54 @@ pub struct TaskDefinition {
     pub timeout_seconds: Option<u64>,
     pub retry_count: Option<u32>,
     pub environment: Option<HashMap<String, String>>,
+    /// Environment variables filled from `secret://path#key` references at run time.
+    pub secrets: Option<HashMap<String, String>>,
+    /// SOPS-encrypted YAML/JSON inputs by name, decrypted in memory. Values
+    /// are available to the command as `${<name>.<key.path>}` and are never
+    /// stored in task state.
+    pub sops_files: Option<HashMap<String, PathBuf>>,
     pub working_directory: Option<PathBuf>,
+    /// Run shell and system commands on these hosts over SSH instead of
+    /// locally. Accepts a single host or a list; `target` is an alias.
+    #[serde(default, alias = "target", deserialize_with = "one_or_many", skip_serializing_if = "Option::is_none")]
+    pub hosts: Option<Vec<String>>,
+    /// Run on inventory hosts matching this expression instead, e.g.
+    /// `group:web,!tag:canary`. Inventory variables are then available as
+    /// `${name}` alongside the task's parameters, which win on conflict.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub targets: Option<String>,
+    /// Hosts to run on at once; defaults to `modules.ssh.parallelism`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallelism: Option<usize>,
+    /// Roll out across the hosts in batches instead of all at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<Strategy>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+fn one_or_many<'de, D>(deserializer: D) -> std::result::Result<Option<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(Some(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(host) => vec![host],
+        OneOrMany::Many(hosts) => hosts,
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub enum TaskCommand {
     Shell { script: String },
     System { command: String, args: Vec<String> },
     Module { module: String, action: String, params: HashMap<String, String> },
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct TaskParameter {
     pub description: String,
     pub required: bool,
@@ -37,7 +86,7 @@ pub struct TaskParameter {
     pub parameter_type: ParameterType,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub enum ParameterType {
     String,
     Integer,
@@ -59,6 +108,59 @@ pub struct TaskInstance {
     pub output: Option<String>,
     pub error: Option<String>,
     pub retry_count: u32,
+    /// Per-host exit status when the task ran over SSH.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub host_results: Vec<HostResult>,
+}
+
+impl TaskInstance {
+    pub fn new(id: Uuid, definition_name: &str, parameters: HashMap<String, String>) -> Self {
+        TaskInstance {
+            id,
+            definition_name: definition_name.to_string(),
+            status: TaskStatus::Pending,
+            parameters,
+            created_at: Utc::now(),
+            started_at: None,
+            completed_at: None,
+            output: None,
+            error: None,
+            retry_count: 0,
+            host_results: Vec::new(),
+        }
+    }
+
+    /// A copy with secret values masked in parameters, output and error,
+    /// as it is stored and served.
+    pub fn redacted(&self) -> TaskInstance {
+        let mut instance = self.clone();
+        instance.output = instance.output.as_deref().map(redact::redact);
+        instance.error = instance.error.as_deref().map(redact::redact);
+        for value in instance.parameters.values_mut() {
+            *value = redact::redact(value);
+        }
+        instance
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status, TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled)
+    }
+}
+
+/// Overrides for one run of a task, from the command line or an agent request.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Replaces the definition's `hosts` and `targets`.
+    pub hosts: Vec<String>,
+    /// Replaces the definition's `hosts` and `targets`.
+    pub targets: Option<String>,
+    pub parallelism: Option<usize>,
+    /// Replace the definition's `strategy.serial`.
+    pub serial: Option<BatchSize>,
+    pub max_fail_percentage: Option<u8>,
+    pub pause_seconds: Option<u64>,
+    /// ID for the new instance, chosen by the agent that started this run.
+    pub instance_id: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -76,8 +178,17 @@ pub async fn handle_command(cmd: &TaskCommands, config: &Config) -> Result<()> {
         TaskCommands::List => {
             list_tasks(config).await?;
         }
-        TaskCommands::Run { name, params } => {
-            run_task(name, params, config).await?;
+        TaskCommands::Run { name, params, hosts, targets, parallel, strategy, instance_id } => {
+            let options = RunOptions {
+                hosts: hosts.clone(),
+                targets: targets.clone(),
+                parallelism: parallel.map(|n| n as usize),
+                serial: strategy.serial,
+                max_fail_percentage: strategy.max_fail_percentage,
+                pause_seconds: strategy.pause,
+                instance_id: *instance_id,
+            };
+            run_task(name, params, &options, config).await?;
         }
         TaskCommands::Status { task } => {
             show_task_status(task, config).await?;
@@ -97,29 +208,26 @@ pub async fn list_tasks(config: &Config) -> Result<()> {
         return Ok(());
     }
     
-    println!("📋 Available Tasks:");
-    println!("==================");
-    
+    output::print(task_definitions(config).await?.as_slice())
+
+}
+
+/// Every valid definition in `tasks.definitions_dir`, by name. Files that
+/// fail to load are logged and skipped.
+pub async fn task_definitions(config: &Config) -> Result<Vec<TaskDefinition>> {
+    let tasks_dir = &config.tasks.definitions_dir;
+    if !tasks_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut definitions = Vec::new();
     let mut entries = fs::read_dir(tasks_dir).await?;
-    let mut found_tasks = false;
-    
     while let Some(entry) = entries.next_entry().await? {
         let path = entry.path();
         if path.extension().and_then(|s| s.to_str()) == Some("toml") {
             if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
                 match load_task_definition(name, config).await {
-                    Ok(task_def) => {
-                        println!("🔧 {}", task_def.name);
-                        if let Some(desc) = &task_def.description {
-                            println!("   {}", desc);
-                        }
-                        println!("   Command: {:?}", task_def.command);
-                        if !task_def.parameters.is_empty() {
-                            println!("   Parameters: {}", task_def.parameters.len());
-                        }
-                        println!();
-                        found_tasks = true;
-                    }
+                    Ok(task_def) => definitions.push(task_def),
                     Err(e) => {
                         warn!("⚠️  Failed to load task '{}': {}", name, e);
                     }
@@ -127,41 +235,91 @@ pub async fn list_tasks(config: &Config) -> Result<()> {
             }
         }
     }
-    
-    if !found_tasks {
-        println!("No valid task definitions found.");
+    definitions.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(definitions)
+}
+
+impl Render for [TaskDefinition] {
+    fn render(&self, text: &mut Text) {
+        if text.is_plain() {
+            let rows: Vec<Vec<String>> = self
+                .iter()
+                .map(|task_def| vec![task_def.name.clone(), task_def.description.clone().unwrap_or_default()])
+                .collect();
+            text.table(&["NAME", "DESCRIPTION"], &rows);
+            return;
+        }
+        text.heading("📋", "Available Tasks:");
+        
+        for task_def in self {
+            text.item("🔧", &task_def.name);
+            if let Some(desc) = &task_def.description {
+                text.line(format!("   {}", desc));
+            }
+            text.line(format!("   Command: {:?}", task_def.command));
+            if !task_def.parameters.is_empty() {
+                text.line(format!("   Parameters: {}", task_def.parameters.len()));
+            }
+            if let Some(strategy) = &task_def.strategy {
+                text.line(format!("   Strategy: serial {}", strategy.serial));
+            }
+            text.blank();
+        }
+        
+        if self.is_empty() {
+            text.line("No valid task definitions found.");
+        }
     }
-    
-    Ok(())
 }
 
-pub async fn run_task(name: &str, params: &[String], config: &Config) -> Result<()> {
-    info!("🚀 Running task: {}", name);
-    
+/// Load a definition and check `key=value` parameters against it, without
+/// running anything.
+pub async fn prepare_task(name: &str, params: &[String], config: &Config) -> Result<(TaskDefinition, HashMap<String, String>)> {
     let task_def = load_task_definition(name, config).await?;
     let parsed_params = parse_parameters(params)?;
-    
-    // Validate parameters
     validate_parameters(&task_def, &parsed_params)?;
+    Ok((task_def, parsed_params))
+}
+
+pub async fn run_task(name: &str, params: &[String], options: &RunOptions, config: &Config) -> Result<()> {
+    info!("🚀 Running task: {}", name);
     
-    // Create task instance
-    let mut task_instance = TaskInstance {
-        id: Uuid::new_v4(),
-        definition_name: name.to_string(),
-        status: TaskStatus::Pending,
-        parameters: parsed_params,
-        created_at: Utc::now(),
-        started_at: None,
-        completed_at: None,
-        output: None,
-        error: None,
-        retry_count: 0,
-    };
+    let (mut task_def, parsed_params) = prepare_task(name, params, config).await?;
+    if !options.hosts.is_empty() {
+        task_def.hosts = Some(options.hosts.clone());
+        task_def.targets = None;
+    }
+    if let Some(targets) = &options.targets {
+        task_def.targets = Some(targets.clone());
+        task_def.hosts = None;
+    }
+    if options.parallelism.is_some() {
+        task_def.parallelism = options.parallelism;
+    }
+    if options.serial.is_some() || options.max_fail_percentage.is_some() || options.pause_seconds.is_some() {
+        let strategy = task_def.strategy.get_or_insert_with(Strategy::default);
+        if let Some(serial) = options.serial {
+            strategy.serial = serial;
+        }
+        if options.max_fail_percentage.is_some() {
+            strategy.max_fail_percentage = options.max_fail_percentage;
+        }
+        if let Some(pause) = options.pause_seconds {
+            strategy.pause_seconds = pause;
+        }
+    }
+    if let Some(strategy) = &task_def.strategy {
+        strategy.validate()?;
+    }
+    
+    let id = options.instance_id.unwrap_or_else(Uuid::new_v4);
+    crate::audit::note_instance(id);
+    let mut task_instance = TaskInstance::new(id, name, parsed_params);
     
     // Save task state
     save_task_instance(&task_instance, config).await?;
     
-    println!("📋 Task '{}' started with ID: {}", name, task_instance.id);
+    say!("{}", output::decorate("📋", format!("Task '{}' started with ID: {}", name, task_instance.id)));
     
     // Execute task
     let result = execute_task_instance(&mut task_instance, &task_def, config).await;
@@ -169,20 +327,17 @@ pub async fn run_task(name: &str, params: &[String], config: &Config) -> Result<
     // Update final state
     save_task_instance(&task_instance, config).await?;
     
+    // Command output was already streamed while the task ran, so only
+    // JSON and YAML output repeat the instance.
+    if output::is_structured() {
+        output::structured(&task_instance.redacted())?;
+    }
     match result {
         Ok(_) => {
-            println!("✅ Task '{}' completed successfully", name);
-            if let Some(output) = &task_instance.output {
-                if !output.trim().is_empty() {
-                    println!("📄 Output:\n{}", output);
-                }
-            }
+            say!("{}", output::decorate("✅", format!("Task '{}' completed successfully", name)));
         }
         Err(e) => {
-            println!("❌ Task '{}' failed: {}", name, e);
-            if let Some(error) = &task_instance.error {
-                println!("💥 Error:\n{}", error);
-            }
+            say!("{}", output::decorate("❌", format!("Task '{}' failed: {}", name, e)));
             return Err(e);
         }
     }
@@ -191,56 +346,71 @@ pub async fn run_task(name: &str, params: &[String], config: &Config) -> Result<
 }
 
 pub async fn show_task_status(task_id: &str, config: &Config) -> Result<()> {
-    // Try to parse as UUID first, otherwise search by name
-    let task_instance = if let Ok(uuid) = Uuid::parse_str(task_id) {
-        load_task_instance_by_id(&uuid, config).await?
-    } else {
-        // Find most recent instance by name
-        find_latest_task_instance_by_name(task_id, config).await?
-    };
-    
-    println!("📊 Task Status");
-    println!("==============");
-    println!("ID: {}", task_instance.id);
-    println!("Name: {}", task_instance.definition_name);
-    println!("Status: {:?}", task_instance.status);
-    println!("Created: {}", task_instance.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
-    
-    if let Some(started) = task_instance.started_at {
-        println!("Started: {}", started.format("%Y-%m-%d %H:%M:%S UTC"));
+    output::print(&find_task_instance(task_id, config).await?)
+}
+
+/// An instance by ID, or the most recent run of a task by name.
+pub async fn find_task_instance(task: &str, config: &Config) -> Result<TaskInstance> {
+    match Uuid::parse_str(task) {
+        Ok(uuid) => load_task_instance_by_id(&uuid, config).await,
+        Err(_) => find_latest_task_instance_by_name(task, config).await,
     }
-    
-    if let Some(completed) = task_instance.completed_at {
-        println!("Completed: {}", completed.format("%Y-%m-%d %H:%M:%S UTC"));
+}
+
+impl Render for TaskInstance {
+    fn render(&self, text: &mut Text) {
+        text.heading("📊", "Task Status");
+        text.field("ID", self.id);
+        text.field("Name", &self.definition_name);
+        text.field("Status", format!("{:?}", self.status));
+        text.field("Created", self.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
         
-        if let Some(started) = task_instance.started_at {
-            let duration = completed.signed_duration_since(started);
-            println!("Duration: {}s", duration.num_seconds());
+        if let Some(started) = self.started_at {
+            text.field("Started", started.format("%Y-%m-%d %H:%M:%S UTC"));
         }
-    }
-    
-    if task_instance.retry_count > 0 {
-        println!("Retries: {}", task_instance.retry_count);
-    }
-    
-    if !task_instance.parameters.is_empty() {
-        println!("Parameters:");
-        for (key, value) in &task_instance.parameters {
-            println!("  {}: {}", key, value);
+        
+        if let Some(completed) = self.completed_at {
+            text.field("Completed", completed.format("%Y-%m-%d %H:%M:%S UTC"));
+            
+            if let Some(started) = self.started_at {
+                let duration = completed.signed_duration_since(started);
+                text.field("Duration", format!("{}s", duration.num_seconds()));
+            }
         }
-    }
-    
-    if let Some(output) = &task_instance.output {
-        if !output.trim().is_empty() {
-            println!("Output:\n{}", output);
+        
+        if self.retry_count > 0 {
+            text.field("Retries", self.retry_count);
+        }
+        
+        if !self.parameters.is_empty() {
+            text.line("Parameters:");
+            for (key, value) in &self.parameters {
+                text.line(format!("  {}: {}", key, value));
+            }
+        }
+        
+        if let Some(output) = &self.output {
+            if !output.trim().is_empty() {
+                text.line(format!("Output:\n{}", output));
+            }
+        }
+        
+        if !self.host_results.is_empty() {
+            text.line("Hosts:");
+            for result in &self.host_results {
+                let line = format!("{}: {}", result.host, result.describe());
+                let line = match result.batch {
+                    Some(batch) => format!("{} (batch {})", line, batch),
+                    None => line,
+                };
+                text.line(format!("  {}", output::decorate(result.mark(), line)));
+            }
+        }
+        
+        if let Some(error) = &self.error {
+            text.line(format!("Error:\n{}", error));
         }
     }
-    
-    if let Some(error) = &task_instance.error {
-        println!("Error:\n{}", error);
-    }
-    
-    Ok(())
 }
 
 pub async fn create_task(name: &str, file_path: Option<&str>, config: &Config) -> Result<()> {
@@ -278,7 +448,13 @@ pub async fn create_task(name: &str, file_path: Option<&str>, config: &Config) -
             timeout_seconds: Some(60),
             retry_count: Some(3),
             environment: None,
+            secrets: None,
+            sops_files: None,
             working_directory: None,
+            hosts: None,
+            targets: None,
+            parallelism: None,
+            strategy: None,
         }
     };
     
@@ -307,22 +483,36 @@ async fn load_task_definition(name: &str, config: &Config) -> Result<TaskDefinit
 async fn execute_task_instance(
     instance: &mut TaskInstance,
     definition: &TaskDefinition,
-    _config: &Config,
+    config: &Config,
 ) -> Result<()> {
     instance.status = TaskStatus::Running;
     instance.started_at = Some(Utc::now());
     
-    let result = match &definition.command {
-        TaskCommand::Shell { script } => {
-            execute_shell_command(script, &instance.parameters, definition).await
-        }
-        TaskCommand::System { command, args } => {
-            execute_system_command(command, args, &instance.parameters).await
+    let result = async {
+        // Decrypted inputs only live in this map, never in `instance`.
+        let inputs = load_sops_inputs(definition, config)?;
+        let env = resolve_environment(definition, config).await?;
+        let mut template = instance.parameters.clone();
+        template.extend(inputs);
+
+        let hosts = remote_hosts(definition, config).await?;
+        if !hosts.is_empty() {
+            return execute_remote(&hosts, &template, &env, definition, config, &mut instance.host_results).await;
         }
-        TaskCommand::Module { module, action, params } => {
-            execute_module_command(module, action, params, &instance.parameters).await
+
+        match &definition.command {
+            TaskCommand::Shell { script } => {
+                execute_shell_command(script, &template, &env, definition).await
+            }
+            TaskCommand::System { command, args } => {
+                execute_system_command(command, args, &template, &env).await
+            }
+            TaskCommand::Module { module, action, params } => {
+                execute_module_command(module, action, params, &template, &env, config).await
+            }
         }
-    };
+    }
+    .await;
     
     instance.completed_at = Some(Utc::now());
     
@@ -332,6 +522,12 @@ async fn execute_task_instance(
             instance.output = Some(output);
         }
         Err(e) => {
+            let e = match e {
+                SigilError::TaskExecution { message, exit_code } => {
+                    SigilError::task_execution(redact::redact(&message)).with_exit_code(exit_code)
+                }
+                other => other,
+            };
             instance.status = TaskStatus::Failed;
             instance.error = Some(e.to_string());
             return Err(e);
@@ -341,9 +537,118 @@ async fn execute_task_instance(
     Ok(())
 }
 
+/// Merge `environment` and `secrets` into the variables passed to the
+/// command, resolving any `secret://` references. Secrets win on conflict.
+async fn resolve_environment(definition: &TaskDefinition, config: &Config) -> Result<HashMap<String, String>> {
+    let mut env = HashMap::new();
+    if let Some(vars) = &definition.environment {
+        env.extend(vars.clone());
+    }
+    if let Some(vars) = &definition.secrets {
+        for (name, reference) in vars {
+            if SecretRef::parse(reference).is_none() {
+                return Err(SigilError::task_execution(format!(
+                    "Secret '{}' must be a secret://path#key reference",
+                    name
+                )));
+            }
+            env.insert(name.clone(), reference.clone());
+        }
+    }
+    secrets::resolve_env(config, &env).await
+}
+
+/// Decrypt the definition's `sops_files` into `<name>.<key.path>` template
+/// values. Relative paths are taken from the task's working directory.
+fn load_sops_inputs(definition: &TaskDefinition, config: &Config) -> Result<HashMap<String, String>> {
+    let identity = secrets::file::identity_path(config);
+    let mut inputs = HashMap::new();
+    for (name, path) in definition.sops_files.iter().flatten() {
+        let path = match &definition.working_directory {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.clone(),
+        };
+        let document = secrets::sops::read_file(&path, Some(&identity))?;
+        secrets::sops::flatten(name, &document, &mut inputs);
+    }
+    Ok(inputs)
+}
+
+/// What a finished command wrote, already masked.
+struct Captured {
+    status: ExitStatus,
+    stdout: String,
+    stderr: String,
+}
+
+/// Run a command, passing its output through to the terminal as it arrives
+/// with secret values masked.
+async fn run_streaming(mut command: Command) -> std::io::Result<Captured> {
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = command.spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+
+    let (stdout, stderr, status) = tokio::join!(pump(stdout, false), pump(stderr, true), child.wait());
+    Ok(Captured {
+        status: status?,
+        stdout: stdout?,
+        stderr: stderr?,
+    })
+}
+
+/// Copy one output stream to ours through a `StreamRedactor`, returning
+/// everything that was written.
+async fn pump<R: AsyncRead + Unpin>(mut reader: R, to_stderr: bool) -> std::io::Result<String> {
+    let mut redactor = StreamRedactor::new();
+    let mut captured = String::new();
+    let mut bytes = Vec::new();
+    let mut buffer = [0u8; 8192];
+
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&buffer[..read]);
+        // Keep an incomplete UTF-8 sequence for the next read.
+        let complete = match std::str::from_utf8(&bytes) {
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            _ => bytes.len(),
+        };
+        let text = String::from_utf8_lossy(&bytes[..complete]).into_owned();
+        bytes.drain(..complete);
+        emit(&redactor.push(&text), to_stderr, &mut captured)?;
+    }
+
+    let mut rest = redactor.push(&String::from_utf8_lossy(&bytes));
+    rest.push_str(&redactor.finish());
+    emit(&rest, to_stderr, &mut captured)?;
+    Ok(captured)
+}
+
+fn emit(text: &str, to_stderr: bool, captured: &mut String) -> std::io::Result<()> {
+    if text.is_empty() {
+        return Ok(());
+    }
+    // JSON and YAML results own stdout.
+    if to_stderr || output::is_structured() {
+        let mut stderr = std::io::stderr();
+        stderr.write_all(text.as_bytes())?;
+        stderr.flush()?;
+    } else {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()?;
+    }
+    captured.push_str(text);
+    Ok(())
+}
+
 async fn execute_shell_command(
     script: &str,
     parameters: &HashMap<String, String>,
+    env: &HashMap<String, String>,
     definition: &TaskDefinition,
 ) -> Result<String> {
     // Substitute parameters in script
@@ -355,24 +660,19 @@ async fn execute_shell_command(
     let mut command = Command::new("bash");
     command.arg("-c").arg(&expanded_script);
     
-    if let Some(env) = &definition.environment {
-        for (key, value) in env {
-            command.env(key, value);
-        }
-    }
+    command.envs(env);
     
     if let Some(work_dir) = &definition.working_directory {
         command.current_dir(work_dir);
     }
     
-    let output = command.output()
+    let output = run_streaming(command).await
         .map_err(|e| SigilError::task_execution(format!("Failed to execute shell command: {}", e)))?;
     
     if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        Ok(output.stdout)
     } else {
-        let error = String::from_utf8_lossy(&output.stderr);
-        Err(SigilError::task_execution(format!("Shell command failed: {}", error)))
+        Err(SigilError::task_execution(format!("Shell command failed: {}", output.stderr)).with_exit_status(output.status))
     }
 }
 
@@ -380,6 +680,7 @@ async fn execute_system_command(
     command: &str,
     args: &[String],
     parameters: &HashMap<String, String>,
+    env: &HashMap<String, String>,
 ) -> Result<String> {
     // Substitute parameters in command and args
     let mut expanded_args = Vec::new();
@@ -391,28 +692,158 @@ async fn execute_system_command(
         expanded_args.push(expanded_arg);
     }
     
-    let output = Command::new(command)
-        .args(&expanded_args)
-        .output()
+    let mut command = Command::new(command);
+    command.args(&expanded_args).envs(env);
+    let output = run_streaming(command).await
         .map_err(|e| SigilError::task_execution(format!("Failed to execute system command: {}", e)))?;
     
     if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        Ok(output.stdout)
     } else {
-        let error = String::from_utf8_lossy(&output.stderr);
-        Err(SigilError::task_execution(format!("System command failed: {}", error)))
+        Err(SigilError::task_execution(format!("System command failed: {}", output.stderr)).with_exit_status(output.status))
     }
 }
 
+/// Hosts the task runs on: its `hosts`, or inventory hosts matching its
+/// `targets`. Empty means run locally.
+async fn remote_hosts(definition: &TaskDefinition, config: &Config) -> Result<Vec<Host>> {
+    let hosts = definition.hosts.as_deref().unwrap_or_default();
+    match &definition.targets {
+        Some(_) if !hosts.is_empty() => Err(SigilError::task_execution(
+            "Task sets both 'hosts' and 'targets'; use one",
+        )),
+        Some(expression) => Inventory::load(config).await?.targets(expression),
+        None => Ok(hosts.iter().map(|host| Host::named(host)).collect()),
+    }
+}
+
+/// Run the task's command on each host over SSH. Output is streamed per
+/// host; the task fails if any host does.
+async fn execute_remote(
+    hosts: &[Host],
+    parameters: &HashMap<String, String>,
+    env: &HashMap<String, String>,
+    definition: &TaskDefinition,
+    config: &Config,
+    host_results: &mut Vec<HostResult>,
+) -> Result<String> {
+    let working_directory = definition.working_directory.as_deref();
+    let mut jobs = Vec::new();
+    for host in hosts {
+        // Inventory variables first, so task parameters win.
+        let mut values = host.template_vars();
+        values.extend(parameters.iter().map(|(key, value)| (key.clone(), value.clone())));
+        let remote = match &definition.command {
+            TaskCommand::Shell { script } => {
+                RemoteCommand::shell(&expand(script, &values), env, working_directory)
+            }
+            TaskCommand::System { command, args } => {
+                let args: Vec<String> = args.iter().map(|arg| expand(arg, &values)).collect();
+                RemoteCommand::system(command, &args, env, working_directory)
+            }
+            TaskCommand::Module { module, .. } => {
+                return Err(SigilError::task_execution(format!(
+                    "Module '{}' steps run locally and cannot target hosts",
+                    module
+                )));
+            }
+        };
+        jobs.push(host.job(remote));
+    }
+
+    info!("🌐 Running on {} host(s)", hosts.len());
+    let runner = Arc::new(SshRunner::new(config, definition.parallelism));
+    let strategy = definition.strategy.clone().unwrap_or_default();
+    let results = strategy::roll_out(&runner, jobs, &strategy).await?;
+    let failed = ssh::summarize(&results);
+
+    let output = results
+        .iter()
+        .flat_map(|result| result.stdout.lines().map(move |line| format!("[{}] {}\n", result.host, line)))
+        .collect();
+    *host_results = results;
+
+    if failed == 0 {
+        return Ok(output);
+    }
+    let failures: Vec<String> = host_results
+        .iter()
+        .filter(|result| result.failed())
+        .map(|result| format!("{} ({})", result.host, result.describe()))
+        .collect();
+    let skipped = host_results.iter().filter(|result| result.skipped).count();
+    let mut message = format!("{} of {} hosts failed: {}", failed, host_results.len(), failures.join(", "));
+    if skipped > 0 {
+        message.push_str(&format!("; {} host(s) skipped", skipped));
+    }
+    Err(SigilError::task_execution(message).with_exit_code(ssh::common_exit_code(host_results)))
+}
+
+fn expand(text: &str, parameters: &HashMap<String, String>) -> String {
+    let mut expanded = text.to_string();
+    for (key, value) in parameters {
+        expanded = expanded.replace(&format!("${{{}}}", key), value);
+    }
+    expanded
+}
+
+/// Run a module action as a task step. Parameter values are expanded like
+/// shell scripts; the resolved environment goes to the module.
 async fn execute_module_command(
     module: &str,
     action: &str,
     params: &HashMap<String, String>,
-    _user_params: &HashMap<String, String>,
+    parameters: &HashMap<String, String>,
+    env: &HashMap<String, String>,
+    config: &Config,
 ) -> Result<String> {
-    // This would integrate with the module system
-    // For now, return a placeholder
-    Ok(format!("Module command executed: {} {} with params: {:?}", module, action, params))
+    let mut expanded = HashMap::new();
+    for (name, value) in params {
+        let mut value = value.clone();
+        for (key, parameter) in parameters {
+            value = value.replace(&format!("${{{}}}", key), parameter);
+        }
+        expanded.insert(name.clone(), value);
+    }
+
+    match (module, action) {
+        ("container", "run") => execute_container(&expanded, env, config).await,
+        _ => Err(SigilError::task_execution(format!(
+            "Module '{}' has no task action '{}'",
+            module, action
+        ))),
+    }
+}
+
+/// Run a one-off container, streaming its output like a shell command's.
+async fn execute_container(
+    params: &HashMap<String, String>,
+    env: &HashMap<String, String>,
+    config: &Config,
+) -> Result<String> {
+    let spec = container::RunSpec::from_params(params, env)?;
+    let client = container::ContainerClient::connect(config)?;
+
+    let (stdout_writer, stdout_reader) = tokio::io::duplex(8192);
+    let (stderr_writer, stderr_reader) = tokio::io::duplex(8192);
+    let (exit, stdout, stderr) = tokio::join!(
+        client.run(&spec, stdout_writer, stderr_writer),
+        pump(stdout_reader, false),
+        pump(stderr_reader, true)
+    );
+    let stream_error = |e: std::io::Error| SigilError::task_execution(format!("Failed to read container output: {}", e));
+    let (stdout, stderr) = (stdout.map_err(stream_error)?, stderr.map_err(stream_error)?);
+
+    match exit? {
+        0 => Ok(stdout),
+        // Engines report -1 when the status is unknown; only real exit
+        // codes are passed on.
+        code => Err(SigilError::task_execution(format!(
+            "Container {} exited with status {}: {}",
+            spec.image, code, stderr
+        ))
+        .with_exit_code(u8::try_from(code).ok().map(i32::from))),
+    }
 }
 
 fn parse_parameters(params: &[String]) -> Result<HashMap<String, String>> {
@@ -434,28 +865,32 @@ fn validate_parameters(
     parameters: &HashMap<String, String>,
 ) -> Result<()> {
     for (param_name, param_def) in &definition.parameters {
-        if param_def.required && !parameters.contains_key(param_name) {
-            if param_def.default_value.is_none() {
-                return Err(SigilError::task_execution(format!("Required parameter '{}' not provided", param_name)));
-            }
+        if param_def.required
+            && !parameters.contains_key(param_name)
+            && param_def.default_value.is_none()
+        {
+            return Err(SigilError::task_execution(format!("Required parameter '{}' not provided", param_name)));
         }
     }
     
     Ok(())
 }
 
-async fn save_task_instance(instance: &TaskInstance, config: &Config) -> Result<()> {
+pub async fn save_task_instance(instance: &TaskInstance, config: &Config) -> Result<()> {
     let state_dir = &config.tasks.state_dir;
     fs::create_dir_all(state_dir).await?;
     
+    // Output is masked as it streams; this also covers parameters and errors.
+    let instance = instance.redacted();
+
     let instance_file = state_dir.join(format!("{}.json", instance.id));
-    let content = serde_json::to_string_pretty(instance)?;
+    let content = serde_json::to_string_pretty(&instance)?;
     fs::write(&instance_file, content).await?;
     
     Ok(())
 }
 
-async fn load_task_instance_by_id(id: &Uuid, config: &Config) -> Result<TaskInstance> {
+pub async fn load_task_instance_by_id(id: &Uuid, config: &Config) -> Result<TaskInstance> {
     let instance_file = config.tasks.state_dir.join(format!("{}.json", id));
     
     if !instance_file.exists() {
@@ -469,34 +904,33 @@ async fn load_task_instance_by_id(id: &Uuid, config: &Config) -> Result<TaskInst
 }
 
 async fn find_latest_task_instance_by_name(name: &str, config: &Config) -> Result<TaskInstance> {
+    task_instances(config)
+        .await?
+        .into_iter()
+        .find(|instance| instance.definition_name == name)
+        .ok_or_else(|| SigilError::resource_not_found(format!("No task instances found for: {}", name)))
+}
+
+/// Every saved instance in `tasks.state_dir`, newest first. Files that do
+/// not parse are skipped.
+pub async fn task_instances(config: &Config) -> Result<Vec<TaskInstance>> {
     let state_dir = &config.tasks.state_dir;
-    
     if !state_dir.exists() {
-        return Err(SigilError::resource_not_found(format!("No task instances found for: {}", name)));
+        return Ok(Vec::new());
     }
-    
+
+    let mut instances = Vec::new();
     let mut entries = fs::read_dir(state_dir).await?;
-    let mut latest_instance: Option<TaskInstance> = None;
-    
     while let Some(entry) = entries.next_entry().await? {
         let path = entry.path();
         if path.extension().and_then(|s| s.to_str()) == Some("json") {
             if let Ok(content) = fs::read_to_string(&path).await {
                 if let Ok(instance) = serde_json::from_str::<TaskInstance>(&content) {
-                    if instance.definition_name == name {
-                        match &latest_instance {
-                            None => latest_instance = Some(instance),
-                            Some(current) => {
-                                if instance.created_at > current.created_at {
-                                    latest_instance = Some(instance);
-                                }
-                            }
-                        }
-                    }
+                    instances.push(instance);
                 }
             }
         }
     }
-    
-    latest_instance.ok_or_else(|| SigilError::resource_not_found(format!("No task instances found for: {}", name)))
+    instances.sort_by_key(|instance| std::cmp::Reverse(instance.created_at));
+    Ok(instances)
 }
@@ -0,0 +1,152 @@
+use crate::error::{Result, SigilError};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+/// What unlocks the secrets file: a passphrase or an age identity.
+/// Held in memory by the agent and handed to commands over its socket.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "secret", rename_all = "lowercase")]
+pub enum Credential {
+    Passphrase(String),
+    Identity(String),
+}
+
+const REQUEST_GET: &str = "get";
+const REQUEST_STOP: &str = "stop";
+
+/// Agent socket for a secrets file, one per file so several vaults can be
+/// unlocked side by side. Lives in `$XDG_RUNTIME_DIR` when available,
+/// otherwise next to the file itself.
+pub fn socket_path(vault: &Path) -> PathBuf {
+    let vault = vault.canonicalize().unwrap_or_else(|_| vault.to_path_buf());
+    let mut hasher = DefaultHasher::new();
+    vault.hash(&mut hasher);
+    let name = format!("sigil-agent-{:016x}.sock", hasher.finish());
+
+    dirs::runtime_dir()
+        .or_else(|| vault.parent().map(Path::to_path_buf))
+        .unwrap_or_else(std::env::temp_dir)
+        .join(name)
+}
+
+/// Ask a running agent for its credential; `None` if no agent answers.
+pub async fn fetch(socket: &Path) -> Option<Credential> {
+    let reply = request(socket, REQUEST_GET).await.ok()?;
+    serde_json::from_str(&reply).ok()
+}
+
+/// Stop a running agent. Returns whether one was running.
+pub async fn stop(socket: &Path) -> bool {
+    request(socket, REQUEST_STOP).await.is_ok()
+}
+
+async fn request(socket: &Path, request: &str) -> std::io::Result<String> {
+    let mut stream = UnixStream::connect(socket).await?;
+    stream.write_all(format!("{}\n", request).as_bytes()).await?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).await?;
+    Ok(reply)
+}
+
+/// Start a detached agent holding `credential` for `ttl`. The credential is
+/// passed over a pipe, never on the command line.
+pub async fn spawn(socket: &Path, ttl: Duration, credential: &Credential) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    stop(socket).await;
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .args(["secret", "agent", "--socket"])
+        .arg(socket)
+        .args(["--ttl", &ttl.as_secs().to_string()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Own process group, so Ctrl-C in the shell doesn't take it down.
+        .process_group(0);
+    let mut child = tokio::process::Command::from(command).spawn()?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin
+        .write_all(format!("{}\n", serde_json::to_string(credential)?).as_bytes())
+        .await?;
+    drop(stdin);
+
+    // Wait until the agent answers so the next command can rely on it.
+    for _ in 0..50 {
+        if fetch(socket).await.is_some() {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    Err(SigilError::Secret(format!("Agent did not start on {}", socket.display())))
+}
+
+/// Run the agent: read the credential from stdin, then serve it to
+/// processes of the same user until `ttl` expires or it is told to stop.
+pub async fn serve(socket: &Path, ttl: Duration) -> Result<()> {
+    let mut line = String::new();
+    BufReader::new(tokio::io::stdin()).read_line(&mut line).await?;
+    let credential: Credential = serde_json::from_str(line.trim())
+        .map_err(|e| SigilError::Secret(format!("Agent received no credential: {}", e)))?;
+
+    if let Some(parent) = socket.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let _ = tokio::fs::remove_file(socket).await;
+    let listener = UnixListener::bind(socket)?;
+    tokio::fs::set_permissions(socket, std::fs::Permissions::from_mode(0o600)).await?;
+    let owner = tokio::fs::metadata(socket).await?.uid();
+
+    let reply = format!("{}\n", serde_json::to_string(&credential)?);
+    let expiry = tokio::time::sleep(ttl);
+    tokio::pin!(expiry);
+
+    loop {
+        let mut stream = tokio::select! {
+            _ = &mut expiry => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(_) => continue,
+            },
+        };
+        // The socket is 0600 already; refuse other users regardless.
+        if !matches!(stream.peer_cred(), Ok(peer) if peer.uid() == owner) {
+            continue;
+        }
+
+        let mut request = String::new();
+        let mut reader = BufReader::new(&mut stream);
+        // Requests are handled one at a time; don't let a silent client stall it.
+        if !matches!(
+            tokio::time::timeout(Duration::from_secs(1), reader.read_line(&mut request)).await,
+            Ok(Ok(_))
+        ) {
+            continue;
+        }
+        match request.trim() {
+            REQUEST_GET => {
+                let _ = stream.write_all(reply.as_bytes()).await;
+            }
+            REQUEST_STOP => {
+                // Remove the socket before answering so a replacement agent
+                // can bind it as soon as `stop` returns.
+                let _ = tokio::fs::remove_file(socket).await;
+                let _ = stream.write_all(b"ok\n").await;
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+
+    let _ = tokio::fs::remove_file(socket).await;
+    Ok(())
+}
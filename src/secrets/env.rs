@@ -0,0 +1,79 @@
+use super::{Secret, SecretStore, DEFAULT_FIELD};
+use crate::error::{Result, SigilError};
+use async_trait::async_trait;
+
+/// Prefix for secrets supplied through the environment:
+/// `secret://db/password` reads `SIGIL_SECRET_DB_PASSWORD`.
+pub const ENV_SECRET_PREFIX: &str = "SIGIL_SECRET_";
+
+/// Read-only backend over process environment variables.
+#[derive(Debug, Default)]
+pub struct EnvStore;
+
+impl EnvStore {
+    pub fn new() -> Self {
+        EnvStore
+    }
+
+    /// Environment variable holding a secret path (and optional field).
+    pub fn var_name(path: &str) -> String {
+        let normalized: String = path
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+            .collect();
+        format!("{}{}", ENV_SECRET_PREFIX, normalized)
+    }
+
+    fn read_only(&self, operation: &str) -> SigilError {
+        SigilError::Secret(format!(
+            "The env backend is read-only; export {}<PATH> instead of using '{}'",
+            ENV_SECRET_PREFIX, operation
+        ))
+    }
+}
+
+#[async_trait]
+impl SecretStore for EnvStore {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    async fn get(&self, path: &str) -> Result<Option<Secret>> {
+        // Fields can be provided individually as `<VAR>__<FIELD>`.
+        let base = Self::var_name(path);
+        let field_prefix = format!("{}__", base);
+        let mut secret = Secret::new();
+        for (name, value) in std::env::vars() {
+            if name == base {
+                secret.insert(DEFAULT_FIELD.to_string(), value);
+            } else if let Some(field) = name.strip_prefix(&field_prefix) {
+                secret.insert(field.to_ascii_lowercase(), value);
+            }
+        }
+        Ok(Some(secret).filter(|secret| !secret.is_empty()))
+    }
+
+    async fn set(&self, _path: &str, _key: &str, _value: &str) -> Result<()> {
+        Err(self.read_only("set"))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let prefix = Self::var_name(prefix);
+        let mut names: Vec<String> = std::env::vars()
+            .map(|(name, _)| name)
+            .filter(|name| name.starts_with(&prefix))
+            .filter_map(|name| {
+                let path = name.strip_prefix(ENV_SECRET_PREFIX)?;
+                let path = path.split("__").next()?;
+                Some(path.to_ascii_lowercase())
+            })
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    async fn delete(&self, _path: &str) -> Result<bool> {
+        Err(self.read_only("delete"))
+    }
+}
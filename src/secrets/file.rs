@@ -0,0 +1,368 @@
+use super::agent::{self, Credential};
+use super::{Secret, SecretStore};
+use crate::config::Config;
+use crate::error::{Result, SigilError};
+use age::secrecy::{ExposeSecret, SecretString};
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Passphrase for the secrets file when no terminal is available.
+pub const PASSPHRASE_ENV: &str = "SIGIL_SECRETS_PASSPHRASE";
+
+/// age identity (`AGE-SECRET-KEY-...`) for the secrets file, e.g. in CI.
+pub const IDENTITY_ENV: &str = "SIGIL_SECRETS_IDENTITY";
+
+/// Default file name under `general.data_dir`.
+pub const DEFAULT_FILE_NAME: &str = "secrets.age";
+
+/// Default identity file name under `general.config_dir`.
+pub const DEFAULT_IDENTITY_NAME: &str = "age-identity.txt";
+
+type Vault = BTreeMap<String, Secret>;
+
+/// Secrets kept in a single age-encrypted JSON document on disk, encrypted
+/// either to a set of X25519 recipients or with a passphrase.
+pub struct FileStore {
+    path: PathBuf,
+    recipients: Vec<String>,
+    identity_file: PathBuf,
+    unlock_ttl: Duration,
+    credential: Mutex<Option<Credential>>,
+}
+
+impl FileStore {
+    pub fn from_config(config: &Config) -> Self {
+        let path = config
+            .secrets
+            .file_path
+            .clone()
+            .unwrap_or_else(|| config.general.data_dir.join(DEFAULT_FILE_NAME));
+        FileStore {
+            path,
+            recipients: config.secrets.file_recipients.clone(),
+            identity_file: identity_path(config),
+            unlock_ttl: Duration::from_secs(config.secrets.unlock_ttl_seconds),
+            credential: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn cached(&self) -> Option<Credential> {
+        self.credential.lock().expect("credential lock poisoned").clone()
+    }
+
+    fn remember(&self, credential: Credential) {
+        *self.credential.lock().expect("credential lock poisoned") = Some(credential);
+    }
+
+    /// Passphrase from the environment, a running agent, or a prompt.
+    async fn passphrase(&self, confirm: bool) -> Result<SecretString> {
+        if let Some(Credential::Passphrase(passphrase)) = self.cached() {
+            return Ok(passphrase.into());
+        }
+
+        let passphrase = match std::env::var(PASSPHRASE_ENV) {
+            Ok(value) => value,
+            Err(_) => match agent::fetch(&agent::socket_path(&self.path)).await {
+                Some(Credential::Passphrase(value)) => value,
+                _ => prompt_passphrase(&format!("Passphrase for {}: ", self.path.display()), confirm)?,
+            },
+        };
+        if passphrase.is_empty() {
+            return Err(SigilError::Secret("Passphrase must not be empty".to_string()));
+        }
+
+        self.remember(Credential::Passphrase(passphrase.clone()));
+        Ok(passphrase.into())
+    }
+
+    /// X25519 identities from the environment, a running agent, or the
+    /// identity file.
+    async fn identities(&self) -> Result<Vec<age::x25519::Identity>> {
+        let text = match self.cached() {
+            Some(Credential::Identity(text)) => text,
+            _ => match std::env::var(IDENTITY_ENV) {
+                Ok(value) => value,
+                Err(_) => match agent::fetch(&agent::socket_path(&self.path)).await {
+                    Some(Credential::Identity(value)) => value,
+                    _ => std::fs::read_to_string(&self.identity_file).map_err(|e| {
+                        SigilError::Secret(format!(
+                            "Cannot read age identity {}: {} (run `sigil secret keygen` or set {})",
+                            self.identity_file.display(),
+                            e,
+                            IDENTITY_ENV
+                        ))
+                    })?,
+                },
+            },
+        };
+
+        let identities = parse_identities(&text)?;
+        self.remember(Credential::Identity(text));
+        Ok(identities)
+    }
+
+    fn parsed_recipients(&self) -> Result<Vec<age::x25519::Recipient>> {
+        self.recipients
+            .iter()
+            .map(|recipient| {
+                recipient.parse().map_err(|_| {
+                    SigilError::invalid_config(
+                        "secrets.file_recipients".to_string(),
+                        format!("'{}' is not an age X25519 public key", recipient),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    async fn load(&self) -> Result<Vault> {
+        let ciphertext = match tokio::fs::read(&self.path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vault::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let plaintext = self.decrypt(&ciphertext).await?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    async fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let fail = |e: &dyn std::fmt::Display| SigilError::Secret(format!("Cannot decrypt {}: {}", self.path.display(), e));
+
+        // The header says how the file was encrypted, whatever the config says now.
+        let decryptor = age::Decryptor::new(ciphertext).map_err(|e| fail(&e))?;
+        let mut reader = if decryptor.is_scrypt() {
+            let identity = age::scrypt::Identity::new(self.passphrase(false).await?);
+            decryptor
+                .decrypt(std::iter::once(&identity as &dyn age::Identity))
+                .map_err(|e| fail(&e))?
+        } else {
+            let identities = self.identities().await?;
+            decryptor
+                .decrypt(identities.iter().map(|identity| identity as &dyn age::Identity))
+                .map_err(|e| fail(&e))?
+        };
+
+        let mut plaintext = Vec::new();
+        reader.read_to_end(&mut plaintext).map_err(|e| fail(&e))?;
+        Ok(plaintext)
+    }
+
+    async fn store(&self, vault: &Vault) -> Result<()> {
+        let plaintext = serde_json::to_vec(vault)?;
+        let ciphertext = self.encrypt(&plaintext).await?;
+        write_private(&self.path, &ciphertext).await
+    }
+
+    /// Encrypt to `secrets.file_recipients`, or with the passphrase if none
+    /// are configured. Every write uses a fresh file key.
+    async fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let fail = |e: &dyn std::fmt::Display| SigilError::Secret(format!("Cannot encrypt {}: {}", self.path.display(), e));
+
+        if self.recipients.is_empty() {
+            let confirm = !self.path.exists();
+            let recipient = age::scrypt::Recipient::new(self.passphrase(confirm).await?);
+            return age::encrypt(&recipient, plaintext).map_err(|e| fail(&e));
+        }
+
+        let recipients = self.parsed_recipients()?;
+        self.check_own_recipient(&recipients).await?;
+
+        let encryptor = age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+            .map_err(|e| fail(&e))?;
+        let mut ciphertext = Vec::new();
+        let mut writer = encryptor.wrap_output(&mut ciphertext).map_err(|e| fail(&e))?;
+        writer.write_all(plaintext).map_err(|e| fail(&e))?;
+        writer.finish().map_err(|e| fail(&e))?;
+        Ok(ciphertext)
+    }
+
+    /// Refuse to write a file our own identity could no longer open.
+    async fn check_own_recipient(&self, recipients: &[age::x25519::Recipient]) -> Result<()> {
+        let Ok(identities) = self.identities().await else {
+            return Ok(());
+        };
+        let ours: Vec<String> = identities.iter().map(|i| i.to_public().to_string()).collect();
+        if recipients.iter().any(|r| ours.contains(&r.to_string())) {
+            return Ok(());
+        }
+        Err(SigilError::Secret(format!(
+            "Your identity ({}) is not in secrets.file_recipients; add it before writing {}",
+            ours.join(", "),
+            self.path.display()
+        )))
+    }
+
+    /// Re-encrypt the file with a fresh key to the current recipients, or
+    /// with a new passphrase. Returns the number of secrets rewritten.
+    pub async fn rotate(&self, new_passphrase: bool) -> Result<usize> {
+        let vault = self.load().await?;
+        if new_passphrase {
+            if !self.recipients.is_empty() {
+                return Err(SigilError::Secret(
+                    "The file is encrypted to secrets.file_recipients, not a passphrase".to_string(),
+                ));
+            }
+            let passphrase = prompt_passphrase("New passphrase: ", true)?;
+            self.remember(Credential::Passphrase(passphrase));
+        }
+        self.store(&vault).await?;
+        // A cached credential may no longer open the file.
+        agent::stop(&agent::socket_path(&self.path)).await;
+        Ok(vault.len())
+    }
+
+    /// Unlock the file once and hand the credential to a background agent.
+    pub async fn unlock(&self, ttl: Option<Duration>) -> Result<Duration> {
+        let socket = agent::socket_path(&self.path);
+        agent::stop(&socket).await;
+
+        // Decrypting proves the credential; a new file just needs one to exist.
+        self.load().await?;
+        if self.cached().is_none() {
+            if self.recipients.is_empty() {
+                self.passphrase(true).await?;
+            } else {
+                self.identities().await?;
+            }
+        }
+
+        let ttl = ttl.unwrap_or(self.unlock_ttl);
+        let credential = self.cached().expect("credential resolved above");
+        agent::spawn(&socket, ttl, &credential).await?;
+        Ok(ttl)
+    }
+
+    /// Stop the agent for this file. Returns whether one was running.
+    pub async fn lock(&self) -> bool {
+        agent::stop(&agent::socket_path(&self.path)).await
+    }
+}
+
+/// Identity file from `secrets.file_identity`, else under `config_dir`.
+pub fn identity_path(config: &Config) -> PathBuf {
+    config
+        .secrets
+        .file_identity
+        .clone()
+        .unwrap_or_else(|| config.general.config_dir.join(DEFAULT_IDENTITY_NAME))
+}
+
+/// Generate a new X25519 identity in age's key file format. Returns the file
+/// contents and the public key to add to `secrets.file_recipients`.
+pub fn generate_identity() -> (String, String) {
+    let identity = age::x25519::Identity::generate();
+    let public = identity.to_public().to_string();
+    let contents = format!(
+        "# created: {}\n# public key: {}\n{}\n",
+        chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        public,
+        identity.to_string().expose_secret()
+    );
+    (contents, public)
+}
+
+/// Parse an age key file: one `AGE-SECRET-KEY-` per line, `#` comments.
+pub fn parse_identities(text: &str) -> Result<Vec<age::x25519::Identity>> {
+    let identities = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.parse::<age::x25519::Identity>()
+                .map_err(|e| SigilError::Secret(format!("Invalid age identity: {}", e)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if identities.is_empty() {
+        return Err(SigilError::Secret("No age identities found".to_string()));
+    }
+    Ok(identities)
+}
+
+fn prompt_passphrase(prompt: &str, confirm: bool) -> Result<String> {
+    if !std::io::stdin().is_terminal() {
+        return Err(SigilError::Secret(format!(
+            "No passphrase available; set {}, run `sigil secret unlock`, or run interactively",
+            PASSPHRASE_ENV
+        )));
+    }
+    let value = rpassword::prompt_password(prompt)?;
+    if confirm && rpassword::prompt_password("Confirm passphrase: ")? != value {
+        return Err(SigilError::Secret("Passphrases do not match".to_string()));
+    }
+    Ok(value)
+}
+
+/// Write a file readable only by the owner, replacing it atomically. The
+/// temporary file is owner-only from the start and named uniquely, so
+/// concurrent writers never share it.
+pub async fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", name, uuid::Uuid::new_v4()));
+
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let written = async {
+        let mut file = options.open(&tmp).await?;
+        file.write_all(data).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp, path).await
+    }
+    .await;
+    if written.is_err() {
+        let _ = tokio::fs::remove_file(&tmp).await;
+    }
+    Ok(written?)
+}
+
+#[async_trait]
+impl SecretStore for FileStore {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn get(&self, path: &str) -> Result<Option<Secret>> {
+        Ok(self.load().await?.remove(path))
+    }
+
+    async fn set(&self, path: &str, key: &str, value: &str) -> Result<()> {
+        let mut vault = self.load().await?;
+        vault
+            .entry(path.to_string())
+            .or_default()
+            .insert(key.to_string(), value.to_string());
+        self.store(&vault).await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .load()
+            .await?
+            .into_keys()
+            .filter(|path| path.starts_with(prefix))
+            .collect())
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool> {
+        let mut vault = self.load().await?;
+        let existed = vault.remove(path).is_some();
+        if existed {
+            self.store(&vault).await?;
+        }
+        Ok(existed)
+    }
+}
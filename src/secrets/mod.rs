@@ -0,0 +1,293 @@
+use crate::cli::SecretCommands;
+use crate::config::Config;
+use crate::error::{Result, SigilError};
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{IsTerminal, Read};
+use std::time::Duration;
+
+pub mod agent;
+pub mod env;
+pub mod file;
+pub mod redact;
+pub mod sops;
+pub mod vault;
+
+/// Scheme prefix for secret references in task definitions and config.
+pub const SECRET_SCHEME: &str = "secret://";
+
+/// A secret is a small set of named fields, mirroring Vault's KV model.
+/// Single-value secrets use the `value` field.
+pub type Secret = BTreeMap<String, String>;
+
+/// Field used when a reference names no key and the secret has several.
+pub const DEFAULT_FIELD: &str = "value";
+
+/// A place secrets can be read from and (for writable backends) stored in.
+#[async_trait]
+pub trait SecretStore: Send + Sync {
+    /// Backend name as used in `secrets.backend`.
+    fn name(&self) -> &'static str;
+
+    /// Fetch all fields of a secret, or `None` if it does not exist.
+    async fn get(&self, path: &str) -> Result<Option<Secret>>;
+
+    /// Set one field of a secret, creating the secret if needed.
+    async fn set(&self, path: &str, key: &str, value: &str) -> Result<()>;
+
+    /// List secret paths below `prefix`.
+    async fn list(&self, prefix: &str) -> Result<Vec<String>>;
+
+    /// Delete a secret entirely. Returns whether it existed.
+    async fn delete(&self, path: &str) -> Result<bool>;
+}
+
+/// `secret://path/to/secret#key`, resolved against the configured backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecretRef {
+    pub path: String,
+    pub key: Option<String>,
+}
+
+impl SecretRef {
+    /// Parse a `secret://` reference; returns `None` for plain values.
+    pub fn parse(value: &str) -> Option<Self> {
+        let rest = value.strip_prefix(SECRET_SCHEME)?;
+        let (path, key) = match rest.split_once('#') {
+            Some((path, key)) => (path, Some(key.to_string()).filter(|key| !key.is_empty())),
+            None => (rest, None),
+        };
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            return None;
+        }
+        Some(SecretRef {
+            path: path.to_string(),
+            key,
+        })
+    }
+
+    /// Accept either a full `secret://` reference or a bare path.
+    pub fn from_arg(value: &str, key: Option<&str>) -> Self {
+        let mut reference = SecretRef::parse(value).unwrap_or_else(|| SecretRef {
+            path: value.trim_matches('/').to_string(),
+            key: None,
+        });
+        if let Some(key) = key {
+            reference.key = Some(key.to_string());
+        }
+        reference
+    }
+}
+
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", SECRET_SCHEME, self.path)?;
+        if let Some(key) = &self.key {
+            write!(f, "#{}", key)?;
+        }
+        Ok(())
+    }
+}
+
+/// Open the backend selected by `secrets.backend`.
+pub fn open_store(config: &Config) -> Result<Box<dyn SecretStore>> {
+    match config.secrets.backend.as_str() {
+        "env" => Ok(Box::new(env::EnvStore::new())),
+        "file" => Ok(Box::new(file::FileStore::from_config(config))),
+        "vault" => Ok(Box::new(vault::VaultStore::from_config(&config.secrets)?)),
+        other => Err(SigilError::invalid_config(
+            "secrets.backend".to_string(),
+            format!("Unknown backend '{}'", other),
+        )),
+    }
+}
+
+/// Resolve a reference to a single string value.
+pub async fn resolve(store: &dyn SecretStore, reference: &SecretRef) -> Result<String> {
+    let secret = store
+        .get(&reference.path)
+        .await?
+        .ok_or_else(|| SigilError::resource_not_found(format!("Secret: {}", reference)))?;
+    let value = pick_field(&secret, reference)?;
+    redact::register(&value);
+    Ok(value)
+}
+
+fn pick_field(secret: &Secret, reference: &SecretRef) -> Result<String> {
+    let field = match &reference.key {
+        Some(key) => secret.get(key),
+        None if secret.len() == 1 => secret.values().next(),
+        None => secret.get(DEFAULT_FIELD),
+    };
+    field.cloned().ok_or_else(|| {
+        SigilError::Secret(format!(
+            "{} has no field {} (available: {})",
+            reference,
+            reference.key.as_deref().unwrap_or(DEFAULT_FIELD),
+            secret.keys().cloned().collect::<Vec<_>>().join(", ")
+        ))
+    })
+}
+
+/// Resolve every `secret://` value in an environment map, leaving plain
+/// values untouched. The store is only opened if a reference is present.
+pub async fn resolve_env(config: &Config, vars: &HashMap<String, String>) -> Result<HashMap<String, String>> {
+    let mut store: Option<Box<dyn SecretStore>> = None;
+    let mut resolved = HashMap::with_capacity(vars.len());
+
+    for (name, value) in vars {
+        let value = match SecretRef::parse(value) {
+            Some(reference) => {
+                if store.is_none() {
+                    store = Some(open_store(config)?);
+                }
+                let store = store.as_deref().expect("opened above");
+                resolve(store, &reference).await?
+            }
+            None => value.clone(),
+        };
+        resolved.insert(name.clone(), value);
+    }
+
+    Ok(resolved)
+}
+
+pub async fn handle_command(cmd: &SecretCommands, config: &Config) -> Result<()> {
+    if matches!(
+        cmd,
+        SecretCommands::Keygen { .. } | SecretCommands::Rotate { .. } | SecretCommands::Unlock { .. } | SecretCommands::Lock
+    ) {
+        return handle_file_command(cmd, config).await;
+    }
+
+    let store = open_store(config)?;
+
+    match cmd {
+        SecretCommands::Get { path, key } => {
+            let reference = SecretRef::from_arg(path, key.as_deref());
+            if reference.key.is_some() {
+                println!("{}", resolve(store.as_ref(), &reference).await?);
+            } else {
+                let secret = store
+                    .get(&reference.path)
+                    .await?
+                    .ok_or_else(|| SigilError::resource_not_found(format!("Secret: {}", reference)))?;
+                match pick_field(&secret, &reference) {
+                    Ok(value) if secret.len() == 1 => println!("{}", value),
+                    _ => {
+                        for (field, value) in &secret {
+                            println!("{}={}", field, value);
+                        }
+                    }
+                }
+            }
+        }
+        SecretCommands::Set { path, key, value } => {
+            let reference = SecretRef::from_arg(path, key.as_deref());
+            let key = reference.key.as_deref().unwrap_or(DEFAULT_FIELD);
+            let value = match value {
+                Some(value) => value.clone(),
+                None => read_value(&reference)?,
+            };
+            store.set(&reference.path, key, &value).await?;
+            println!(
+                "✅ Stored {} in {} backend",
+                SecretRef {
+                    path: reference.path.clone(),
+                    key: Some(key.to_string()),
+                },
+                store.name()
+            );
+        }
+        SecretCommands::List { prefix } => {
+            let prefix = prefix.as_deref().map(|p| SecretRef::from_arg(p, None).path).unwrap_or_default();
+            let paths = store.list(&prefix).await?;
+            if paths.is_empty() {
+                println!("No secrets found in {} backend", store.name());
+            }
+            for path in paths {
+                println!("{}", path);
+            }
+        }
+        SecretCommands::Delete { path } => {
+            let reference = SecretRef::from_arg(path, None);
+            if store.delete(&reference.path).await? {
+                println!("🗑️  Deleted {}", reference);
+            } else {
+                return Err(SigilError::resource_not_found(format!("Secret: {}", reference)));
+            }
+        }
+        // The agent is started from main without loading config.
+        SecretCommands::Agent { .. } => unreachable!("dispatched in main"),
+        _ => unreachable!("handled by handle_file_command"),
+    }
+
+    Ok(())
+}
+
+/// Commands that manage the encrypted file backend itself.
+async fn handle_file_command(cmd: &SecretCommands, config: &Config) -> Result<()> {
+    if let SecretCommands::Keygen { file, force } = cmd {
+        let path = file.clone().unwrap_or_else(|| file::identity_path(config));
+        if path.exists() && !force {
+            return Err(SigilError::Secret(format!(
+                "{} already exists (use --force to replace it)",
+                path.display()
+            )));
+        }
+        let (contents, public) = file::generate_identity();
+        file::write_private(&path, contents.as_bytes()).await?;
+        println!("🔑 Wrote age identity to {}", path.display());
+        println!("Public key: {}", public);
+        println!("Add it to secrets.file_recipients, then run `sigil secret rotate`");
+        return Ok(());
+    }
+
+    if config.secrets.backend != "file" {
+        return Err(SigilError::Secret(format!(
+            "Only the file backend can be rotated, unlocked or locked (secrets.backend = \"{}\")",
+            config.secrets.backend
+        )));
+    }
+    let store = file::FileStore::from_config(config);
+
+    match cmd {
+        SecretCommands::Rotate { new_passphrase } => {
+            let count = store.rotate(*new_passphrase).await?;
+            let target = match config.secrets.file_recipients.len() {
+                0 => "a passphrase".to_string(),
+                1 => "1 recipient".to_string(),
+                n => format!("{} recipients", n),
+            };
+            println!("🔄 Re-encrypted {} secrets in {} to {}", count, store.path().display(), target);
+        }
+        SecretCommands::Unlock { ttl } => {
+            let ttl = store.unlock(ttl.map(Duration::from_secs)).await?;
+            println!("🔓 {} unlocked for {}s", store.path().display(), ttl.as_secs());
+        }
+        SecretCommands::Lock => {
+            if store.lock().await {
+                println!("🔒 {} locked", store.path().display());
+            } else {
+                println!("No unlock agent running for {}", store.path().display());
+            }
+        }
+        _ => unreachable!("not a file backend command"),
+    }
+
+    Ok(())
+}
+
+/// Read a secret value without putting it on the command line: prompt on a
+/// terminal, otherwise take stdin.
+fn read_value(reference: &SecretRef) -> Result<String> {
+    if std::io::stdin().is_terminal() {
+        Ok(rpassword::prompt_password(format!("Value for {}: ", reference))?)
+    } else {
+        let mut value = String::new();
+        std::io::stdin().read_to_string(&mut value)?;
+        Ok(value.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
@@ -0,0 +1,209 @@
+use crate::config::SecretsConfig;
+use regex::Regex;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::sync::{LazyLock, RwLock};
+
+/// What secret values are replaced with.
+pub const MASK: &str = "***";
+
+/// Values shorter than this are not masked; hiding every `1` or `yes` in
+/// output would make it unreadable without protecting anything.
+pub const MIN_SECRET_LEN: usize = 4;
+
+/// Patterns masked unless `secrets.redact_builtin_patterns = false`. When a
+/// pattern has a capture group only the group is masked, so labels such as
+/// `Bearer` stay readable.
+pub const BUILTIN_PATTERNS: &[&str] = &[
+    // AWS access key IDs
+    r"\b((?:AKIA|ASIA)[0-9A-Z]{16})\b",
+    // AWS secret access keys next to their usual names
+    r"(?i)aws_secret_access_key\W{1,4}([A-Za-z0-9/+=]{40})",
+    // Authorization: Bearer <token>
+    r"(?i)\bbearer\s+([A-Za-z0-9\-._~+/]+=*)",
+    // GitHub tokens
+    r"\b(gh[pousr]_[A-Za-z0-9]{36,})\b",
+];
+
+/// Secret values and patterns to mask in anything sigil prints or stores.
+#[derive(Debug, Default)]
+pub struct Redactor {
+    /// Longest first, so a secret containing another is masked whole.
+    secrets: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+static REDACTOR: LazyLock<RwLock<Redactor>> = LazyLock::new(Default::default);
+
+/// Load masking patterns from `[secrets]`. Invalid patterns are reported by
+/// `sigil config validate` and skipped here. Patterns are matched within a
+/// single line of output.
+pub fn configure(config: &SecretsConfig) {
+    let builtin = BUILTIN_PATTERNS
+        .iter()
+        .filter(|_| config.redact_builtin_patterns)
+        .map(|pattern| pattern.to_string());
+    let patterns = builtin
+        .chain(config.redact_patterns.iter().cloned())
+        .filter_map(|pattern| Regex::new(&pattern).ok())
+        .collect();
+    REDACTOR.write().expect("redactor lock poisoned").patterns = patterns;
+}
+
+/// Mask `value` from now on, wherever it appears.
+pub fn register(value: &str) {
+    let value = value.trim();
+    if value.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut redactor = REDACTOR.write().expect("redactor lock poisoned");
+    if !redactor.secrets.iter().any(|secret| secret == value) {
+        redactor.secrets.push(value.to_string());
+        redactor.secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+    }
+}
+
+/// Mask every known secret and pattern match in `text`.
+pub fn redact(text: &str) -> String {
+    let redactor = REDACTOR.read().expect("redactor lock poisoned");
+    let ranges = redactor.matches(text);
+    apply(text, &ranges, text.len())
+}
+
+impl Redactor {
+    /// Byte ranges to mask, sorted and merged.
+    fn matches(&self, text: &str) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self
+            .secrets
+            .iter()
+            .flat_map(|secret| text.match_indices(secret.as_str()).map(|(at, s)| at..at + s.len()))
+            .collect();
+        for pattern in &self.patterns {
+            for captures in pattern.captures_iter(text) {
+                let found = captures.get(1).or_else(|| captures.get(0)).expect("group 0 always matches");
+                ranges.push(found.range());
+            }
+        }
+
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+
+    /// Length of the longest tail of `text` that could be the start of a
+    /// secret continuing in the next chunk.
+    fn partial_tail(&self, text: &str) -> usize {
+        self.secrets
+            .iter()
+            .flat_map(|secret| (1..secret.len()).rev().map(move |len| &secret.as_bytes()[..len]))
+            .filter(|prefix| text.as_bytes().ends_with(prefix))
+            .map(<[u8]>::len)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Copy `text[..end]` with `ranges` replaced by the mask.
+fn apply(text: &str, ranges: &[Range<usize>], end: usize) -> String {
+    let mut out = String::with_capacity(end);
+    let mut at = 0;
+    for range in ranges.iter().take_while(|range| range.start < end) {
+        out.push_str(&text[at..range.start]);
+        out.push_str(MASK);
+        at = range.end;
+    }
+    if at < end {
+        out.push_str(&text[at..end]);
+    }
+    out
+}
+
+/// Masks a stream that arrives in arbitrary chunks. Text is held back until
+/// it can no longer be the start of a secret or of a pattern match on the
+/// current line, so values split across chunks are still caught.
+#[derive(Debug, Default)]
+pub struct StreamRedactor {
+    pending: String,
+}
+
+/// Longest stretch without a newline held back for pattern matching.
+const MAX_PENDING_LINE: usize = 64 * 1024;
+
+impl StreamRedactor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a chunk; returns the masked text that is safe to emit now.
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.pending.push_str(chunk);
+        let redactor = REDACTOR.read().expect("redactor lock poisoned");
+
+        // Hold back a tail that may be the first part of a secret.
+        let limit = self.pending.len() - redactor.partial_tail(&self.pending);
+        // Patterns are matched on whole lines.
+        let line_end = match self.pending.rfind('\n') {
+            Some(newline) => newline + 1,
+            None if self.pending.len() > MAX_PENDING_LINE => self.pending.len(),
+            None => 0,
+        };
+        let mut cut = floor_char_boundary(&self.pending, limit.min(line_end));
+
+        let ranges = redactor.matches(&self.pending);
+        // Never split a masked range.
+        if let Some(straddling) = ranges.iter().find(|range| range.start < cut && range.end > cut) {
+            cut = straddling.end;
+        }
+        let ready = apply(&self.pending, &ranges, cut);
+        self.pending.drain(..cut);
+        ready
+    }
+
+    /// Flush whatever is still held back at the end of the stream.
+    pub fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.pending);
+        redact(&rest)
+    }
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// `MakeWriter` for tracing that masks secrets in every formatted event,
+/// span fields included. Writes to stderr, keeping logs apart from the
+/// command's result on stdout.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RedactingMakeWriter;
+
+/// Masks secrets in everything written through it.
+pub struct RedactingWriter<W: Write>(pub W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The fmt layer writes each event in one call.
+        self.0.write_all(redact(&String::from_utf8_lossy(buf)).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for RedactingMakeWriter {
+    type Writer = RedactingWriter<io::Stderr>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(io::stderr())
+    }
+}
@@ -0,0 +1,402 @@
+use super::file::{self, IDENTITY_ENV};
+use crate::error::{Result, SigilError};
+use aes_gcm::aead::consts::U32;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::aes::Aes256;
+use aes_gcm::AesGcm;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use regex::Regex;
+use serde_json::Value;
+use sha2::{Digest, Sha512};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// age private key(s), as understood by the sops CLI.
+pub const SOPS_AGE_KEY_ENV: &str = "SOPS_AGE_KEY";
+
+/// File with age private key(s), as understood by the sops CLI.
+pub const SOPS_AGE_KEY_FILE_ENV: &str = "SOPS_AGE_KEY_FILE";
+
+/// Top-level key holding SOPS metadata.
+const METADATA_KEY: &str = "sops";
+
+/// Key that stands in for a YAML comment (see [`mark_comments`]).
+const COMMENT_MARK: char = '\u{1}';
+
+/// SOPS encrypts with AES-256-GCM using 32-byte nonces.
+type SopsCipher = AesGcm<Aes256, U32>;
+
+/// Which values a SOPS file has encrypted and which the MAC covers, from
+/// its metadata.
+struct Rules {
+    unencrypted_suffix: Option<String>,
+    encrypted_suffix: Option<String>,
+    unencrypted_regex: Option<Regex>,
+    encrypted_regex: Option<Regex>,
+    mac_only_encrypted: bool,
+}
+
+impl Rules {
+    fn from_metadata(metadata: &Value) -> Result<Self> {
+        let text = |name: &str| metadata[name].as_str().filter(|value| !value.is_empty()).map(str::to_string);
+        let regex = |name: &str| {
+            text(name)
+                .map(|pattern| Regex::new(&pattern))
+                .transpose()
+                .map_err(|e| SigilError::Secret(format!("Invalid {}: {}", name, e)))
+        };
+        Ok(Rules {
+            unencrypted_suffix: text("unencrypted_suffix"),
+            encrypted_suffix: text("encrypted_suffix"),
+            unencrypted_regex: regex("unencrypted_regex")?,
+            encrypted_regex: regex("encrypted_regex")?,
+            mac_only_encrypted: metadata["mac_only_encrypted"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// Whether the value at `path` is encrypted, decided like sops does:
+    /// each rule that is set overrides the ones before it.
+    fn encrypted(&self, path: &[String]) -> bool {
+        let mut encrypted = true;
+        if let Some(suffix) = &self.unencrypted_suffix {
+            encrypted = !path.iter().any(|key| key.ends_with(suffix.as_str()));
+        }
+        if let Some(suffix) = &self.encrypted_suffix {
+            encrypted = path.iter().any(|key| key.ends_with(suffix.as_str()));
+        }
+        if let Some(regex) = &self.unencrypted_regex {
+            if path.iter().any(|key| regex.is_match(key)) {
+                encrypted = false;
+            }
+        }
+        if let Some(regex) = &self.encrypted_regex {
+            encrypted = path.iter().any(|key| regex.is_match(key));
+        }
+        encrypted
+    }
+}
+
+/// Values as sops feeds them to its MAC.
+fn mac_bytes(value: &Value) -> String {
+    match value {
+        Value::Bool(true) => "True".to_string(),
+        Value::Bool(false) => "False".to_string(),
+        Value::String(text) => text.clone(),
+        Value::Number(number) => match number.as_f64().filter(|_| number.is_f64()) {
+            Some(float) => float.to_string(),
+            None => number.to_string(),
+        },
+        _ => String::new(),
+    }
+}
+
+/// Whether a parsed document carries SOPS metadata.
+pub fn is_encrypted(document: &Value) -> bool {
+    document.get(METADATA_KEY).is_some_and(Value::is_object)
+}
+
+/// Read a YAML or JSON file, decrypting it in memory if SOPS encrypted it.
+/// `identity_file` is tried alongside the usual sops key locations.
+/// Decrypted values are masked in output from then on.
+pub fn read_file(path: &Path, identity_file: Option<&Path>) -> Result<Value> {
+    let content = std::fs::read_to_string(path)?;
+    let mut document = parse(path, &content)?;
+    if !is_encrypted(&document) {
+        return Ok(document);
+    }
+    if !matches!(path.extension().and_then(|e| e.to_str()), Some("json" | "toml")) {
+        document = parse(path, &mark_comments(&content))?;
+    }
+    decrypt(document, identity_file).map_err(|e| match e {
+        SigilError::Secret(message) => SigilError::Secret(format!("{}: {}", path.display(), message)),
+        other => other,
+    })
+}
+
+fn parse(path: &Path, content: &str) -> Result<Value> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let parsed = match extension {
+        "json" => serde_json::from_str(content).map_err(|e| e.to_string()),
+        "toml" => toml::from_str(content).map_err(|e| e.to_string()),
+        _ => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+    };
+    parsed.map_err(|e| SigilError::Secret(format!("Cannot parse {}: {}", path.display(), e)))
+}
+
+/// serde_yaml drops comments, but sops encrypts them and includes them in
+/// the MAC. Turn each full-line comment into an entry under a
+/// [`COMMENT_MARK`] key, at the indentation of the line it comes before, so
+/// it keeps its place among the values. Trailing comments are dropped.
+fn mark_comments(content: &str) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut marked = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        let Some(comment) = line.trim_start().strip_prefix('#') else {
+            marked.push_str(line);
+            marked.push('\n');
+            continue;
+        };
+        let next = lines[index + 1..].iter().find(|line| {
+            let line = line.trim_start();
+            !line.is_empty() && !line.starts_with('#')
+        });
+        let Some(next) = next else {
+            continue;
+        };
+        let body = next.trim_start();
+        let indent = &next[..next.len() - body.len()];
+        let text = serde_json::to_string(comment).expect("strings always serialize");
+        if body == "-" || body.starts_with("- ") {
+            marked.push_str(&format!("{}- {{\"\\u0001\": {}}}\n", indent, text));
+        } else {
+            marked.push_str(&format!("{}\"\\u0001{}\": {}\n", indent, index, text));
+        }
+    }
+    marked
+}
+
+/// The comment text if `value` stands in for a comment in a list.
+fn list_comment(value: &Value) -> Option<&str> {
+    match value.as_object()?.iter().next() {
+        Some((key, Value::String(text))) if key.starts_with(COMMENT_MARK) => Some(text),
+        _ => None,
+    }
+}
+
+/// Decrypt every `ENC[...]` value of a SOPS document, check the document
+/// MAC over the decrypted values, and drop the metadata.
+///
+/// Each value is authenticated by AES-GCM against its key path; the MAC
+/// makes sure none were swapped, dropped or reordered. It covers values in
+/// document order, plus comments in YAML files.
+pub fn decrypt(mut document: Value, identity_file: Option<&Path>) -> Result<Value> {
+    let metadata = document
+        .as_object_mut()
+        .and_then(|root| root.remove(METADATA_KEY))
+        .ok_or_else(|| SigilError::Secret("Not a SOPS-encrypted document".to_string()))?;
+    let rules = Rules::from_metadata(&metadata)?;
+    let key = data_key(&metadata, identity_file)?;
+    let cipher = SopsCipher::new_from_slice(&key)
+        .map_err(|_| SigilError::Secret("SOPS data key has the wrong length".to_string()))?;
+
+    let mut mac = Sha512::new();
+    decrypt_value(&mut document, &cipher, &rules, &mut mac, &mut Vec::new())?;
+    verify_mac(&metadata, &cipher, &hex::encode_upper(mac.finalize()))?;
+    Ok(document)
+}
+
+/// Compare the MAC sops stored, encrypted against `lastmodified`, with the
+/// one computed over the decrypted document.
+fn verify_mac(metadata: &Value, cipher: &SopsCipher, computed: &str) -> Result<()> {
+    let (Some(stored), Some(modified)) = (metadata["mac"].as_str(), metadata["lastmodified"].as_str()) else {
+        return Err(SigilError::Secret("File has no MAC".to_string()));
+    };
+    let (expected, _) = decrypt_scalar(stored, cipher, modified)
+        .map_err(|e| SigilError::Secret(format!("Cannot decrypt the MAC: {}", e)))?;
+    if expected != computed {
+        return Err(SigilError::Secret(
+            "MAC mismatch: values were changed, removed or reordered outside sops".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Unwrap the file's data key with one of our age identities.
+fn data_key(metadata: &Value, identity_file: Option<&Path>) -> Result<Vec<u8>> {
+    let stanzas = metadata["age"].as_array().cloned().unwrap_or_default();
+    if stanzas.is_empty() {
+        return Err(SigilError::Secret(
+            "File has no age recipients; only age-encrypted SOPS files are supported".to_string(),
+        ));
+    }
+
+    let identities = identities(identity_file)?;
+    for stanza in &stanzas {
+        let Some(armored) = stanza["enc"].as_str() else {
+            continue;
+        };
+        let reader = age::armor::ArmoredReader::new(armored.as_bytes());
+        let Ok(decryptor) = age::Decryptor::new(reader) else {
+            continue;
+        };
+        if let Ok(mut reader) = decryptor.decrypt(identities.iter().map(|i| i as &dyn age::Identity)) {
+            let mut key = Vec::new();
+            reader.read_to_end(&mut key)?;
+            return Ok(key);
+        }
+    }
+
+    let recipients: Vec<&str> = stanzas.iter().filter_map(|s| s["recipient"].as_str()).collect();
+    Err(SigilError::Secret(format!(
+        "None of our age identities can decrypt this file (recipients: {})",
+        recipients.join(", ")
+    )))
+}
+
+/// age identities from `SOPS_AGE_KEY`, `SOPS_AGE_KEY_FILE`, the sops default
+/// key file, `SIGIL_SECRETS_IDENTITY` and the sigil identity file.
+fn identities(identity_file: Option<&Path>) -> Result<Vec<age::x25519::Identity>> {
+    let mut sources: Vec<String> = [SOPS_AGE_KEY_ENV, IDENTITY_ENV]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .collect();
+
+    let mut files: Vec<PathBuf> = std::env::var_os(SOPS_AGE_KEY_FILE_ENV).map(PathBuf::from).into_iter().collect();
+    files.extend(dirs::config_dir().map(|dir| dir.join("sops/age/keys.txt")));
+    files.extend(identity_file.map(Path::to_path_buf));
+    sources.extend(files.iter().filter_map(|path| std::fs::read_to_string(path).ok()));
+
+    let identities: Vec<_> = sources
+        .iter()
+        .filter_map(|text| file::parse_identities(text).ok())
+        .flatten()
+        .collect();
+    if identities.is_empty() {
+        return Err(SigilError::Secret(format!(
+            "No age identity found; set {} or {}, or run `sigil secret keygen`",
+            SOPS_AGE_KEY_FILE_ENV, IDENTITY_ENV
+        )));
+    }
+    Ok(identities)
+}
+
+/// Decrypt the values under `value` that `rules` says are encrypted, in
+/// document order, feeding each to `mac`.
+fn decrypt_value(
+    value: &mut Value,
+    cipher: &SopsCipher,
+    rules: &Rules,
+    mac: &mut Sha512,
+    path: &mut Vec<String>,
+) -> Result<()> {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                if key.starts_with(COMMENT_MARK) {
+                    add_comment(child.as_str().unwrap_or_default(), cipher, rules, mac, path);
+                    continue;
+                }
+                path.push(key.clone());
+                decrypt_value(child, cipher, rules, mac, path)?;
+                path.pop();
+            }
+            map.retain(|key, _| !key.starts_with(COMMENT_MARK));
+        }
+        // List items share their parent's key path.
+        Value::Array(items) => {
+            for item in items.iter_mut() {
+                if let Some(comment) = list_comment(item) {
+                    add_comment(comment, cipher, rules, mac, path);
+                    continue;
+                }
+                decrypt_value(item, cipher, rules, mac, path)?;
+            }
+            items.retain(|item| list_comment(item).is_none());
+        }
+        // sops skips nulls.
+        Value::Null => {}
+        _ => {
+            let encrypted = rules.encrypted(path);
+            if encrypted {
+                let Value::String(text) = &*value else {
+                    return Err(SigilError::Secret(format!("'{}' is not encrypted", path.join("."))));
+                };
+                let aad = format!("{}:", path.join(":"));
+                let (plaintext, typed) = decrypt_scalar(text, cipher, &aad)
+                    .map_err(|e| SigilError::Secret(format!("Cannot decrypt '{}': {}", path.join("."), e)))?;
+                super::redact::register(&plaintext);
+                *value = typed;
+            }
+            if encrypted || !rules.mac_only_encrypted {
+                mac.update(mac_bytes(value).as_bytes());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Feed a comment to the MAC, decrypted if it was encrypted. Files from
+/// older sops versions have plaintext comments, which are taken as they are.
+fn add_comment(comment: &str, cipher: &SopsCipher, rules: &Rules, mac: &mut Sha512, path: &[String]) {
+    let encrypted = rules.encrypted(path);
+    if encrypted || !rules.mac_only_encrypted {
+        let aad = format!("{}:", path.join(":"));
+        let text = match decrypt_scalar(comment, cipher, &aad) {
+            Ok((plaintext, _)) if encrypted => plaintext,
+            _ => comment.to_string(),
+        };
+        mac.update(text.as_bytes());
+    }
+}
+
+/// Decrypt `ENC[AES256_GCM,data:...,iv:...,tag:...,type:...]`.
+/// Returns the plaintext and the value converted to its recorded type.
+fn decrypt_scalar(text: &str, cipher: &SopsCipher, aad: &str) -> std::result::Result<(String, Value), String> {
+    let body = text
+        .strip_prefix("ENC[AES256_GCM,")
+        .and_then(|rest| rest.strip_suffix(']'))
+        .ok_or("unsupported cipher")?;
+    let field = |name: &str| {
+        body.split(',')
+            .find_map(|part| part.strip_prefix(name).and_then(|rest| rest.strip_prefix(':')))
+            .ok_or(format!("missing {}", name))
+    };
+    let decode = |name: &str| {
+        BASE64
+            .decode(field(name)?)
+            .map_err(|e| format!("bad {}: {}", name, e))
+    };
+
+    let mut ciphertext = decode("data")?;
+    ciphertext.extend(decode("tag")?);
+    let iv = decode("iv")?;
+    if iv.len() != 32 {
+        return Err("bad iv length".to_string());
+    }
+    let plaintext = cipher
+        .decrypt(
+            iv.as_slice().into(),
+            Payload {
+                msg: &ciphertext,
+                aad: aad.as_bytes(),
+            },
+        )
+        .map_err(|_| "authentication failed (wrong key or tampered value)".to_string())?;
+    let plaintext = String::from_utf8(plaintext).map_err(|_| "value is not UTF-8".to_string())?;
+
+    let typed = match field("type")? {
+        "int" => plaintext.parse::<i64>().map(Value::from).map_err(|e| e.to_string())?,
+        "float" => plaintext.parse::<f64>().map(Value::from).map_err(|e| e.to_string())?,
+        "bool" => Value::Bool(plaintext.eq_ignore_ascii_case("true")),
+        _ => Value::String(plaintext.clone()),
+    };
+    Ok((plaintext, typed))
+}
+
+/// Flatten a decrypted document into `prefix.key.path` → value pairs, list
+/// items as `prefix.list[0]`, for use as template variables.
+pub fn flatten(prefix: &str, value: &Value, out: &mut HashMap<String, String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                flatten(&format!("{}.{}", prefix, key), child, out);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                flatten(&format!("{}[{}]", prefix, index), item, out);
+            }
+        }
+        Value::String(text) => {
+            out.insert(prefix.to_string(), text.clone());
+        }
+        Value::Null => {
+            out.insert(prefix.to_string(), String::new());
+        }
+        other => {
+            out.insert(prefix.to_string(), other.to_string());
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use super::{Secret, SecretStore};
+use crate::config::SecretsConfig;
+use crate::error::{Result, SigilError};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde_json::{json, Value};
+
+/// HashiCorp Vault KV secrets engine, version 1 or 2.
+pub struct VaultStore {
+    client: Client,
+    endpoint: String,
+    token: String,
+    mount: String,
+    kv_version: u8,
+    namespace: Option<String>,
+}
+
+impl VaultStore {
+    /// Build from `[secrets]`, falling back to `VAULT_ADDR`/`VAULT_TOKEN`.
+    pub fn from_config(config: &SecretsConfig) -> Result<Self> {
+        let endpoint = config
+            .vault_endpoint
+            .clone()
+            .or_else(|| std::env::var("VAULT_ADDR").ok())
+            .ok_or_else(|| SigilError::invalid_config("secrets.vault_endpoint", "Vault endpoint is not configured"))?;
+        // The token unlocks this backend, so it can't itself live in it.
+        let token = match &config.vault_token {
+            Some(token) => Some(token.resolve_local()?.ok_or_else(|| {
+                SigilError::invalid_config(
+                    "secrets.vault_token".to_string(),
+                    "Cannot be a secret:// reference to Vault itself; use env: or file:".to_string(),
+                )
+            })?),
+            None => None,
+        }
+        .or_else(|| std::env::var("VAULT_TOKEN").ok())
+        .ok_or_else(|| SigilError::Authentication("No Vault token (set secrets.vault_token or VAULT_TOKEN)".to_string()))?;
+        super::redact::register(&token);
+        if !matches!(config.vault_kv_version, 1 | 2) {
+            return Err(SigilError::invalid_config(
+                "secrets.vault_kv_version".to_string(),
+                format!("Unsupported KV version {}", config.vault_kv_version),
+            ));
+        }
+
+        Ok(VaultStore {
+            client: Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            token,
+            mount: config.vault_mount.trim_matches('/').to_string(),
+            kv_version: config.vault_kv_version,
+            namespace: config.vault_namespace.clone(),
+        })
+    }
+
+    /// API path for reading/writing a secret's data.
+    fn data_url(&self, path: &str) -> String {
+        match self.kv_version {
+            2 => format!("{}/v1/{}/data/{}", self.endpoint, self.mount, path),
+            _ => format!("{}/v1/{}/{}", self.endpoint, self.mount, path),
+        }
+    }
+
+    /// API path for listing and (in v2) deleting all versions.
+    fn metadata_url(&self, path: &str) -> String {
+        match self.kv_version {
+            2 => format!("{}/v1/{}/metadata/{}", self.endpoint, self.mount, path),
+            _ => format!("{}/v1/{}/{}", self.endpoint, self.mount, path),
+        }
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = request.header("X-Vault-Token", &self.token);
+        match &self.namespace {
+            Some(namespace) => request.header("X-Vault-Namespace", namespace),
+            None => request,
+        }
+    }
+
+    /// Send a request; `Ok(None)` for 404, an error for other failures.
+    async fn send(&self, request: RequestBuilder) -> Result<Option<Value>> {
+        let response = self
+            .authorize(request)
+            .send()
+            .await
+            .map_err(|e| SigilError::Network(format!("Vault request failed: {}", e)))?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if status == StatusCode::FORBIDDEN || status == StatusCode::UNAUTHORIZED {
+            return Err(SigilError::Authentication(format!("Vault denied access ({})", status)));
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| SigilError::Network(format!("Vault response unreadable: {}", e)))?;
+        if !status.is_success() {
+            return Err(SigilError::Secret(format!("Vault returned {}: {}", status, body.trim())));
+        }
+        if body.trim().is_empty() {
+            return Ok(Some(Value::Null));
+        }
+        Ok(Some(serde_json::from_str(&body)?))
+    }
+
+    fn parse_secret(&self, body: &Value) -> Secret {
+        let data = match self.kv_version {
+            2 => &body["data"]["data"],
+            _ => &body["data"],
+        };
+        data.as_object()
+            .map(|fields| {
+                fields
+                    .iter()
+                    .map(|(key, value)| {
+                        let value = match value {
+                            Value::String(s) => s.clone(),
+                            other => other.to_string(),
+                        };
+                        (key.clone(), value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl SecretStore for VaultStore {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    async fn get(&self, path: &str) -> Result<Option<Secret>> {
+        let body = self.send(self.client.get(self.data_url(path))).await?;
+        Ok(body.map(|body| self.parse_secret(&body)))
+    }
+
+    async fn set(&self, path: &str, key: &str, value: &str) -> Result<()> {
+        // KV stores whole documents, so merge into the existing fields.
+        let mut secret = self.get(path).await?.unwrap_or_default();
+        secret.insert(key.to_string(), value.to_string());
+        let body = match self.kv_version {
+            2 => json!({ "data": secret }),
+            _ => json!(secret),
+        };
+        self.send(self.client.post(self.data_url(path)).json(&body)).await?;
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        // Vault lists one directory level at a time; walk into sub-folders.
+        let mut paths = Vec::new();
+        let mut pending = vec![prefix.trim_end_matches('/').to_string()];
+
+        while let Some(dir) = pending.pop() {
+            let url = format!("{}/", self.metadata_url(&dir));
+            let Some(body) = self.send(self.client.get(url).query(&[("list", "true")])).await? else {
+                continue;
+            };
+            let keys = body["data"]["keys"].as_array().cloned().unwrap_or_default();
+            for key in keys.iter().filter_map(Value::as_str) {
+                let full = if dir.is_empty() { key.to_string() } else { format!("{}/{}", dir, key) };
+                match full.strip_suffix('/') {
+                    Some(folder) => pending.push(folder.to_string()),
+                    None => paths.push(full),
+                }
+            }
+        }
+
+        paths.sort();
+        Ok(paths)
+    }
+
+    async fn delete(&self, path: &str) -> Result<bool> {
+        if self.get(path).await?.is_none() {
+            return Ok(false);
+        }
+        self.send(self.client.delete(self.metadata_url(path))).await?;
+        Ok(true)
+    }
+}
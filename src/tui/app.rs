@@ -0,0 +1,258 @@
+use crate::modules::system::{ServiceStatus, SystemInfo};
+use crate::runtime::task_runner::{TaskDefinition, TaskInstance};
+use crate::secrets::redact::MASK;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Lines of a task log kept for display.
+const LOG_LINES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Tasks,
+    Instances,
+}
+
+/// What a key press asks the event loop to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// Start a task with `key=value` parameters.
+    Run { task: String, params: Vec<String> },
+    Cancel(Uuid),
+}
+
+/// Parameters being typed before a run.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub task: String,
+    pub input: String,
+}
+
+/// The tail of the selected instance's log.
+#[derive(Debug, Default)]
+pub struct LogView {
+    pub id: Option<Uuid>,
+    /// Bytes of the log file read so far.
+    pub offset: u64,
+    pub lines: Vec<String>,
+    /// An unterminated last line, completed by the next read.
+    partial: String,
+}
+
+impl LogView {
+    pub fn new(id: Uuid) -> Self {
+        LogView {
+            id: Some(id),
+            ..LogView::default()
+        }
+    }
+
+    pub fn push(&mut self, text: &str) {
+        self.partial.push_str(text);
+        while let Some(newline) = self.partial.find('\n') {
+            let line: String = self.partial.drain(..=newline).collect();
+            self.lines.push(line.trim_end_matches(['\r', '\n']).to_string());
+        }
+        let excess = self.lines.len().saturating_sub(LOG_LINES);
+        self.lines.drain(..excess);
+    }
+
+    /// Lines to show, the unterminated one included.
+    pub fn visible(&self) -> impl Iterator<Item = &str> {
+        self.lines
+            .iter()
+            .map(String::as_str)
+            .chain((!self.partial.is_empty()).then_some(self.partial.as_str()))
+    }
+}
+
+/// Everything the dashboard shows. Kept apart from the terminal so it can
+/// be drawn on any ratatui backend, including a `TestBackend`.
+#[derive(Debug)]
+pub struct App {
+    pub system: Option<Result<SystemInfo, String>>,
+    pub services: Vec<ServiceStatus>,
+    pub definitions: Vec<TaskDefinition>,
+    pub instances: Vec<TaskInstance>,
+    pub focus: Pane,
+    pub selected_task: usize,
+    pub selected_instance: usize,
+    pub log: LogView,
+    /// Instances started from this dashboard, which it can cancel.
+    pub started: HashSet<Uuid>,
+    pub prompt: Option<Prompt>,
+    /// Result of the last action, shown in place of the key help.
+    pub message: Option<String>,
+    pub quit: bool,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        App {
+            system: None,
+            services: Vec::new(),
+            definitions: Vec::new(),
+            instances: Vec::new(),
+            focus: Pane::Tasks,
+            selected_task: 0,
+            selected_instance: 0,
+            log: LogView::default(),
+            started: HashSet::new(),
+            prompt: None,
+            message: None,
+            quit: false,
+        }
+    }
+}
+
+impl App {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn selected_definition(&self) -> Option<&TaskDefinition> {
+        self.definitions.get(self.selected_task)
+    }
+
+    pub fn selected_instance(&self) -> Option<&TaskInstance> {
+        self.instances.get(self.selected_instance)
+    }
+
+    pub fn set_definitions(&mut self, definitions: Vec<TaskDefinition>) {
+        self.definitions = definitions;
+        self.selected_task = self.selected_task.min(self.definitions.len().saturating_sub(1));
+    }
+
+    /// Replace the instance list, keeping the same instance selected.
+    pub fn set_instances(&mut self, instances: Vec<TaskInstance>) {
+        let selected = self.selected_instance().map(|instance| instance.id);
+        self.instances = instances;
+        self.selected_instance = selected
+            .and_then(|id| self.instances.iter().position(|instance| instance.id == id))
+            .unwrap_or(self.selected_instance)
+            .min(self.instances.len().saturating_sub(1));
+    }
+
+    /// Show a run that was just started, selected, before its process
+    /// has saved any state.
+    pub fn add_started(&mut self, instance: TaskInstance) {
+        self.started.insert(instance.id);
+        self.instances.retain(|existing| existing.id != instance.id);
+        self.instances.insert(0, instance);
+        self.selected_instance = 0;
+        self.focus = Pane::Instances;
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) -> Option<Request> {
+        if key.kind != KeyEventKind::Press {
+            return None;
+        }
+        if self.prompt.is_some() {
+            return self.on_prompt_key(key);
+        }
+        self.message = None;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.focus = match self.focus {
+                    Pane::Tasks => Pane::Instances,
+                    Pane::Instances => Pane::Tasks,
+                }
+            }
+            KeyCode::Left => self.focus = Pane::Tasks,
+            KeyCode::Right => self.focus = Pane::Instances,
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Enter | KeyCode::Char('r') if self.focus == Pane::Tasks => return self.run_selected(),
+            KeyCode::Char('R') | KeyCode::Char('r') if self.focus == Pane::Instances => return self.rerun_selected(),
+            KeyCode::Char('c') => return self.cancel_selected(),
+            _ => {}
+        }
+        None
+    }
+
+    fn on_prompt_key(&mut self, key: KeyEvent) -> Option<Request> {
+        let prompt = self.prompt.as_mut()?;
+        match key.code {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Enter => {
+                let prompt = self.prompt.take()?;
+                let params = prompt.input.split_whitespace().map(str::to_string).collect();
+                return Some(Request::Run { task: prompt.task, params });
+            }
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Char(c) => prompt.input.push(c),
+            _ => {}
+        }
+        None
+    }
+
+    fn move_selection(&mut self, by: isize) {
+        let (selected, len) = match self.focus {
+            Pane::Tasks => (&mut self.selected_task, self.definitions.len()),
+            Pane::Instances => (&mut self.selected_instance, self.instances.len()),
+        };
+        *selected = selected.saturating_add_signed(by).min(len.saturating_sub(1));
+    }
+
+    /// Run the selected task, asking for parameters first if it has any.
+    fn run_selected(&mut self) -> Option<Request> {
+        let definition = self.selected_definition()?;
+        if definition.parameters.is_empty() {
+            return Some(Request::Run {
+                task: definition.name.clone(),
+                params: Vec::new(),
+            });
+        }
+        let mut names: Vec<&String> = definition.parameters.keys().collect();
+        names.sort();
+        let input = names
+            .into_iter()
+            .map(|name| {
+                let default = definition.parameters[name].default_value.as_deref().unwrap_or("");
+                format!("{}={}", name, default)
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.prompt = Some(Prompt {
+            task: definition.name.clone(),
+            input,
+        });
+        None
+    }
+
+    /// Run the selected instance's task again with the same parameters.
+    fn rerun_selected(&mut self) -> Option<Request> {
+        let instance = self.selected_instance()?;
+        if instance.parameters.values().any(|value| value.contains(MASK)) {
+            self.message = Some("Parameters of this run were masked; start it from the task list".to_string());
+            return None;
+        }
+        let mut params: Vec<String> = instance
+            .parameters
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        params.sort();
+        Some(Request::Run {
+            task: instance.definition_name.clone(),
+            params,
+        })
+    }
+
+    fn cancel_selected(&mut self) -> Option<Request> {
+        let instance = self.selected_instance()?;
+        if instance.is_finished() {
+            self.message = Some("This run has already finished".to_string());
+        } else if !self.started.contains(&instance.id) {
+            self.message = Some("Only runs started from this dashboard can be cancelled".to_string());
+        } else {
+            return Some(Request::Cancel(instance.id));
+        }
+        None
+    }
+}
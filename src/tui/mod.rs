@@ -0,0 +1,321 @@
+//! `sigil tui`: a dashboard of system metrics, watched services and task
+//! runs, following the selected run's log as it is written.
+//!
+//! Runs started here are `sigil task run` processes (see
+//! [`crate::runtime::background`]), so they keep going if the dashboard
+//! is closed. [`ui::draw`] only reads an [`App`], which makes the screen
+//! reproducible on ratatui's `TestBackend`.
+
+mod app;
+#[cfg(test)]
+mod tests;
+mod ui;
+
+use crate::audit;
+use crate::config::{Config, LoadOptions};
+use crate::error::{Result, SigilError};
+use crate::logging;
+use crate::modules::system::{self, ServiceStatus, SystemInfo};
+use crate::output::{self, say};
+use crate::runtime::background;
+use crate::runtime::task_runner::{self, TaskInstance};
+use app::{App, LogView, Request};
+use crossterm::event::{self, Event, KeyEvent};
+use ratatui::DefaultTerminal;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Child;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// How often runs and the selected log are re-read.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often system metrics and services are sampled.
+const METRICS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Task definitions are re-read every this many refreshes.
+const DEFINITIONS_EVERY: u32 = 10;
+
+/// A run started from the dashboard.
+struct Run {
+    child: Child,
+    /// Shown until the task process saves its own state.
+    accepted: TaskInstance,
+    cancelled: bool,
+}
+
+struct Sample {
+    system: std::result::Result<SystemInfo, String>,
+    services: Vec<ServiceStatus>,
+}
+
+pub async fn handle_command(services: &[String], config: &Config, options: &LoadOptions) -> Result<()> {
+    if !std::io::stdout().is_terminal() {
+        return Err(SigilError::module("tui", "The dashboard needs an interactive terminal"));
+    }
+    let mut watched = config.modules.system.watched_services.clone();
+    for service in services {
+        if !watched.contains(service) {
+            watched.push(service.clone());
+        }
+    }
+
+    // Console logs would draw over the dashboard; file logs carry on.
+    let mut quiet = config.clone();
+    quiet.logging.console_enabled = false;
+    logging::apply(&quiet);
+
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, watched, config, options).await;
+    ratatui::try_restore()?;
+    logging::apply(config);
+
+    let running = result?;
+    if running > 0 {
+        say!(
+            "{}",
+            output::decorate("⏳", format!("{} run(s) still going; check on them with 'sigil task status'", running))
+        );
+    }
+    Ok(())
+}
+
+/// Run the dashboard until quit. Returns how many runs started here are
+/// still going.
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    services: Vec<String>,
+    config: &Config,
+    options: &LoadOptions,
+) -> Result<usize> {
+    let mut app = App::new();
+    let mut runs: HashMap<Uuid, Run> = HashMap::new();
+    refresh(&mut app, &runs, config, true).await;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let (key_tx, mut keys) = mpsc::channel(32);
+    let input = std::thread::spawn({
+        let stop = Arc::clone(&stop);
+        move || read_keys(key_tx, &stop)
+    });
+    let (sample_tx, mut samples) = mpsc::channel(1);
+    let collector = tokio::spawn(collect_metrics(services, sample_tx));
+    let mut ticker = tokio::time::interval(REFRESH_INTERVAL);
+    let mut ticks = 0u32;
+
+    let result = loop {
+        if let Err(e) = terminal.draw(|frame| ui::draw(frame, &app)) {
+            break Err(e.into());
+        }
+        if app.quit {
+            break Ok(());
+        }
+        tokio::select! {
+            Some(key) = keys.recv() => {
+                if let Some(request) = app.on_key(key) {
+                    handle_request(&mut app, &mut runs, request, config, options).await;
+                }
+            }
+            Some(sample) = samples.recv() => {
+                app.system = Some(sample.system);
+                app.services = sample.services;
+            }
+            _ = ticker.tick() => {
+                reap(&mut runs, config).await;
+                ticks = ticks.wrapping_add(1);
+                refresh(&mut app, &runs, config, ticks.is_multiple_of(DEFINITIONS_EVERY)).await;
+            }
+        }
+    };
+
+    stop.store(true, Ordering::Relaxed);
+    collector.abort();
+    let _ = input.join();
+    result.map(|()| runs.len())
+}
+
+/// Forward key presses until `stop` is set or the dashboard has gone.
+fn read_keys(keys: mpsc::Sender<KeyEvent>, stop: &AtomicBool) {
+    while !stop.load(Ordering::Relaxed) {
+        match event::poll(Duration::from_millis(100)) {
+            Ok(true) => {
+                if let Ok(Event::Key(key)) = event::read() {
+                    if keys.blocking_send(key).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(false) => {}
+            Err(_) => return,
+        }
+    }
+}
+
+async fn collect_metrics(services: Vec<String>, samples: mpsc::Sender<Sample>) {
+    loop {
+        let system = system::get_system_info().await.map_err(|e| e.to_string());
+        let mut statuses = Vec::new();
+        for service in &services {
+            if let Ok(status) = system::get_service_status(service).await {
+                statuses.push(status);
+            }
+        }
+        let sample = Sample {
+            system,
+            services: statuses,
+        };
+        if samples.send(sample).await.is_err() {
+            return;
+        }
+        tokio::time::sleep(METRICS_INTERVAL).await;
+    }
+}
+
+async fn handle_request(
+    app: &mut App,
+    runs: &mut HashMap<Uuid, Run>,
+    request: Request,
+    config: &Config,
+    options: &LoadOptions,
+) {
+    match request {
+        Request::Run { task, params } => match start(runs, &task, &params, config, options).await {
+            Ok(instance) => {
+                app.message = Some(format!("Started '{}' as {}", task, instance.id));
+                app.add_started(instance);
+            }
+            Err(e) => app.message = Some(e.to_string()),
+        },
+        Request::Cancel(id) => {
+            let Some(run) = runs.get_mut(&id) else {
+                return;
+            };
+            let event = audit::Event::new("task.cancel")
+                .param("task", run.accepted.definition_name.as_str())
+                .param("instance_id", id.to_string());
+            let result = background::cancel(&run.child).await;
+            audit::record(&config.audit, event, &result);
+            app.message = Some(match result {
+                Ok(()) => {
+                    run.cancelled = true;
+                    format!("Cancelling {}", id)
+                }
+                Err(e) => e.to_string(),
+            });
+        }
+    }
+}
+
+/// Check the task and its parameters, then start it in the background.
+async fn start(
+    runs: &mut HashMap<Uuid, Run>,
+    task: &str,
+    params: &[String],
+    config: &Config,
+    options: &LoadOptions,
+) -> Result<TaskInstance> {
+    if runs.len() >= config.tasks.max_concurrent_tasks {
+        return Err(SigilError::task_execution(format!(
+            "{} tasks are already running (tasks.max_concurrent_tasks)",
+            runs.len()
+        )));
+    }
+    let (_, parameters) = task_runner::prepare_task(task, params, config).await?;
+    let args: Vec<String> = params
+        .iter()
+        .flat_map(|param| ["--params".to_string(), param.clone()])
+        .collect();
+    let id = Uuid::new_v4();
+    let child = background::spawn(id, task, &args, options, config).await?;
+    let accepted = TaskInstance::new(id, task, parameters).redacted();
+    runs.insert(
+        id,
+        Run {
+            child,
+            accepted: accepted.clone(),
+            cancelled: false,
+        },
+    );
+    Ok(accepted)
+}
+
+/// Settle runs whose task process has exited.
+async fn reap(runs: &mut HashMap<Uuid, Run>, config: &Config) {
+    let mut exited = Vec::new();
+    for (id, run) in runs.iter_mut() {
+        match run.child.try_wait() {
+            Ok(Some(status)) => exited.push((*id, Ok(status))),
+            Ok(None) => {}
+            Err(e) => exited.push((*id, Err(e))),
+        }
+    }
+    for (id, status) in exited {
+        if let Some(run) = runs.remove(&id) {
+            background::settle(run.accepted, &status, run.cancelled, config).await;
+        }
+    }
+}
+
+/// Re-read saved runs, and task definitions when `definitions` is set,
+/// then follow the selected run's log.
+async fn refresh(app: &mut App, runs: &HashMap<Uuid, Run>, config: &Config, definitions: bool) {
+    if definitions {
+        match task_runner::task_definitions(config).await {
+            Ok(found) => app.set_definitions(found),
+            Err(e) => app.message = Some(e.to_string()),
+        }
+    }
+    match task_runner::task_instances(config).await {
+        Ok(mut instances) => {
+            for run in runs.values() {
+                if !instances.iter().any(|instance| instance.id == run.accepted.id) {
+                    instances.push(run.accepted.clone());
+                }
+            }
+            instances.sort_by_key(|instance| std::cmp::Reverse(instance.created_at));
+            app.set_instances(instances);
+        }
+        Err(e) => app.message = Some(e.to_string()),
+    }
+    follow_log(app, config);
+}
+
+/// Read what was added to the selected run's log since the last refresh.
+/// Runs started from the command line have no log; their saved output is
+/// shown instead.
+fn follow_log(app: &mut App, config: &Config) {
+    let Some(instance) = app.selected_instance() else {
+        app.log = LogView::default();
+        return;
+    };
+    let id = instance.id;
+    let path = background::log_path(config, &id);
+    let Ok(mut file) = std::fs::File::open(&path) else {
+        let mut saved = instance.output.clone().unwrap_or_default();
+        if let Some(error) = &instance.error {
+            saved.push_str(&format!("\n{}\n", error));
+        }
+        app.log = LogView::new(id);
+        app.log.push(&saved);
+        return;
+    };
+    if app.log.id != Some(id) {
+        app.log = LogView::new(id);
+    }
+
+    let mut added = Vec::new();
+    if file.seek(SeekFrom::Start(app.log.offset)).is_err() || file.read_to_end(&mut added).is_err() {
+        return;
+    }
+    // Leave a character cut off at the end for the next read.
+    let read = match std::str::from_utf8(&added) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => added.len(),
+    };
+    app.log.offset += read as u64;
+    app.log.push(&String::from_utf8_lossy(&added[..read]));
+}
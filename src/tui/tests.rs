@@ -0,0 +1,222 @@
+//! Drives [`App`] with key presses the way the event loop does and checks
+//! what [`ui::draw`] puts on a `TestBackend`.
+
+use super::app::{App, Pane, Request};
+use super::ui;
+use crate::runtime::task_runner::{TaskDefinition, TaskInstance, TaskStatus};
+use crate::secrets::redact::MASK;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::backend::TestBackend;
+use ratatui::Terminal;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+fn definition(toml: &str) -> TaskDefinition {
+    toml::from_str(toml).expect("valid task definition")
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.set_definitions(vec![
+        definition(
+            r#"
+            name = "backup"
+            description = "Nightly backup"
+            [parameters.target]
+            description = "Where to"
+            required = true
+            default_value = "nas"
+            parameter_type = "String"
+            [command.Shell]
+            script = "true"
+            "#,
+        ),
+        definition(
+            r#"
+            name = "cleanup"
+            [parameters]
+            [command.Shell]
+            script = "true"
+            "#,
+        ),
+    ]);
+    app
+}
+
+fn press(app: &mut App, code: KeyCode) -> Option<Request> {
+    app.on_key(KeyEvent::new(code, KeyModifiers::NONE))
+}
+
+fn type_text(app: &mut App, text: &str) {
+    for c in text.chars() {
+        assert_eq!(press(app, KeyCode::Char(c)), None);
+    }
+}
+
+/// The screen as lines of text.
+fn screen(app: &App) -> Vec<String> {
+    let mut terminal = Terminal::new(TestBackend::new(120, 40)).expect("test terminal");
+    terminal.draw(|frame| ui::draw(frame, app)).expect("draw");
+    let buffer = terminal.backend().buffer();
+    buffer
+        .content()
+        .chunks(buffer.area.width as usize)
+        .map(|row| row.iter().map(|cell| cell.symbol()).collect())
+        .collect()
+}
+
+fn shows(app: &App, text: &str) -> bool {
+    screen(app).iter().any(|line| line.contains(text))
+}
+
+/// What the event loop does with an accepted run request.
+fn start(app: &mut App, request: Option<Request>) -> Uuid {
+    let Some(Request::Run { task, params }) = request else {
+        panic!("expected a run request, got {:?}", request);
+    };
+    let parameters = params
+        .iter()
+        .filter_map(|param| param.split_once('='))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    let instance = TaskInstance::new(Uuid::new_v4(), &task, parameters);
+    let id = instance.id;
+    app.message = Some(format!("Started '{}' as {}", task, id));
+    app.add_started(instance);
+    id
+}
+
+#[test]
+fn lists_tasks_with_the_first_selected() {
+    let app = app();
+    assert!(shows(&app, "> backup  Nightly backup"));
+    assert!(shows(&app, "cleanup"));
+    assert!(shows(&app, "q quit"));
+}
+
+#[test]
+fn runs_a_task_without_parameters_at_once() {
+    let mut app = app();
+    assert_eq!(press(&mut app, KeyCode::Down), None);
+    assert!(shows(&app, "> cleanup"));
+    assert_eq!(
+        press(&mut app, KeyCode::Enter),
+        Some(Request::Run {
+            task: "cleanup".to_string(),
+            params: Vec::new(),
+        })
+    );
+}
+
+#[test]
+fn asks_for_parameters_then_shows_the_started_run() {
+    let mut app = app();
+    assert_eq!(press(&mut app, KeyCode::Char('r')), None);
+    assert!(shows(&app, "Parameters for backup: target=nas_"));
+
+    for _ in 0.."nas".len() {
+        press(&mut app, KeyCode::Backspace);
+    }
+    type_text(&mut app, "tape");
+    assert!(shows(&app, "Parameters for backup: target=tape_"));
+
+    let request = press(&mut app, KeyCode::Enter);
+    assert_eq!(
+        request,
+        Some(Request::Run {
+            task: "backup".to_string(),
+            params: vec!["target=tape".to_string()],
+        })
+    );
+    let id = start(&mut app, request);
+    assert_eq!(app.focus, Pane::Instances);
+    assert!(shows(&app, &format!("● {}", &id.to_string()[..8])));
+    assert!(shows(&app, "Pending"));
+    assert!(shows(&app, &format!("Log: backup {}", &id.to_string()[..8])));
+    assert!(shows(&app, &format!("Started 'backup' as {}", id)));
+}
+
+#[test]
+fn escape_leaves_the_prompt_without_running() {
+    let mut app = app();
+    press(&mut app, KeyCode::Enter);
+    assert!(app.prompt.is_some());
+    assert_eq!(press(&mut app, KeyCode::Esc), None);
+    assert!(app.prompt.is_none());
+    assert!(!app.quit);
+    assert!(shows(&app, "q quit"));
+}
+
+#[test]
+fn cancels_only_unfinished_runs_started_here() {
+    let mut app = app();
+    press(&mut app, KeyCode::Down);
+    let request = press(&mut app, KeyCode::Enter);
+    let id = start(&mut app, request);
+    assert_eq!(press(&mut app, KeyCode::Char('c')), Some(Request::Cancel(id)));
+
+    // The task process saves its state once it has stopped.
+    let mut cancelled = app.instances[0].clone();
+    cancelled.status = TaskStatus::Cancelled;
+    let other = TaskInstance::new(Uuid::new_v4(), "backup", HashMap::new());
+    app.set_instances(vec![cancelled, other]);
+    assert_eq!(app.selected_instance().map(|instance| instance.id), Some(id));
+    assert!(shows(&app, "Cancelled"));
+
+    assert_eq!(press(&mut app, KeyCode::Char('c')), None);
+    assert!(shows(&app, "This run has already finished"));
+
+    press(&mut app, KeyCode::Down);
+    assert_eq!(press(&mut app, KeyCode::Char('c')), None);
+    assert!(shows(&app, "Only runs started from this dashboard can be cancelled"));
+}
+
+#[test]
+fn reruns_with_the_same_parameters() {
+    let mut app = app();
+    let mut finished = TaskInstance::new(
+        Uuid::new_v4(),
+        "backup",
+        HashMap::from([
+            ("target".to_string(), "tape".to_string()),
+            ("compress".to_string(), "yes".to_string()),
+        ]),
+    );
+    finished.status = TaskStatus::Completed;
+    app.set_instances(vec![finished]);
+    press(&mut app, KeyCode::Tab);
+    assert!(shows(&app, "Completed"));
+
+    let request = press(&mut app, KeyCode::Char('r'));
+    assert_eq!(
+        request,
+        Some(Request::Run {
+            task: "backup".to_string(),
+            params: vec!["compress=yes".to_string(), "target=tape".to_string()],
+        })
+    );
+    let id = start(&mut app, request);
+    assert_eq!(app.instances.len(), 2);
+    assert_eq!(app.selected_instance().map(|instance| instance.id), Some(id));
+}
+
+#[test]
+fn does_not_rerun_with_masked_parameters() {
+    let mut app = app();
+    let masked = TaskInstance::new(
+        Uuid::new_v4(),
+        "backup",
+        HashMap::from([("token".to_string(), MASK.to_string())]),
+    );
+    app.set_instances(vec![masked]);
+    press(&mut app, KeyCode::Right);
+    assert_eq!(press(&mut app, KeyCode::Char('R')), None);
+    assert!(shows(&app, "Parameters of this run were masked; start it from the task list"));
+}
+
+#[test]
+fn quits_on_q() {
+    let mut app = app();
+    assert_eq!(press(&mut app, KeyCode::Char('q')), None);
+    assert!(app.quit);
+}
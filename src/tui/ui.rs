@@ -0,0 +1,264 @@
+use super::app::{App, Pane};
+use crate::modules::system::SystemInfo;
+use crate::runtime::task_runner::{TaskInstance, TaskStatus};
+use chrono::{Local, Utc};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, LineGauge, List, ListItem, ListState, Paragraph, Row, Table, TableState};
+use ratatui::Frame;
+
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Colors are left out when `NO_COLOR` is set; bold and reversed text
+/// still mark what is selected.
+fn fg(color: Color) -> Style {
+    if crate::output::no_color() {
+        Style::default()
+    } else {
+        Style::default().fg(color)
+    }
+}
+
+fn pane(title: &str, focused: bool) -> Block<'_> {
+    let block = Block::default().borders(Borders::ALL).title(format!(" {} ", title));
+    if focused {
+        block.border_style(fg(Color::Cyan).add_modifier(Modifier::BOLD))
+    } else {
+        block
+    }
+}
+
+/// Draw the whole dashboard.
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [top, middle, log, footer] = Layout::vertical([
+        Constraint::Length(8),
+        Constraint::Min(6),
+        Constraint::Percentage(40),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [system, services] = Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(top);
+    let [tasks, instances] = Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)]).areas(middle);
+
+    draw_system(frame, system, app);
+    draw_services(frame, services, app);
+    draw_tasks(frame, tasks, app);
+    draw_instances(frame, instances, app);
+    draw_log(frame, log, app);
+    draw_footer(frame, footer, app);
+}
+
+fn draw_system(frame: &mut Frame, area: Rect, app: &App) {
+    let block = pane("System", false);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let info = match &app.system {
+        None => return frame.render_widget(Paragraph::new("Collecting metrics..."), inner),
+        Some(Err(e)) => return frame.render_widget(Paragraph::new(e.as_str()).style(fg(Color::Red)), inner),
+        Some(Ok(info)) => info,
+    };
+
+    let [host, load, cpu, memory, disks] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Min(0),
+    ])
+    .areas(inner);
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled(info.hostname.as_str(), Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!("  {}", info.uptime)),
+        ])),
+        host,
+    );
+    frame.render_widget(Paragraph::new(format!("Load {}", load_average(info))), load);
+    frame.render_widget(
+        gauge(
+            "CPU",
+            info.cpu_info.usage_percent,
+            format!("{:.1}% of {} cores", info.cpu_info.usage_percent, info.cpu_info.cores),
+        ),
+        cpu,
+    );
+    frame.render_widget(
+        gauge(
+            "Mem",
+            info.memory_info.usage_percent,
+            format!(
+                "{:.1}% ({:.1} / {:.1} GiB)",
+                info.memory_info.usage_percent,
+                info.memory_info.used as f64 / GIB,
+                info.memory_info.total as f64 / GIB
+            ),
+        ),
+        memory,
+    );
+    // Block devices only; tmpfs and friends would crowd them out.
+    let lines: Vec<Line> = info
+        .disk_usage
+        .iter()
+        .filter(|disk| disk.filesystem.starts_with("/dev/"))
+        .map(|disk| Line::from(format!("{:<12} {:>5} of {}", disk.mount_point, disk.usage_percent, disk.size)))
+        .collect();
+    frame.render_widget(Paragraph::new(lines), disks);
+}
+
+/// The 1, 5 and 15 minute averages, without the run queue and last PID.
+fn load_average(info: &SystemInfo) -> String {
+    info.load_average.split_whitespace().take(3).collect::<Vec<_>>().join(" ")
+}
+
+fn gauge(label: &str, percent: f64, text: String) -> LineGauge<'static> {
+    let color = match percent {
+        p if p >= 90.0 => Color::Red,
+        p if p >= 75.0 => Color::Yellow,
+        _ => Color::Green,
+    };
+    LineGauge::default()
+        .ratio((percent / 100.0).clamp(0.0, 1.0))
+        .label(format!("{:<4}{}", label, text))
+        .filled_style(fg(color))
+}
+
+fn draw_services(frame: &mut Frame, area: Rect, app: &App) {
+    let block = pane("Services", false);
+    if app.services.is_empty() {
+        let hint = Paragraph::new("No watched services (--service or modules.system.watched_services)").block(block);
+        return frame.render_widget(hint, area);
+    }
+    let rows = app.services.iter().map(|service| {
+        let (state, color) = if service.active { ("active", Color::Green) } else { ("inactive", Color::Red) };
+        Row::new(vec![
+            Span::raw(service.name.clone()),
+            Span::styled(format!("● {}", state), fg(color)),
+            Span::raw(if service.enabled { "enabled" } else { "disabled" }),
+        ])
+    });
+    let table = Table::new(rows, [Constraint::Fill(1), Constraint::Length(10), Constraint::Length(8)])
+        .header(Row::new(["UNIT", "STATE", "BOOT"]).style(Style::default().add_modifier(Modifier::BOLD)))
+        .block(block);
+    frame.render_widget(table, area);
+}
+
+fn draw_tasks(frame: &mut Frame, area: Rect, app: &App) {
+    let focused = app.focus == Pane::Tasks;
+    let items: Vec<ListItem> = app
+        .definitions
+        .iter()
+        .map(|definition| {
+            let mut spans = vec![Span::raw(definition.name.clone())];
+            if let Some(description) = &definition.description {
+                spans.push(Span::styled(format!("  {}", description), fg(Color::DarkGray)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(pane("Tasks", focused))
+        .highlight_style(highlight(focused))
+        .highlight_symbol("> ");
+    let mut state = ListState::default().with_selected((!app.definitions.is_empty()).then_some(app.selected_task));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn highlight(focused: bool) -> Style {
+    if focused {
+        Style::default().add_modifier(Modifier::REVERSED)
+    } else {
+        Style::default().add_modifier(Modifier::BOLD)
+    }
+}
+
+fn status_style(status: &TaskStatus) -> Style {
+    match status {
+        TaskStatus::Completed => fg(Color::Green),
+        TaskStatus::Failed => fg(Color::Red),
+        TaskStatus::Cancelled => fg(Color::DarkGray),
+        TaskStatus::Pending | TaskStatus::Running | TaskStatus::Retrying => fg(Color::Yellow),
+    }
+}
+
+/// How long an instance ran, or has been running.
+fn duration(instance: &TaskInstance) -> String {
+    let Some(started) = instance.started_at else {
+        return String::new();
+    };
+    let seconds = (instance.completed_at.unwrap_or_else(Utc::now) - started).num_seconds().max(0);
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m{:02}s", s / 60, s % 60),
+        s => format!("{}h{:02}m", s / 3600, s % 3600 / 60),
+    }
+}
+
+fn draw_instances(frame: &mut Frame, area: Rect, app: &App) {
+    let focused = app.focus == Pane::Instances;
+    let today = Local::now().date_naive();
+    let rows = app.instances.iter().map(|instance| {
+        let created = instance.created_at.with_timezone(&Local);
+        let created = if created.date_naive() == today {
+            created.format("%H:%M:%S").to_string()
+        } else {
+            created.format("%Y-%m-%d %H:%M").to_string()
+        };
+        let marker = if app.started.contains(&instance.id) { "●" } else { " " };
+        Row::new(vec![
+            Span::raw(format!("{} {}", marker, &instance.id.to_string()[..8])),
+            Span::raw(instance.definition_name.clone()),
+            Span::styled(format!("{:?}", instance.status), status_style(&instance.status)),
+            Span::raw(created),
+            Span::raw(duration(instance)),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(10),
+            Constraint::Fill(1),
+            Constraint::Length(9),
+            Constraint::Length(16),
+            Constraint::Length(7),
+        ],
+    )
+    .header(Row::new(["  ID", "TASK", "STATUS", "CREATED", "TIME"]).style(Style::default().add_modifier(Modifier::BOLD)))
+    .block(pane("Runs", focused))
+    .row_highlight_style(highlight(focused));
+    let mut state = TableState::default().with_selected((!app.instances.is_empty()).then_some(app.selected_instance));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_log(frame: &mut Frame, area: Rect, app: &App) {
+    let title = match app.selected_instance() {
+        Some(instance) => format!("Log: {} {}", instance.definition_name, &instance.id.to_string()[..8]),
+        None => "Log".to_string(),
+    };
+    let block = pane(&title, false);
+    let height = block.inner(area).height as usize;
+    let lines: Vec<&str> = app.log.visible().collect();
+    let tail: Vec<Line> = lines[lines.len().saturating_sub(height)..]
+        .iter()
+        .map(|line| Line::from(*line))
+        .collect();
+    frame.render_widget(Paragraph::new(tail).block(block), area);
+}
+
+fn draw_footer(frame: &mut Frame, area: Rect, app: &App) {
+    let line = if let Some(prompt) = &app.prompt {
+        Line::from(vec![
+            Span::styled(format!("Parameters for {}: ", prompt.task), Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!("{}_", prompt.input)),
+            Span::styled("  (enter run, esc back)", fg(Color::DarkGray)),
+        ])
+    } else if let Some(message) = &app.message {
+        Line::from(message.as_str())
+    } else {
+        Line::styled(
+            "q quit  tab switch pane  ↑↓ select  enter/r run  r re-run  c cancel",
+            fg(Color::DarkGray),
+        )
+    };
+    frame.render_widget(Paragraph::new(line), area);
+}